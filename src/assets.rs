@@ -0,0 +1,75 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{UtxoOutpoint, VotingError};
+
+// Bitcoin-native assets (Runes and inscription collections) used for poll
+// eligibility and vote weighting
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BitcoinAsset {
+    /// A Rune, identified by the block height and transaction index of its etching
+    Rune { block: u64, tx: u32 },
+    /// An inscription collection, identified by its parent inscription
+    InscriptionCollection { parent_txid: [u8; 32], parent_index: u32 },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AssetHolding {
+    /// Outpoint holding the asset
+    pub outpoint: UtxoOutpoint,
+    /// Amount held (Rune units, or number of inscriptions from the collection)
+    pub amount: u128,
+}
+
+/// Source of truth for the Bitcoin-native assets held at an outpoint
+pub trait AssetIndexer {
+    /// Amount of `asset` held at `outpoint` (zero if none)
+    fn asset_amount(&self, asset: &BitcoinAsset, outpoint: &UtxoOutpoint) -> u128;
+}
+
+/// Holdings attested by the poll's oracle, which acts as the indexer on-chain
+pub struct AttestedHoldings<'a> {
+    asset: BitcoinAsset,
+    holdings: &'a [AssetHolding],
+}
+
+impl<'a> AttestedHoldings<'a> {
+    pub fn new(asset: BitcoinAsset, holdings: &'a [AssetHolding]) -> Self {
+        Self { asset, holdings }
+    }
+}
+
+impl AssetIndexer for AttestedHoldings<'_> {
+    fn asset_amount(&self, asset: &BitcoinAsset, outpoint: &UtxoOutpoint) -> u128 {
+        if *asset != self.asset {
+            return 0;
+        }
+
+        self.holdings
+            .iter()
+            .filter(|holding| holding.outpoint == *outpoint)
+            .map(|holding| holding.amount)
+            .fold(0u128, |total, amount| total.saturating_add(amount))
+    }
+}
+
+/// Total amount of `asset` held across `outpoints`.
+/// Every outpoint must hold some of the asset, and the total must fit a vote weight.
+pub fn total_holdings(
+    indexer: &impl AssetIndexer,
+    asset: &BitcoinAsset,
+    outpoints: &[UtxoOutpoint],
+) -> Result<u64, VotingError> {
+    let mut total: u128 = 0;
+
+    for outpoint in outpoints {
+        let amount = indexer.asset_amount(asset, outpoint);
+        if amount == 0 {
+            return Err(VotingError::InvalidAssetProof);
+        }
+
+        total = total.checked_add(amount).ok_or(VotingError::InvalidVoteWeight)?;
+    }
+
+    u64::try_from(total).map_err(|_| VotingError::InvalidVoteWeight)
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Minimal `--name value` argument parsing for the CLI
+
+/// Options that take no value
+const SWITCHES: &[&str] = &[
+    "json",
+    "private",
+    "allow-revote",
+    "encrypted",
+    "require-registered",
+    "early-close",
+    "not-accepting",
+    "restart",
+    "apply",
+];
+
+#[derive(Debug, Default)]
+pub struct Args {
+    pub command: String,
+    pub positional: Vec<String>,
+    options: HashMap<String, Vec<String>>,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if let Some(name) = arg.strip_prefix("--") {
+                let value = if SWITCHES.contains(&name) {
+                    String::new()
+                } else {
+                    args.next().ok_or_else(|| format!("--{} needs a value", name))?
+                };
+                parsed.options.entry(name.to_string()).or_default().push(value);
+            } else if parsed.command.is_empty() {
+                parsed.command = arg;
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn switch(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    pub fn optional(&self, name: &str) -> Option<&str> {
+        self.options.get(name).and_then(|values| values.last()).map(String::as_str)
+    }
+
+    pub fn required(&self, name: &str) -> Result<&str, String> {
+        self.optional(name).ok_or_else(|| format!("missing --{}", name))
+    }
+
+    /// Every value of an option that may be repeated, in order
+    pub fn all(&self, name: &str) -> &[String] {
+        self.options.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn parse_optional<T: FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.optional(name)
+            .map(|value| value.parse().map_err(|_| format!("invalid --{}: {}", name, value)))
+            .transpose()
+    }
+
+    pub fn parse_required<T: FromStr>(&self, name: &str) -> Result<T, String> {
+        self.parse_optional(name)?.ok_or_else(|| format!("missing --{}", name))
+    }
+
+    /// Positional argument `index` (after the subcommand), named `name` in errors
+    pub fn positional<T: FromStr>(&self, index: usize, name: &str) -> Result<T, String> {
+        let value = self.positional.get(index).ok_or_else(|| format!("missing <{}>", name))?;
+        value.parse().map_err(|_| format!("invalid <{}>: {}", name, value))
+    }
+}
@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+
+use arch_program::bitcoin::secp256k1::Keypair;
+use arch_program::bitcoin::Network;
+use arch_program::instruction::Instruction;
+use arch_program::pubkey::Pubkey;
+use arch_program::sanitized::ArchMessage;
+use arch_sdk::blocking::ArchRpcClient;
+use arch_sdk::{build_and_sign_transaction, ArchError, Config};
+use arch_voting_contract::indexer::{AccountSource, Snapshot};
+use arch_voting_contract::simulator::Simulator;
+use arch_voting_contract::{hex, ProgramAccount, ACCOUNT_VERSION};
+
+use crate::keys;
+
+// Where the CLI reads accounts and sends instructions: an Arch node over RPC, or a local
+// mock that runs the program in the simulator against accounts kept in a snapshot file
+
+/// A submitted transaction
+pub struct Submission {
+    pub txid: String,
+    /// Program logs, including the `voting-event:` lines
+    pub logs: Vec<String>,
+}
+
+pub trait Backend {
+    /// Current Bitcoin block height, as the program sees it
+    fn block_height(&self) -> Result<u64, String>;
+
+    /// Data of the account at `address`, or `None` if it does not exist
+    fn account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, String>;
+
+    /// Sign `instruction` with `signers` (the first pays the fee) and submit it
+    fn submit(&mut self, instruction: Instruction, signers: &[Keypair]) -> Result<Submission, String>;
+}
+
+/// Load a program account of type `T`; accounts holding another type are reported as errors
+pub fn load<T: ProgramAccount>(backend: &dyn Backend, address: &Pubkey) -> Result<Option<T>, String> {
+    let Some(data) = backend.account_data(address)? else {
+        return Ok(None);
+    };
+
+    if data.first().is_none_or(|account_type| *account_type == 0) {
+        return Ok(None);
+    }
+
+    if data.len() < 2 || data[0] != T::ACCOUNT_TYPE as u8 || data[1] != ACCOUNT_VERSION {
+        return Err(format!("{} is not a {:?} account", hex::encode(&address.serialize()), T::ACCOUNT_TYPE));
+    }
+
+    T::deserialize(&mut &data[..])
+        .map(Some)
+        .map_err(|error| format!("{}: {}", hex::encode(&address.serialize()), error))
+}
+
+pub struct RpcBackend {
+    client: ArchRpcClient,
+    network: Network,
+}
+
+impl RpcBackend {
+    pub fn new(url: &str, network: Network) -> Self {
+        let config = Config { arch_node_url: url.to_string(), network, ..Config::localnet() };
+        RpcBackend { client: ArchRpcClient::new(&config), network }
+    }
+}
+
+impl Backend for RpcBackend {
+    fn block_height(&self) -> Result<u64, String> {
+        self.client.get_block_count().map_err(|error| format!("RPC error: {:?}", error))
+    }
+
+    fn account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, String> {
+        // The node reports missing accounts as not-found errors; anything else is a real failure
+        match self.client.read_account_info(*address) {
+            Ok(account) => Ok(Some(account.data)),
+            Err(ArchError::NotFound(_)) => Ok(None),
+            Err(error) => Err(format!("RPC error: {:?}", error)),
+        }
+    }
+
+    fn submit(&mut self, instruction: Instruction, signers: &[Keypair]) -> Result<Submission, String> {
+        let payer = keys::pubkey(signers.first().ok_or("no signer to pay the transaction fee")?);
+        let blockhash = self
+            .client
+            .get_best_block_hash()
+            .map_err(|error| format!("RPC error: {:?}", error))?;
+
+        let message = ArchMessage::new(&[instruction], Some(payer), blockhash);
+        let transaction = build_and_sign_transaction(message, signers.to_vec(), self.network)
+            .map_err(|error| format!("failed to sign transaction: {:?}", error))?;
+
+        let txid = self
+            .client
+            .send_transaction(transaction)
+            .map_err(|error| format!("RPC error: {:?}", error))?;
+        let processed = self
+            .client
+            .wait_for_processed_transaction(&txid)
+            .map_err(|error| format!("RPC error: {:?}", error))?;
+
+        Ok(Submission { txid: txid.to_string(), logs: processed.logs })
+    }
+}
+
+/// Comment line of the mock state file holding the block height
+const BLOCK_HEIGHT_COMMENT: &str = "# block-height";
+
+/// Runs instructions in the program simulator; accounts and the block height are kept in a
+/// snapshot file between invocations
+pub struct MockBackend {
+    path: PathBuf,
+    simulator: Simulator,
+    submitted: u64,
+}
+
+impl MockBackend {
+    /// Open the state at `path`; `block_height` moves the mock chain to that height
+    pub fn open(program_id: Pubkey, path: &Path, block_height: Option<u64>) -> Result<Self, String> {
+        let mut simulator = Simulator::new(program_id);
+
+        if path.exists() {
+            let text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+            let snapshot = Snapshot::parse(&text).map_err(|error| format!("{}: {:?}", path.display(), error))?;
+            for (address, data) in snapshot.accounts.accounts() {
+                simulator.set_program_account(*address, data.to_vec());
+            }
+
+            // The height is stored in a comment, which snapshot parsing skips
+            let stored_height = text
+                .lines()
+                .find_map(|line| line.strip_prefix(BLOCK_HEIGHT_COMMENT))
+                .map(|height| height.trim().parse().map_err(|_| format!("{}: invalid block height", path.display())))
+                .transpose()?;
+            simulator.set_block_height(stored_height.unwrap_or(0));
+        }
+
+        if let Some(block_height) = block_height {
+            simulator.set_block_height(block_height);
+        }
+
+        Ok(MockBackend { path: path.to_path_buf(), simulator, submitted: 0 })
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let program_id = self.simulator.program_id();
+        let mut text = format!("# voting-cli mock state\n{} {}\n", BLOCK_HEIGHT_COMMENT, self.simulator.block_height());
+
+        // Accounts that were only read stay empty and are registered again when submitting
+        let accounts = self.simulator.accounts().filter(|(_, account)| account.owner == program_id && !account.data.is_empty());
+        for (address, account) in accounts {
+            text.push_str(&format!("account {} {}\n", hex::encode(&address.serialize()), hex::encode(&account.data)));
+        }
+
+        std::fs::write(&self.path, text).map_err(|error| format!("{}: {}", self.path.display(), error))
+    }
+}
+
+impl Backend for MockBackend {
+    fn block_height(&self) -> Result<u64, String> {
+        Ok(self.simulator.block_height())
+    }
+
+    fn account_data(&self, address: &Pubkey) -> Result<Option<Vec<u8>>, String> {
+        Ok(self.simulator.account_data(address))
+    }
+
+    fn submit(&mut self, instruction: Instruction, _signers: &[Keypair]) -> Result<Submission, String> {
+        // The state file only keeps written program accounts, so the wallets and empty accounts the
+        // instruction names are registered again: wallets for keys it reads, program accounts otherwise
+        for meta in &instruction.accounts {
+            if meta.is_signer || self.simulator.account(&meta.pubkey).is_some() {
+                continue;
+            }
+            if meta.is_writable {
+                self.simulator.set_program_account(meta.pubkey, Vec::new());
+            } else {
+                self.simulator.set_wallet(meta.pubkey);
+            }
+        }
+
+        let result = self.simulator.process(&instruction);
+        let logs = self.simulator.last_processed().map(|processed| processed.logs.clone()).unwrap_or_default();
+
+        if let Err(error) = result {
+            return Err(format!("instruction failed: {:?}\n{}", error, logs.join("\n")));
+        }
+        self.save()?;
+
+        self.submitted += 1;
+        Ok(Submission { txid: format!("mock-{}", self.submitted), logs })
+    }
+}
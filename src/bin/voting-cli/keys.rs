@@ -0,0 +1,24 @@
+use std::path::Path;
+
+use arch_program::bitcoin::secp256k1::{Keypair, Secp256k1, SecretKey};
+use arch_program::pubkey::Pubkey;
+use arch_voting_contract::{hex, indexer};
+
+// Key files hold a hex-encoded 32-byte secp256k1 secret key; the account key is its x-only public key
+
+pub fn load_keypair(path: &Path) -> Result<Keypair, String> {
+    let text = std::fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let secret = hex::decode(text.trim()).ok_or_else(|| format!("{}: key is not hex", path.display()))?;
+    let secret_key = SecretKey::from_slice(&secret).map_err(|error| format!("{}: {}", path.display(), error))?;
+
+    Ok(Keypair::from_secret_key(&Secp256k1::new(), &secret_key))
+}
+
+pub fn pubkey(keypair: &Keypair) -> Pubkey {
+    let (x_only, _) = keypair.x_only_public_key();
+    indexer::parse_pubkey(&hex::encode(&x_only.serialize())).expect("x-only keys are 32 bytes")
+}
+
+pub fn parse_pubkey(value: &str) -> Result<Pubkey, String> {
+    indexer::parse_pubkey(value).ok_or_else(|| format!("invalid public key: {}", value))
+}
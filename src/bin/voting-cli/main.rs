@@ -0,0 +1,765 @@
+// Command-line client for the voting program.
+//
+//     voting-cli [--rpc <url> | --mock <state file>] --program-id <hex> [--keypair <file>] [--json] <command> ...
+//
+// Every `PollInstruction` has a subcommand (`create-poll`, `cast-vote`, ..., `recount`); `show`
+// prints a poll with its results, `verify-content` checks the poll's off-chain documents against
+// their hashes and `decode` decodes a poll, vote or tally account from hex.
+// Run without a command for the full list of options.
+
+mod args;
+mod backend;
+mod keys;
+mod output;
+
+use std::path::Path;
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use arch_program::bitcoin::{
+    self, absolute::LockTime, transaction::Version, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, Witness,
+};
+use arch_program::bitcoin::hashes::Hash;
+use arch_program::bitcoin::secp256k1::Keypair;
+use arch_program::instruction::Instruction;
+use arch_program::program_error::ProgramError;
+use arch_program::pubkey::Pubkey;
+use arch_voting_contract::assets::AssetHolding;
+use arch_voting_contract::content::{self, ContentRef, DirectoryStore};
+use arch_voting_contract::client::{
+    self, AssetProof, CastVoteBuilder, ChangeVoteBuilder, CreatePollBuilder, DelegateVoteBuilder, ProxyGroup,
+    RegisterDelegateBuilder, UpdatePollBuilder,
+};
+use arch_voting_contract::weight::{BonusSchedule, BPS_DENOMINATOR};
+use arch_voting_contract::{
+    hex, pda, results, Delegation, DelegationShare, Poll, RecountTally, UtxoOutpoint, Vote, VoteCount, VoterRecord,
+    VoterRegistry, WeightSource, VOTERS_PER_PAGE,
+};
+
+use args::Args;
+use backend::{load, Backend, MockBackend, RpcBackend};
+use output::{ContentReport, DecodedAccount, LabeledResults};
+
+const DEFAULT_RPC_URL: &str = "http://localhost:9002";
+
+const USAGE: &str = "usage: voting-cli [--rpc <url> | --mock <state file>] --program-id <hex> [--keypair <file>] [--json]
+                  [--network regtest|testnet|mainnet] [--fee-utxo <txid:vout>] [--block-height <height>] <command>
+
+commands:
+  create-poll --nonce <n> --title <text> --option <text>... --start <height> --end <height>
+              [--description <text>] [--private] [--allow-revote] [--encrypted]
+              [--weighted token|utxo|escrow] [--weight-token <key>] [--oracle <key> --max-balance-age <blocks>]
+              [--early-bonus <percent>] [--delegation-depth <hops> [--require-registered]] [--topic <text>]
+              [--max-extensions <n> [--quorum <weight>]] [--early-close]
+              [--metadata <uri>#<sha256>] [--option-metadata <uri>#<sha256>|-...]
+  cancel-poll <poll id>
+  extend-poll <poll id> --end <height>
+  update-poll <poll id> [--title <text>] [--description <text>] [--option <text>...] [--start <height>]
+              [--end <height>] [--metadata <uri>#<sha256>|-] [--option-metadata <uri>#<sha256>|-...]
+  cast-vote <poll id> <option> [--weight <n>]
+            [--encrypted-data <hex> --nonce <hex>] [--zk-proof <hex>] [--proxy <principal>:<delegation id>,...]...
+  change-vote <poll id> <option> [--encrypted-data <hex> --nonce <hex>] [--zk-proof <hex>]
+  close-poll <poll id>
+  get-results <poll id>
+  decrypt-results <poll id> --key <hex>
+  delegate-vote --nonce <n> --to <key>[:<bps>]... [--poll <poll id> | --topic <text>] [--expiration <height>]
+  revoke-delegation <delegation id> [--delegate <key>]
+  update-token-balance --token <key> --amount <n> --oracle-keypair <file>
+  prove-utxo-weight <poll id> --utxo <txid:vout>... --prev-tx <hex>...
+  prove-asset-holdings <poll id> --holding <txid:vout:amount>... --prev-tx <hex>... --oracle-keypair <file>
+  lock-escrow --token <key> --amount <n> --unlock-height <height>
+  extend-escrow --token <key> --unlock-height <height>
+  withdraw-escrow --token <key>
+  register-delegate --statement-uri <uri> --statement-hash <hex>
+                    [--policy <text>] [--max-delegators <n>] [--not-accepting]
+  recount <poll id> [--restart] [--apply]
+  show <poll id>
+  verify-content <poll id> --content-dir <dir>
+  decode <account data hex>";
+
+fn main() -> ExitCode {
+    let result = Args::parse(std::env::args().skip(1)).and_then(|args| run(&args));
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Settings shared by every command
+struct Context {
+    program_id: Pubkey,
+    backend: Box<dyn Backend>,
+    is_mock: bool,
+    as_json: bool,
+}
+
+impl Context {
+    fn block_height(&self, args: &Args) -> Result<u64, String> {
+        match args.parse_optional("block-height")? {
+            Some(height) => Ok(height),
+            None => self.backend.block_height(),
+        }
+    }
+
+    fn load<T: arch_voting_contract::ProgramAccount>(&self, address: &Pubkey) -> Result<Option<T>, String> {
+        load(self.backend.as_ref(), address)
+    }
+
+    fn poll(&self, poll_id: u64) -> Result<Poll, String> {
+        self.load(&pda::find_poll_address(&self.program_id, poll_id).0)?
+            .ok_or_else(|| format!("poll {} not found", poll_id))
+    }
+
+    fn vote_count(&self, poll_id: u64) -> Result<VoteCount, String> {
+        self.load(&pda::find_vote_count_address(&self.program_id, poll_id).0)?
+            .ok_or_else(|| format!("vote count of poll {} not found", poll_id))
+    }
+
+    /// Every voter of `poll_id`, read from the registry pages in order
+    fn voters(&self, poll_id: u64) -> Result<Vec<Pubkey>, String> {
+        let mut voters = Vec::new();
+        for page in 0.. {
+            let address = pda::find_voter_registry_address(&self.program_id, poll_id, page).0;
+            match self.load::<VoterRegistry>(&address)? {
+                Some(registry) => voters.extend(registry.voters),
+                None => break,
+            }
+        }
+        Ok(voters)
+    }
+
+    /// Submit `instruction` signed by `signers` and print the result
+    fn submit(&mut self, instruction: Result<Instruction, ProgramError>, signers: &[Keypair]) -> Result<(), String> {
+        let instruction = instruction.map_err(describe_error)?;
+        let submission = self.backend.submit(instruction, signers)?;
+        output::print(&submission, self.as_json);
+        Ok(())
+    }
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    if args.command.is_empty() {
+        return Err(USAGE.to_string());
+    }
+
+    // Decoding needs neither a program nor a backend
+    if args.command == "decode" {
+        let data = hex::decode(args.positional.first().ok_or("missing <account data hex>")?)
+            .ok_or("account data is not hex")?;
+        output::print(&DecodedAccount::decode(&data)?, args.switch("json"));
+        return Ok(());
+    }
+
+    let program_id = keys::parse_pubkey(args.required("program-id")?)?;
+    let (backend, is_mock): (Box<dyn Backend>, bool) = match args.optional("mock") {
+        Some(path) => {
+            let block_height = args.parse_optional("block-height")?;
+            (Box::new(MockBackend::open(program_id, Path::new(path), block_height)?), true)
+        }
+        None => {
+            let url = args.optional("rpc").unwrap_or(DEFAULT_RPC_URL);
+            let network = match args.optional("network").unwrap_or("regtest") {
+                "mainnet" | "bitcoin" => bitcoin::Network::Bitcoin,
+                "testnet" => bitcoin::Network::Testnet,
+                "regtest" => bitcoin::Network::Regtest,
+                other => return Err(format!("unknown network: {}", other)),
+            };
+            (Box::new(RpcBackend::new(url, network)), false)
+        }
+    };
+
+    let mut context = Context { program_id, backend, is_mock, as_json: args.switch("json") };
+
+    match args.command.as_str() {
+        "create-poll" => create_poll(&mut context, args),
+        "cancel-poll" => {
+            let signer = signer(args)?;
+            let poll_id = args.positional(0, "poll id")?;
+            let instruction = client::cancel_poll(&program_id, &keys::pubkey(&signer), poll_id, fee_transaction(&context, args, &[])?);
+            context.submit(instruction, &[signer])
+        }
+        "extend-poll" => {
+            let signer = signer(args)?;
+            let poll = context.poll(args.positional(0, "poll id")?)?;
+            let instruction = client::extend_poll(
+                &program_id,
+                &poll,
+                args.parse_required("end")?,
+                context.block_height(args)?,
+                fee_transaction(&context, args, &[])?,
+            );
+            context.submit(instruction, &[signer])
+        }
+        "update-poll" => update_poll(&mut context, args),
+        "cast-vote" => cast_vote(&mut context, args),
+        "change-vote" => {
+            let signer = signer(args)?;
+            let poll = context.poll(args.positional(0, "poll id")?)?;
+            let mut builder = ChangeVoteBuilder::new(program_id, keys::pubkey(&signer), &poll, args.positional(1, "option")?);
+            if let Some(data) = args.optional("encrypted-data") {
+                builder = builder.encrypted(parse_hex(data)?, parse_hex(args.required("nonce")?)?);
+            }
+            if let Some(proof) = args.optional("zk-proof") {
+                builder = builder.zk_proof(parse_hex(proof)?);
+            }
+            let instruction = builder.build(context.block_height(args)?, fee_transaction(&context, args, &[])?);
+            context.submit(instruction, &[signer])
+        }
+        "close-poll" => {
+            let signer = signer(args)?;
+            let poll_id = args.positional(0, "poll id")?;
+            let instruction = client::close_poll(&program_id, &keys::pubkey(&signer), poll_id, fee_transaction(&context, args, &[])?);
+            context.submit(instruction, &[signer])
+        }
+        "get-results" | "show" => show_results(&context, args),
+        "verify-content" => verify_content(&context, args),
+        "decrypt-results" => {
+            let signer = signer(args)?;
+            let poll_id = args.positional(0, "poll id")?;
+            let voters = context.voters(poll_id)?;
+            let instruction = client::decrypt_results(
+                &program_id,
+                &keys::pubkey(&signer),
+                poll_id,
+                parse_hex(args.required("key")?)?,
+                &voters,
+                fee_transaction(&context, args, &[])?,
+            );
+            context.submit(instruction, &[signer])
+        }
+        "delegate-vote" => delegate_vote(&mut context, args),
+        "revoke-delegation" => {
+            let signer = signer(args)?;
+            let delegation_id = args.positional(0, "delegation id")?;
+            let delegation: Delegation = context
+                .load(&pda::find_delegation_address(&program_id, delegation_id).0)?
+                .ok_or_else(|| format!("delegation {} not found", delegation_id))?;
+            if delegation.delegator != keys::pubkey(&signer) {
+                return Err(format!("delegation {} belongs to another delegator", delegation_id));
+            }
+            let delegate = args.optional("delegate").map(keys::parse_pubkey).transpose()?;
+            let instruction = client::revoke_delegation(
+                &program_id,
+                &delegation,
+                delegate,
+                fee_transaction(&context, args, &[])?,
+            );
+            context.submit(instruction, &[signer])
+        }
+        "update-token-balance" => {
+            let signer = signer(args)?;
+            let oracle = keys::load_keypair(Path::new(args.required("oracle-keypair")?))?;
+            let instruction = client::update_token_balance(
+                &program_id,
+                &keys::pubkey(&signer),
+                &keys::parse_pubkey(args.required("token")?)?,
+                args.parse_required("amount")?,
+                &keys::pubkey(&oracle),
+                fee_transaction(&context, args, &[])?,
+            );
+            context.submit(instruction, &[signer, oracle])
+        }
+        "prove-utxo-weight" => {
+            let signer = signer(args)?;
+            let utxos = args.all("utxo").iter().map(|utxo| parse_outpoint(utxo)).collect::<Result<Vec<_>, _>>()?;
+            let prev_txs = args.all("prev-tx").iter().map(|tx| parse_hex(tx)).collect::<Result<Vec<_>, _>>()?;
+            let instruction = client::prove_utxo_weight(
+                &program_id,
+                &keys::pubkey(&signer),
+                args.positional(0, "poll id")?,
+                prev_txs,
+                // The proven UTXOs are spent alongside the fee input
+                fee_transaction(&context, args, &utxos)?,
+            );
+            context.submit(instruction, &[signer])
+        }
+        "prove-asset-holdings" => prove_asset_holdings(&mut context, args),
+        "lock-escrow" => {
+            let signer = signer(args)?;
+            let instruction = client::lock_escrow(
+                &program_id,
+                &keys::pubkey(&signer),
+                &keys::parse_pubkey(args.required("token")?)?,
+                args.parse_required("amount")?,
+                args.parse_required("unlock-height")?,
+                fee_transaction(&context, args, &[])?,
+            );
+            context.submit(instruction, &[signer])
+        }
+        "extend-escrow" => {
+            let signer = signer(args)?;
+            let instruction = client::extend_escrow(
+                &program_id,
+                &keys::pubkey(&signer),
+                &keys::parse_pubkey(args.required("token")?)?,
+                args.parse_required("unlock-height")?,
+                fee_transaction(&context, args, &[])?,
+            );
+            context.submit(instruction, &[signer])
+        }
+        "withdraw-escrow" => {
+            let signer = signer(args)?;
+            let instruction = client::withdraw_escrow(
+                &program_id,
+                &keys::pubkey(&signer),
+                &keys::parse_pubkey(args.required("token")?)?,
+                fee_transaction(&context, args, &[])?,
+            );
+            context.submit(instruction, &[signer])
+        }
+        "register-delegate" => {
+            let signer = signer(args)?;
+            let statement_hash: [u8; 32] = parse_hex(args.required("statement-hash")?)?
+                .try_into()
+                .map_err(|_| "--statement-hash must be 32 bytes")?;
+            let mut builder = RegisterDelegateBuilder::new(
+                program_id,
+                keys::pubkey(&signer),
+                args.required("statement-uri")?.to_string(),
+                statement_hash,
+            )
+            .accepting_delegations(!args.switch("not-accepting"));
+            if let Some(policy) = args.optional("policy") {
+                builder = builder.voting_policy(policy.to_string());
+            }
+            if let Some(max_delegators) = args.parse_optional("max-delegators")? {
+                builder = builder.max_delegators(max_delegators);
+            }
+            let instruction = builder.build(fee_transaction(&context, args, &[])?);
+            context.submit(instruction, &[signer])
+        }
+        "recount" => recount(&mut context, args),
+        other => Err(format!("unknown command: {}\n{}", other, USAGE)),
+    }
+}
+
+fn create_poll(context: &mut Context, args: &Args) -> Result<(), String> {
+    let signer = signer(args)?;
+    let mut builder = CreatePollBuilder::new(
+        context.program_id,
+        keys::pubkey(&signer),
+        args.parse_required("nonce")?,
+        args.required("title")?.to_string(),
+        args.all("option").to_vec(),
+        args.parse_required("start")?,
+        args.parse_required("end")?,
+    );
+
+    if let Some(description) = args.optional("description") {
+        builder = builder.description(description.to_string());
+    }
+    if args.switch("private") {
+        builder = builder.private();
+    }
+    if args.switch("allow-revote") {
+        builder = builder.allow_revote();
+    }
+    if args.switch("encrypted") {
+        builder = builder.encrypted();
+    }
+    if let Some(source) = args.optional("weighted") {
+        let weight_source = match source {
+            "token" => WeightSource::TokenBalance,
+            "utxo" => WeightSource::BitcoinUtxo,
+            "escrow" => WeightSource::VoteEscrow,
+            other => return Err(format!("unknown weight source: {}", other)),
+        };
+        let weight_token = args.optional("weight-token").map(keys::parse_pubkey).transpose()?;
+        builder = builder.weighted(weight_source, weight_token);
+    }
+    if let Some(oracle) = args.optional("oracle") {
+        builder = builder.balance_oracle(keys::parse_pubkey(oracle)?, args.parse_optional("max-balance-age")?.unwrap_or(0));
+    }
+    if let Some(bonus) = args.parse_optional("early-bonus")? {
+        builder = builder.early_voter_bonus(bonus, BonusSchedule::Linear);
+    }
+    if let Some(depth) = args.parse_optional("delegation-depth")? {
+        builder = builder.allow_delegation(depth, args.switch("require-registered"));
+    }
+    if let Some(topic) = args.optional("topic") {
+        builder = builder.topic(topic.to_string());
+    }
+    if let Some(max_extensions) = args.parse_optional("max-extensions")? {
+        builder = builder.extensions(max_extensions, args.parse_optional("quorum")?);
+    }
+    if args.switch("early-close") {
+        builder = builder.allow_early_close();
+    }
+    if let Some(metadata) = args.optional("metadata") {
+        builder = builder.metadata(parse_content_ref(metadata)?);
+    }
+    if !args.all("option-metadata").is_empty() {
+        builder = builder.option_metadata(parse_option_metadata(args)?);
+    }
+
+    if !context.as_json {
+        println!("Creating poll {}", builder.poll_id());
+    }
+    let instruction = builder.build(context.block_height(args)?, fee_transaction(context, args, &[])?);
+    context.submit(instruction, &[signer])
+}
+
+fn update_poll(context: &mut Context, args: &Args) -> Result<(), String> {
+    let signer = signer(args)?;
+    let poll = context.poll(args.positional(0, "poll id")?)?;
+    let mut builder = UpdatePollBuilder::new(context.program_id, &poll);
+
+    if let Some(title) = args.optional("title") {
+        builder = builder.title(title.to_string());
+    }
+    if let Some(description) = args.optional("description") {
+        builder = builder.description(description.to_string());
+    }
+    if !args.all("option").is_empty() {
+        builder = builder.options(args.all("option").to_vec());
+    }
+    match args.optional("metadata") {
+        Some("-") => builder = builder.metadata(None),
+        Some(metadata) => builder = builder.metadata(Some(parse_content_ref(metadata)?)),
+        None => {}
+    }
+    if !args.all("option-metadata").is_empty() {
+        builder = builder.option_metadata(parse_option_metadata(args)?);
+    }
+    // Times not given keep their current values
+    let start_time = args.parse_optional("start")?.unwrap_or(poll.start_time);
+    let end_time = args.parse_optional("end")?.unwrap_or(poll.end_time);
+    builder = builder.times(start_time, end_time);
+
+    let instruction = builder.build(context.block_height(args)?, fee_transaction(context, args, &[])?);
+    context.submit(instruction, &[signer])
+}
+
+fn cast_vote(context: &mut Context, args: &Args) -> Result<(), String> {
+    let signer = signer(args)?;
+    let voter = keys::pubkey(&signer);
+    let program_id = context.program_id;
+    let poll_id: u64 = args.positional(0, "poll id")?;
+
+    // The builder derives the optional accounts from the current state of the poll and the voter
+    let poll = context.poll(poll_id)?;
+    let vote_count = context.vote_count(poll_id)?;
+    let voter_record: Option<VoterRecord> = context.load(&pda::find_voter_record_address(&program_id, poll_id, &voter).0)?;
+    let previous_vote: Option<Vote> = context.load(&pda::find_vote_address(&program_id, poll_id, &voter).0)?;
+
+    let mut builder = CastVoteBuilder::new(program_id, voter, &poll, &vote_count, args.positional(1, "option")?);
+    if let Some(weight) = args.parse_optional("weight")? {
+        builder = builder.weight(weight);
+    }
+    if let Some(data) = args.optional("encrypted-data") {
+        builder = builder.encrypted(parse_hex(data)?, parse_hex(args.required("nonce")?)?);
+    }
+    if let Some(proof) = args.optional("zk-proof") {
+        builder = builder.zk_proof(parse_hex(proof)?);
+    }
+    if let Some(voter_record) = &voter_record {
+        builder = builder.voter_record(voter_record);
+    }
+    if let Some(previous_vote) = &previous_vote {
+        builder = builder.previous_vote(previous_vote);
+    }
+    for proxy in args.all("proxy") {
+        builder = builder.proxy_group(parse_proxy_group(proxy)?);
+    }
+
+    let instruction = builder.build(context.block_height(args)?, fee_transaction(context, args, &[])?);
+    context.submit(instruction, &[signer])
+}
+
+fn show_results(context: &Context, args: &Args) -> Result<(), String> {
+    // Results are computed the way `GetResults` computes them, from the poll and its vote count
+    let poll_id = args.positional(0, "poll id")?;
+    let poll = context.poll(poll_id)?;
+    let vote_count = context.vote_count(poll_id)?;
+    let poll_results = results::poll_results(&poll, &vote_count, context.block_height(args)?);
+
+    if args.command == "show" && !context.as_json {
+        output::print(&poll, false);
+    }
+    output::print(&LabeledResults { poll: &poll, results: &poll_results }, context.as_json);
+    Ok(())
+}
+
+/// Check every document the poll references against its hash, reading them from `--content-dir`
+fn verify_content(context: &Context, args: &Args) -> Result<(), String> {
+    let poll = context.poll(args.positional(0, "poll id")?)?;
+    let store = DirectoryStore::new(args.required("content-dir")?);
+
+    let options = poll
+        .option_metadata
+        .iter()
+        .enumerate()
+        .filter_map(|(index, document)| Some((format!("option {}", index), document.as_ref()?)));
+    let documents = poll
+        .metadata
+        .as_ref()
+        .map(|document| ("poll".to_string(), document))
+        .into_iter()
+        .chain(options)
+        .map(|(label, document)| {
+            let result = content::fetch_verified(&store, document).map(|data| data.len());
+            (label, document.clone(), result)
+        })
+        .collect();
+
+    let report = ContentReport { documents };
+    output::print(&report, context.as_json);
+    if report.documents.iter().any(|(_, _, result)| result.is_err()) {
+        return Err("some documents could not be verified".to_string());
+    }
+    Ok(())
+}
+
+fn delegate_vote(context: &mut Context, args: &Args) -> Result<(), String> {
+    let signer = signer(args)?;
+
+    // Delegates without an explicit share split the remainder evenly
+    let mut shares = Vec::new();
+    let mut unassigned = Vec::new();
+    for to in args.all("to") {
+        match to.split_once(':') {
+            Some((delegate, bps)) => shares.push(DelegationShare {
+                delegate: keys::parse_pubkey(delegate)?,
+                bps: bps.parse().map_err(|_| format!("invalid share: {}", to))?,
+            }),
+            None => unassigned.push(keys::parse_pubkey(to)?),
+        }
+    }
+    if !unassigned.is_empty() {
+        let assigned: u64 = shares.iter().map(|share| share.bps as u64).sum();
+        let remainder = BPS_DENOMINATOR.saturating_sub(assigned);
+        let count = unassigned.len() as u64;
+        for (index, delegate) in unassigned.into_iter().enumerate() {
+            // The first delegate takes any rounding remainder
+            let bps = remainder / count + if index == 0 { remainder % count } else { 0 };
+            shares.push(DelegationShare { delegate, bps: bps as u16 });
+        }
+    }
+
+    let mut builder = DelegateVoteBuilder::new(context.program_id, keys::pubkey(&signer), args.parse_required("nonce")?, shares);
+    if let Some(poll_id) = args.parse_optional("poll")? {
+        builder = builder.poll(poll_id);
+    }
+    if let Some(topic) = args.optional("topic") {
+        builder = builder.topic(topic.to_string());
+    }
+    if let Some(expiration) = args.parse_optional("expiration")? {
+        builder = builder.expiration(expiration);
+    }
+
+    if !context.as_json {
+        println!("Creating delegation {}", builder.delegation_id());
+    }
+    let instruction = builder.build(context.block_height(args)?, fee_transaction(context, args, &[])?);
+    context.submit(instruction, &[signer])
+}
+
+fn prove_asset_holdings(context: &mut Context, args: &Args) -> Result<(), String> {
+    let signer = signer(args)?;
+    let oracle = keys::load_keypair(Path::new(args.required("oracle-keypair")?))?;
+    let poll = context.poll(args.positional(0, "poll id")?)?;
+
+    // The proof is for the poll's weight asset, or its eligibility asset
+    let asset = match (&poll.weight_source, poll.is_weighted) {
+        (WeightSource::BitcoinAsset(asset), true) => *asset,
+        _ => poll.eligibility_asset.ok_or("poll has no weight or eligibility asset")?,
+    };
+
+    let mut holdings = Vec::new();
+    for holding in args.all("holding") {
+        let (outpoint, amount) = holding.rsplit_once(':').ok_or_else(|| format!("invalid holding: {}", holding))?;
+        let outpoint = parse_outpoint(outpoint)?;
+        holdings.push(AssetHolding {
+            outpoint: UtxoOutpoint { txid: outpoint.txid.to_byte_array(), vout: outpoint.vout },
+            amount: amount.parse().map_err(|_| format!("invalid holding amount: {}", holding))?,
+        });
+    }
+    let prev_txs = args.all("prev-tx").iter().map(|tx| parse_hex(tx)).collect::<Result<Vec<_>, _>>()?;
+
+    let instruction = client::prove_asset_holdings(
+        &context.program_id,
+        &keys::pubkey(&signer),
+        &poll,
+        AssetProof { asset, holdings, prev_txs },
+        fee_transaction(context, args, &[])?,
+    );
+    context.submit(instruction, &[signer, oracle])
+}
+
+fn recount(context: &mut Context, args: &Args) -> Result<(), String> {
+    let signer = signer(args)?;
+    let poll_id = args.positional(0, "poll id")?;
+    let restart = args.switch("restart");
+
+    // Continue the recount in progress, if any; otherwise the program starts a fresh one
+    let tally: Option<RecountTally> = context.load(&pda::find_recount_address(&context.program_id, poll_id).0)?;
+    let next_voter = match tally {
+        Some(tally) if !restart && !tally.is_complete => tally.next_voter,
+        _ => 0,
+    };
+
+    let page = u32::try_from(next_voter / VOTERS_PER_PAGE as u64).map_err(|_| "voter out of range")?;
+    let registry: VoterRegistry = context
+        .load(&pda::find_voter_registry_address(&context.program_id, poll_id, page).0)?
+        .ok_or_else(|| format!("voter registry page {} not found", page))?;
+
+    let instruction = client::recount(
+        &context.program_id,
+        &keys::pubkey(&signer),
+        &registry,
+        next_voter,
+        restart,
+        args.switch("apply"),
+        fee_transaction(context, args, &[])?,
+    );
+    context.submit(instruction, &[signer])
+}
+
+fn signer(args: &Args) -> Result<Keypair, String> {
+    let path = match args.optional("keypair") {
+        Some(path) => path.to_string(),
+        None => std::env::var("VOTING_CLI_KEYPAIR").map_err(|_| "missing --keypair (or VOTING_CLI_KEYPAIR)")?,
+    };
+    keys::load_keypair(Path::new(&path))
+}
+
+/// Fee transaction spending `--fee-utxo`, followed by `extra_inputs`.
+/// The mock backend does not check fees, so it falls back to a null outpoint.
+fn fee_transaction(context: &Context, args: &Args, extra_inputs: &[OutPoint]) -> Result<Vec<u8>, String> {
+    let fee_utxo = match args.optional("fee-utxo") {
+        Some(fee_utxo) => parse_outpoint(fee_utxo)?,
+        None if context.is_mock => OutPoint::null(),
+        None => return Err("missing --fee-utxo".to_string()),
+    };
+
+    let input = std::iter::once(fee_utxo)
+        .chain(extra_inputs.iter().copied())
+        .map(|previous_output| TxIn {
+            previous_output,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::default(),
+        })
+        .collect();
+
+    let transaction = Transaction { version: Version::TWO, lock_time: LockTime::ZERO, input, output: vec![] };
+    Ok(bitcoin::consensus::serialize(&transaction))
+}
+
+/// `<principal>:<delegation id>,<delegation id>...`, delegation IDs in chain order
+fn parse_proxy_group(value: &str) -> Result<ProxyGroup, String> {
+    let (principal, delegation_ids) = value.split_once(':').ok_or_else(|| format!("invalid proxy: {}", value))?;
+    let delegation_ids = delegation_ids
+        .split(',')
+        .map(|id| id.parse().map_err(|_| format!("invalid delegation id: {}", id)))
+        .collect::<Result<Vec<u64>, String>>()?;
+
+    Ok(ProxyGroup {
+        principal: keys::parse_pubkey(principal)?,
+        delegation_ids,
+        displaced_delegates: Vec::new(),
+    })
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>, String> {
+    hex::decode(value).ok_or_else(|| format!("invalid hex: {}", value))
+}
+
+/// Document reference written as `<uri>#<sha256 hex>`
+fn parse_content_ref(value: &str) -> Result<ContentRef, String> {
+    let (uri, hash) = value
+        .rsplit_once('#')
+        .ok_or_else(|| format!("invalid document (expected uri#sha256): {}", value))?;
+    let hash = parse_hex(hash)?.try_into().map_err(|_| format!("document hash must be 32 bytes: {}", value))?;
+    Ok(ContentRef { uri: uri.to_string(), hash })
+}
+
+/// `--option-metadata` values, one per option in order; `-` marks an option without a document
+fn parse_option_metadata(args: &Args) -> Result<Vec<Option<ContentRef>>, String> {
+    args.all("option-metadata")
+        .iter()
+        .map(|value| match value.as_str() {
+            "-" => Ok(None),
+            value => parse_content_ref(value).map(Some),
+        })
+        .collect()
+}
+
+fn parse_outpoint(value: &str) -> Result<OutPoint, String> {
+    OutPoint::from_str(value).map_err(|_| format!("invalid outpoint (expected txid:vout): {}", value))
+}
+
+/// Program errors, with the program's custom codes shown as `VotingError` names where known
+fn describe_error(error: ProgramError) -> String {
+    match error {
+        ProgramError::Custom(code) => match voting_error_name(code) {
+            Some(name) => format!("{} ({})", name, code),
+            None => format!("custom program error {}", code),
+        },
+        other => format!("{:?}", other),
+    }
+}
+
+fn voting_error_name(code: u32) -> Option<String> {
+    use arch_voting_contract::VotingError::*;
+
+    // Every variant maps to a distinct code, so comparing the conversions finds the name
+    let errors = [
+        InvalidPollParameters,
+        PollAlreadyExists,
+        PollDoesNotExist,
+        PollNotActive,
+        PollNotStarted,
+        PollEnded,
+        NotPollCreator,
+        AlreadyVoted,
+        RevotingNotAllowed,
+        InvalidOptionIndex,
+        InvalidVoteWeight,
+        InvalidDelegation,
+        DelegationExpired,
+        InvalidZkProof,
+        InvalidEncryption,
+        InsufficientFees,
+        InvalidFeeTransaction,
+        PollAlreadyStarted,
+        PollNotEncrypted,
+        ResultsAlreadyFinalized,
+        InvalidDecryptionKey,
+        PollStillActive,
+        DelegationNotFound,
+        NotDelegator,
+        TokenBalanceNotFound,
+        InvalidToken,
+        MissingNonce,
+        UnverifiedTokenBalance,
+        StaleTokenBalance,
+        UtxoAlreadyCounted,
+        InvalidUtxoProof,
+        NotEligible,
+        InvalidAssetProof,
+        EscrowLocked,
+        InvalidEscrowLock,
+        DelegationCycle,
+        DelegationTooDeep,
+        DelegateNotRegistered,
+        DelegateNotAccepting,
+        InvalidAccountAddress,
+        InvalidAccountOwner,
+        InvalidAccountType,
+        RecountOutdated,
+        ExtensionLimitReached,
+        QuorumReached,
+        PollHasVotes,
+        EscrowNotBacked,
+    ];
+
+    errors
+        .into_iter()
+        .find(|error| ProgramError::from(error.clone()) == ProgramError::Custom(code))
+        .map(|error| format!("{:?}", error))
+}
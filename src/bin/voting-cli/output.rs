@@ -0,0 +1,404 @@
+use arch_program::pubkey::Pubkey;
+use arch_voting_contract::content::{ContentError, ContentRef};
+use arch_voting_contract::events::{self, VotingEvent};
+use arch_voting_contract::results::{PollOutcome, PollResults};
+use arch_voting_contract::{hex, AccountType, Poll, RecountTally, Vote, VoteCount, ACCOUNT_VERSION};
+use borsh::BorshDeserialize;
+use serde_json::{json, Value};
+
+use crate::backend::Submission;
+
+// Pretty and JSON rendering of accounts, results and submissions
+
+pub trait Render {
+    fn json(&self) -> Value;
+    fn pretty(&self) -> String;
+}
+
+pub fn print(item: &dyn Render, as_json: bool) {
+    if as_json {
+        println!("{}", serde_json::to_string_pretty(&item.json()).expect("values always serialize"));
+    } else {
+        println!("{}", item.pretty());
+    }
+}
+
+fn key(pubkey: &Pubkey) -> String {
+    hex::encode(&pubkey.serialize())
+}
+
+fn content(content: &ContentRef) -> Value {
+    json!({ "uri": content.uri, "sha256": hex::encode(&content.hash) })
+}
+
+fn percent(bps: u16) -> String {
+    format!("{}.{:02}%", bps / 100, bps % 100)
+}
+
+/// Poll, vote, vote count or recount tally decoded from raw account data
+pub enum DecodedAccount {
+    Poll(Poll),
+    Vote(Vote),
+    VoteCount(VoteCount),
+    RecountTally(RecountTally),
+}
+
+impl DecodedAccount {
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        if data.len() < 2 || data[1] != ACCOUNT_VERSION {
+            return Err("not a program account of this layout version".to_string());
+        }
+
+        // Accounts may be larger than the struct they hold
+        let reader = &mut &data[..];
+        let decoded = match data[0] {
+            tag if tag == AccountType::Poll as u8 => Poll::deserialize(reader).map(DecodedAccount::Poll),
+            tag if tag == AccountType::Vote as u8 => Vote::deserialize(reader).map(DecodedAccount::Vote),
+            tag if tag == AccountType::VoteCount as u8 => VoteCount::deserialize(reader).map(DecodedAccount::VoteCount),
+            tag if tag == AccountType::RecountTally as u8 => {
+                RecountTally::deserialize(reader).map(DecodedAccount::RecountTally)
+            }
+            tag => return Err(format!("account type {} is not a poll, vote or tally", tag)),
+        };
+
+        decoded.map_err(|error| format!("invalid account data: {}", error))
+    }
+
+    fn inner(&self) -> &dyn Render {
+        match self {
+            DecodedAccount::Poll(poll) => poll,
+            DecodedAccount::Vote(vote) => vote,
+            DecodedAccount::VoteCount(vote_count) => vote_count,
+            DecodedAccount::RecountTally(tally) => tally,
+        }
+    }
+}
+
+impl Render for DecodedAccount {
+    fn json(&self) -> Value {
+        self.inner().json()
+    }
+
+    fn pretty(&self) -> String {
+        self.inner().pretty()
+    }
+}
+
+impl Render for Poll {
+    fn json(&self) -> Value {
+        json!({
+            "type": "poll",
+            "id": self.id,
+            "creator": key(&self.creator),
+            "title": self.title,
+            "description": self.description,
+            "options": self.options,
+            "start_time": self.start_time,
+            "end_time": self.end_time,
+            "is_private": self.is_private,
+            "allow_revote": self.allow_revote,
+            "is_active": self.is_active,
+            "is_weighted": self.is_weighted,
+            "weight_source": format!("{:?}", self.weight_source),
+            "weight_token": self.weight_token.as_ref().map(key),
+            "balance_oracle": self.balance_oracle.as_ref().map(key),
+            "allow_delegation": self.allow_delegation,
+            "max_delegation_depth": self.max_delegation_depth,
+            "is_encrypted": self.is_encrypted,
+            "topic": self.topic,
+            "max_extensions": self.max_extensions,
+            "extension_count": self.extension_count,
+            "quorum": self.quorum,
+            "allow_early_close": self.allow_early_close,
+            "metadata": self.metadata.as_ref().map(content),
+            "option_metadata": self.option_metadata.iter().map(|option| option.as_ref().map(content)).collect::<Vec<_>>(),
+        })
+    }
+
+    fn pretty(&self) -> String {
+        let mut text = format!(
+            "Poll {}: {}\n  Creator: {}\n  Blocks {} - {}{}\n",
+            self.id,
+            self.title,
+            key(&self.creator),
+            self.start_time,
+            self.end_time,
+            if self.is_active { "" } else { " (closed)" }
+        );
+        if !self.description.is_empty() {
+            text.push_str(&format!("  {}\n", self.description));
+        }
+        if let Some(metadata) = &self.metadata {
+            text.push_str(&format!("  Document: {}\n", metadata.uri));
+        }
+        if self.max_extensions > 0 {
+            text.push_str(&format!("  Extended {} of {} times\n", self.extension_count, self.max_extensions));
+        }
+        for (index, option) in self.options.iter().enumerate() {
+            text.push_str(&format!("  [{}] {}\n", index, option));
+            if let Some(Some(document)) = self.option_metadata.get(index) {
+                text.push_str(&format!("      Document: {}\n", document.uri));
+            }
+        }
+        text.trim_end().to_string()
+    }
+}
+
+impl Render for Vote {
+    fn json(&self) -> Value {
+        json!({
+            "type": "vote",
+            "poll_id": self.poll_id,
+            "voter": key(&self.voter),
+            "option_index": self.option_index,
+            "timestamp": self.timestamp,
+            "weight": self.weight,
+            "raw_weight": self.raw_weight,
+            "proxies": self.proxies.iter().map(|proxy| json!({
+                "principal": key(&proxy.principal),
+                "depth": proxy.depth,
+                "share_bps": proxy.share_bps,
+                "weight": proxy.weight,
+                "raw_weight": proxy.raw_weight,
+            })).collect::<Vec<_>>(),
+            "is_encrypted": self.encrypted_data.is_some(),
+        })
+    }
+
+    fn pretty(&self) -> String {
+        let mut text = format!(
+            "Vote by {} in poll {}\n  Option: {}\n  Weight: {} (raw {}) at block {}\n",
+            key(&self.voter),
+            self.poll_id,
+            if self.encrypted_data.is_some() { "encrypted".to_string() } else { self.option_index.to_string() },
+            self.weight,
+            self.raw_weight,
+            self.timestamp
+        );
+        for proxy in &self.proxies {
+            text.push_str(&format!(
+                "  Carries {} for {} ({} hops, {})\n",
+                proxy.weight,
+                key(&proxy.principal),
+                proxy.depth,
+                percent(proxy.share_bps)
+            ));
+        }
+        text.trim_end().to_string()
+    }
+}
+
+impl Render for VoteCount {
+    fn json(&self) -> Value {
+        json!({
+            "type": "vote_count",
+            "poll_id": self.poll_id,
+            "counts": self.counts,
+            "raw_counts": self.raw_counts,
+            "proxy_counts": self.proxy_counts,
+            "total_voters": self.total_voters,
+            "last_updated": self.last_updated,
+            "revision": self.revision,
+            "is_finalized": self.is_finalized,
+        })
+    }
+
+    fn pretty(&self) -> String {
+        let mut text = format!(
+            "Vote count of poll {}{}\n  Voters: {}\n",
+            self.poll_id,
+            if self.is_finalized { " (final)" } else { "" },
+            self.total_voters
+        );
+        for (index, count) in self.counts.iter().enumerate() {
+            text.push_str(&format!(
+                "  [{}] {} (raw {}, by proxy {})\n",
+                index,
+                count,
+                self.raw_counts.get(index).copied().unwrap_or(0),
+                self.proxy_counts.get(index).copied().unwrap_or(0)
+            ));
+        }
+        text.trim_end().to_string()
+    }
+}
+
+impl Render for RecountTally {
+    fn json(&self) -> Value {
+        json!({
+            "type": "recount_tally",
+            "poll_id": self.poll_id,
+            "counts": self.counts,
+            "raw_counts": self.raw_counts,
+            "proxy_counts": self.proxy_counts,
+            "next_voter": self.next_voter,
+            "is_complete": self.is_complete,
+            "discrepancies": self.discrepancies,
+            "applied": self.applied,
+        })
+    }
+
+    fn pretty(&self) -> String {
+        let status = match (self.is_complete, self.applied) {
+            (false, _) => format!("in progress, next voter {}", self.next_voter),
+            (true, false) => "complete".to_string(),
+            (true, true) => "complete and applied".to_string(),
+        };
+        let mut text = format!("Recount of poll {} ({})\n", self.poll_id, status);
+        for (index, count) in self.counts.iter().enumerate() {
+            let flag = if self.discrepancies.contains(&(index as u8)) { "  <- differs from vote count" } else { "" };
+            text.push_str(&format!("  [{}] {}{}\n", index, count, flag));
+        }
+        text.trim_end().to_string()
+    }
+}
+
+/// Poll results labelled with the poll's option names
+/// Outcome of checking each document a poll references against its hash
+pub struct ContentReport {
+    /// What the document describes ("poll" or "option <index>"), its reference and the check result
+    pub documents: Vec<(String, ContentRef, Result<usize, ContentError>)>,
+}
+
+impl Render for ContentReport {
+    fn json(&self) -> Value {
+        Value::Array(
+            self.documents
+                .iter()
+                .map(|(label, document, result)| {
+                    let status = match result {
+                        Ok(_) => "verified",
+                        Err(ContentError::NotFound(_)) => "missing",
+                        Err(ContentError::HashMismatch { .. }) => "hash_mismatch",
+                    };
+                    json!({
+                        "document": label,
+                        "uri": document.uri,
+                        "sha256": hex::encode(&document.hash),
+                        "status": status,
+                        "size": result.as_ref().ok(),
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn pretty(&self) -> String {
+        if self.documents.is_empty() {
+            return "The poll references no documents".to_string();
+        }
+
+        let lines: Vec<String> = self
+            .documents
+            .iter()
+            .map(|(label, document, result)| {
+                let status = match result {
+                    Ok(size) => format!("verified, {} bytes", size),
+                    Err(ContentError::NotFound(_)) => "missing from the content store".to_string(),
+                    Err(ContentError::HashMismatch { actual, .. }) => format!("hash mismatch (sha256 {})", hex::encode(actual)),
+                };
+                format!("{}: {} - {}", label, document.uri, status)
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+pub struct LabeledResults<'a> {
+    pub poll: &'a Poll,
+    pub results: &'a PollResults,
+}
+
+impl Render for LabeledResults<'_> {
+    fn json(&self) -> Value {
+        let outcome = match &self.results.outcome {
+            PollOutcome::Hidden => json!({ "kind": "hidden" }),
+            PollOutcome::NoVotes => json!({ "kind": "no_votes" }),
+            PollOutcome::Winner { option } => json!({ "kind": "winner", "option": option }),
+            PollOutcome::Tie { options } => json!({ "kind": "tie", "options": options }),
+        };
+
+        json!({
+            "poll_id": self.results.poll_id,
+            "title": self.poll.title,
+            "status": format!("{:?}", self.results.status),
+            "is_finalized": self.results.is_finalized,
+            "outcome": outcome,
+            "total_voters": self.results.total_voters,
+            "total_weight": self.results.total_weight,
+            "total_raw_weight": self.results.total_raw_weight,
+            "turnout_bps": self.results.turnout_bps,
+            "options": self.results.options.iter().enumerate().map(|(index, option)| json!({
+                "label": self.poll.options.get(index),
+                "count": option.count,
+                "raw_count": option.raw_count,
+                "proxy_count": option.proxy_count,
+                "share_bps": option.share_bps,
+            })).collect::<Vec<_>>(),
+        })
+    }
+
+    fn pretty(&self) -> String {
+        let results = self.results;
+        let label = |option: u8| self.poll.options.get(option as usize).cloned().unwrap_or_default();
+        let outcome = match &results.outcome {
+            PollOutcome::Hidden => "hidden until the results are decrypted".to_string(),
+            PollOutcome::NoVotes => "no votes yet".to_string(),
+            PollOutcome::Winner { option } => format!("{} leads", label(*option)),
+            PollOutcome::Tie { options } => {
+                let labels: Vec<String> = options.iter().map(|option| label(*option)).collect();
+                format!("tie between {}", labels.join(", "))
+            }
+        };
+
+        let mut text = format!(
+            "{} (poll {})\n  Status: {:?}{}\n  Outcome: {}\n  Voters: {}, weight {} (raw {})\n",
+            self.poll.title,
+            results.poll_id,
+            results.status,
+            if results.is_finalized { ", final" } else { "" },
+            outcome,
+            results.total_voters,
+            results.total_weight,
+            results.total_raw_weight
+        );
+        if let Some(turnout_bps) = results.turnout_bps {
+            text.push_str(&format!("  Turnout: {}\n", percent(turnout_bps)));
+        }
+        for (index, option) in results.options.iter().enumerate() {
+            text.push_str(&format!(
+                "  [{}] {:<24} {:>12} {:>8}\n",
+                index,
+                label(index as u8),
+                option.count,
+                percent(option.share_bps)
+            ));
+        }
+        text.trim_end().to_string()
+    }
+}
+
+impl Render for Submission {
+    fn json(&self) -> Value {
+        let events: Vec<String> = events::parse_logs(self.logs.iter().map(String::as_str))
+            .unwrap_or_default()
+            .iter()
+            .map(|event: &VotingEvent| format!("{:?}", event))
+            .collect();
+
+        json!({ "txid": self.txid, "events": events, "logs": self.logs })
+    }
+
+    fn pretty(&self) -> String {
+        let mut text = format!("Submitted {}\n", self.txid);
+        match events::parse_logs(self.logs.iter().map(String::as_str)) {
+            Ok(events) => {
+                for event in events {
+                    text.push_str(&format!("  {:?}\n", event));
+                }
+            }
+            Err(error) => text.push_str(&format!("  (unreadable event: {:?})\n", error)),
+        }
+        text.trim_end().to_string()
+    }
+}
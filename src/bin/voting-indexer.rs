@@ -0,0 +1,109 @@
+// Rebuilds the indexer view from a snapshot file and answers a single query.
+//
+//     voting-indexer <program id hex> <snapshot file> active <block height>
+//     voting-indexer <program id hex> <snapshot file> voter <pubkey hex>
+//     voting-indexer <program id hex> <snapshot file> delegate <pubkey hex>
+//     voting-indexer <program id hex> <snapshot file> results <poll id> <block height>
+//     voting-indexer <program id hex> <snapshot file> sqlite <database path>   (`sqlite` feature)
+
+use std::process::ExitCode;
+
+use arch_voting_contract::indexer::{self, Indexer, Snapshot};
+use arch_voting_contract::{hex, Poll};
+
+const USAGE: &str = "usage: voting-indexer <program id hex> <snapshot file> \
+    (active <block height> | voter <pubkey> | delegate <pubkey> | results <poll id> <block height> | sqlite <path>)";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let [program_id, snapshot_path, query @ ..] = args else {
+        return Err(USAGE.to_string());
+    };
+
+    let program_id = indexer::parse_pubkey(program_id).ok_or("invalid program id")?;
+    let text = std::fs::read_to_string(snapshot_path).map_err(|error| format!("{}: {}", snapshot_path, error))?;
+    let snapshot = Snapshot::parse(&text).map_err(|error| format!("invalid snapshot: {:?}", error))?;
+
+    let mut index = Indexer::new(program_id);
+    index
+        .ingest_snapshot(&snapshot)
+        .map_err(|error| format!("failed to index snapshot: {:?}", error))?;
+
+    match query {
+        [command, height] if command == "active" => {
+            let height = height.parse().map_err(|_| "invalid block height")?;
+            for poll in index.active_polls(height) {
+                print_poll(poll);
+            }
+        }
+        [command, voter] if command == "voter" => {
+            let voter = indexer::parse_pubkey(voter).ok_or("invalid voter")?;
+            for vote in index.votes_by_voter(&voter) {
+                println!(
+                    "poll {} option {} weight {} (raw {}, {} proxies)",
+                    vote.poll_id,
+                    vote.option_index,
+                    vote.weight,
+                    vote.raw_weight,
+                    vote.proxies.len()
+                );
+            }
+        }
+        [command, delegate] if command == "delegate" => {
+            let delegate = indexer::parse_pubkey(delegate).ok_or("invalid delegate")?;
+            for delegation in index.delegations_to(&delegate) {
+                let bps = delegation
+                    .shares
+                    .iter()
+                    .filter(|share| share.delegate == delegate)
+                    .map(|share| share.bps)
+                    .sum::<u16>();
+                println!(
+                    "delegation {} from {} ({} bps, poll {:?}, topic {:?})",
+                    delegation.id,
+                    hex::encode(&delegation.delegator.serialize()),
+                    bps,
+                    delegation.poll_id,
+                    delegation.topic
+                );
+            }
+        }
+        [command, poll_id, height] if command == "results" => {
+            let poll_id = poll_id.parse().map_err(|_| "invalid poll id")?;
+            let height = height.parse().map_err(|_| "invalid block height")?;
+            let results = index.results(poll_id, height).ok_or("poll not indexed")?;
+            println!("{:#?}", results);
+        }
+        #[cfg(feature = "sqlite")]
+        [command, path] if command == "sqlite" => {
+            let mut connection = rusqlite::Connection::open(path).map_err(|error| error.to_string())?;
+            indexer::sqlite::export(&index, &mut connection).map_err(|error| error.to_string())?;
+            println!("exported {} polls to {}", index.polls().count(), path);
+        }
+        _ => return Err(USAGE.to_string()),
+    }
+
+    Ok(())
+}
+
+fn print_poll(poll: &Poll) {
+    println!(
+        "poll {} \"{}\" ({} options, {} - {})",
+        poll.id,
+        poll.title,
+        poll.options.len(),
+        poll.start_time,
+        poll.end_time
+    );
+}
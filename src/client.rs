@@ -0,0 +1,1125 @@
+use arch_program::{
+    account::AccountMeta,
+    bitcoin::{self, hashes::Hash, Transaction},
+    instruction::Instruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::assets::{AssetHolding, BitcoinAsset};
+use crate::content::ContentRef;
+use crate::weight::{BonusSchedule, WeightTransform, BPS_DENOMINATOR};
+use crate::{
+    pda, validate_delegate_profile, validate_poll, AccountType, DelegateProfile, Delegation, DelegationShare, Poll,
+    PollInstruction, UtxoOutpoint, Vote, VoteCount, VoterRecord, VoterRegistry, VotingError, WeightSource,
+    ACCOUNT_VERSION,
+    MAX_DELEGATION_SHARES, MAX_TOPIC_LEN, VOTERS_PER_PAGE,
+};
+
+// Typed instruction builders for clients.
+// Each builder checks its inputs with the rules the program enforces, derives the program
+// accounts from `pda`, and returns an `Instruction` with its accounts in the order the handler reads them.
+
+/// Rune or inscription holdings proven with `ProveAssetHoldings`
+#[derive(Debug, Clone)]
+pub struct AssetProof {
+    pub asset: BitcoinAsset,
+    pub holdings: Vec<AssetHolding>,
+    /// Raw transactions creating the holding outputs
+    pub prev_txs: Vec<Vec<u8>>,
+}
+
+/// A principal whose weight a `CastVote` carries by proxy
+#[derive(Debug, Clone)]
+pub struct ProxyGroup {
+    pub principal: Pubkey,
+    /// IDs of the delegations from the principal to the voter, in chain order
+    pub delegation_ids: Vec<u64>,
+    /// Delegates whose slices of the principal this vote displaces, in the principal's record order
+    pub displaced_delegates: Vec<Pubkey>,
+}
+
+/// Builds `CreatePoll`; the poll starts out unweighted, public and without delegation
+#[derive(Debug, Clone)]
+pub struct CreatePollBuilder {
+    program_id: Pubkey,
+    poll: Poll,
+    id_nonce: u64,
+}
+
+impl CreatePollBuilder {
+    pub fn new(
+        program_id: Pubkey,
+        creator: Pubkey,
+        id_nonce: u64,
+        title: String,
+        options: Vec<String>,
+        start_time: u64,
+        end_time: u64,
+    ) -> Self {
+        let poll = Poll {
+            account_type: AccountType::Poll,
+            version: ACCOUNT_VERSION,
+            id: pda::poll_id(&creator, id_nonce),
+            creator,
+            title,
+            description: String::new(),
+            options,
+            start_time,
+            end_time,
+            is_private: false,
+            allow_revote: false,
+            is_active: true,
+            is_weighted: false,
+            allow_delegation: false,
+            is_encrypted: false,
+            decryption_key: None,
+            weight_token: None,
+            early_voter_bonus: 0,
+            bonus_schedule: BonusSchedule::Linear,
+            balance_oracle: None,
+            max_balance_age: 0,
+            weight_source: WeightSource::TokenBalance,
+            weight_transform: WeightTransform::NONE,
+            eligibility_asset: None,
+            max_delegation_depth: 0,
+            topic: None,
+            require_registered_delegates: false,
+            max_extensions: 0,
+            extension_count: 0,
+            quorum: None,
+            allow_early_close: false,
+            metadata: None,
+            option_metadata: Vec::new(),
+        };
+
+        CreatePollBuilder { program_id, poll, id_nonce }
+    }
+
+    /// ID the poll will be created with
+    pub fn poll_id(&self) -> u64 {
+        self.poll.id
+    }
+
+    pub fn description(mut self, description: String) -> Self {
+        self.poll.description = description;
+        self
+    }
+
+    /// Require a zero-knowledge proof with every vote
+    pub fn private(mut self) -> Self {
+        self.poll.is_private = true;
+        self
+    }
+
+    pub fn allow_revote(mut self) -> Self {
+        self.poll.allow_revote = true;
+        self
+    }
+
+    pub fn encrypted(mut self) -> Self {
+        self.poll.is_encrypted = true;
+        self
+    }
+
+    /// Weight votes by `weight_source`; token-backed sources take the token in `weight_token`
+    pub fn weighted(mut self, weight_source: WeightSource, weight_token: Option<Pubkey>) -> Self {
+        self.poll.is_weighted = true;
+        self.poll.weight_source = weight_source;
+        self.poll.weight_token = weight_token;
+        self
+    }
+
+    /// Oracle attesting balances and holdings, and how many blocks its attestations stay fresh
+    pub fn balance_oracle(mut self, oracle: Pubkey, max_balance_age: u64) -> Self {
+        self.poll.balance_oracle = Some(oracle);
+        self.poll.max_balance_age = max_balance_age;
+        self
+    }
+
+    pub fn weight_transform(mut self, weight_transform: WeightTransform) -> Self {
+        self.poll.weight_transform = weight_transform;
+        self
+    }
+
+    pub fn early_voter_bonus(mut self, early_voter_bonus: u8, bonus_schedule: BonusSchedule) -> Self {
+        self.poll.early_voter_bonus = early_voter_bonus;
+        self.poll.bonus_schedule = bonus_schedule;
+        self
+    }
+
+    pub fn eligibility_asset(mut self, asset: BitcoinAsset) -> Self {
+        self.poll.eligibility_asset = Some(asset);
+        self
+    }
+
+    /// Allow delegated weight through chains of up to `max_delegation_depth` hops
+    pub fn allow_delegation(mut self, max_delegation_depth: u8, require_registered_delegates: bool) -> Self {
+        self.poll.allow_delegation = true;
+        self.poll.max_delegation_depth = max_delegation_depth;
+        self.poll.require_registered_delegates = require_registered_delegates;
+        self
+    }
+
+    pub fn topic(mut self, topic: String) -> Self {
+        self.poll.topic = Some(topic);
+        self
+    }
+
+    /// Let the creator push back the end time up to `max_extensions` times, only while less
+    /// than `quorum` raw weight has been cast if a quorum is given
+    pub fn extensions(mut self, max_extensions: u8, quorum: Option<u64>) -> Self {
+        self.poll.max_extensions = max_extensions;
+        self.poll.quorum = quorum;
+        self
+    }
+
+    /// Let anyone close the poll early once its outcome is decided
+    pub fn allow_early_close(mut self) -> Self {
+        self.poll.allow_early_close = true;
+        self
+    }
+
+    /// Off-chain document with the full proposal
+    pub fn metadata(mut self, metadata: ContentRef) -> Self {
+        self.poll.metadata = Some(metadata);
+        self
+    }
+
+    /// Off-chain document of each option, one entry per option
+    pub fn option_metadata(mut self, option_metadata: Vec<Option<ContentRef>>) -> Self {
+        self.poll.option_metadata = option_metadata;
+        self
+    }
+
+    /// Validate the poll as of block height `current_time` and build the instruction
+    pub fn build(self, current_time: u64, tx_hex: Vec<u8>) -> Result<Instruction, ProgramError> {
+        validate_poll(&self.poll, current_time)?;
+
+        let program_id = &self.program_id;
+        let poll = self.poll;
+        let mut accounts = vec![
+            writable_signer(poll.creator),
+            writable(pda::find_poll_address(program_id, poll.id).0),
+            writable(pda::find_vote_count_address(program_id, poll.id).0),
+            writable(pda::find_voter_registry_address(program_id, poll.id, 0).0),
+        ];
+
+        if poll.needs_token_account() {
+            // Validation guarantees token-backed polls name their token
+            accounts.push(readonly(poll.weight_token.ok_or(VotingError::InvalidPollParameters)?));
+        }
+
+        let data = PollInstruction::CreatePoll {
+            title: poll.title,
+            description: poll.description,
+            options: poll.options,
+            start_time: poll.start_time,
+            end_time: poll.end_time,
+            is_private: poll.is_private,
+            allow_revote: poll.allow_revote,
+            is_weighted: poll.is_weighted,
+            allow_delegation: poll.allow_delegation,
+            is_encrypted: poll.is_encrypted,
+            weight_token: poll.weight_token,
+            early_voter_bonus: poll.early_voter_bonus,
+            bonus_schedule: poll.bonus_schedule,
+            balance_oracle: poll.balance_oracle,
+            max_balance_age: poll.max_balance_age,
+            weight_source: poll.weight_source,
+            weight_transform: poll.weight_transform,
+            eligibility_asset: poll.eligibility_asset,
+            max_delegation_depth: poll.max_delegation_depth,
+            topic: poll.topic,
+            require_registered_delegates: poll.require_registered_delegates,
+            max_extensions: poll.max_extensions,
+            quorum: poll.quorum,
+            allow_early_close: poll.allow_early_close,
+            metadata: poll.metadata,
+            option_metadata: poll.option_metadata,
+            id_nonce: self.id_nonce,
+            tx_hex,
+        };
+
+        instruction(program_id, accounts, &data)
+    }
+}
+
+/// Builds `UpdatePoll` from the current poll; fields left untouched keep their current values
+#[derive(Debug, Clone)]
+pub struct UpdatePollBuilder {
+    program_id: Pubkey,
+    poll: Poll,
+    current_start_time: u64,
+}
+
+impl UpdatePollBuilder {
+    pub fn new(program_id: Pubkey, poll: &Poll) -> Self {
+        UpdatePollBuilder { program_id, poll: poll.clone(), current_start_time: poll.start_time }
+    }
+
+    pub fn title(mut self, title: String) -> Self {
+        self.poll.title = title;
+        self
+    }
+
+    pub fn description(mut self, description: String) -> Self {
+        self.poll.description = description;
+        self
+    }
+
+    /// Replace the options; the vote count is resized to match
+    pub fn options(mut self, options: Vec<String>) -> Self {
+        self.poll.options = options;
+        self
+    }
+
+    pub fn times(mut self, start_time: u64, end_time: u64) -> Self {
+        self.poll.start_time = start_time;
+        self.poll.end_time = end_time;
+        self
+    }
+
+    /// Replace the proposal document (`None` removes it)
+    pub fn metadata(mut self, metadata: Option<ContentRef>) -> Self {
+        self.poll.metadata = metadata;
+        self
+    }
+
+    /// Replace the option documents; must be empty or match the options in number
+    pub fn option_metadata(mut self, option_metadata: Vec<Option<ContentRef>>) -> Self {
+        self.poll.option_metadata = option_metadata;
+        self
+    }
+
+    /// Check the edit as of block height `current_time` and build the instruction. Whether a
+    /// vote has been cast is only checked by the program.
+    pub fn build(self, current_time: u64, tx_hex: Vec<u8>) -> Result<Instruction, ProgramError> {
+        if !self.poll.is_active {
+            return Err(VotingError::PollNotActive.into());
+        }
+
+        if current_time >= self.current_start_time {
+            return Err(VotingError::PollAlreadyStarted.into());
+        }
+
+        validate_poll(&self.poll, current_time)?;
+
+        let program_id = &self.program_id;
+        let poll = self.poll;
+        let accounts = vec![
+            signer(poll.creator),
+            writable(pda::find_poll_address(program_id, poll.id).0),
+            writable(pda::find_vote_count_address(program_id, poll.id).0),
+        ];
+
+        let data = PollInstruction::UpdatePoll {
+            poll_id: poll.id,
+            title: poll.title,
+            description: poll.description,
+            options: poll.options,
+            start_time: poll.start_time,
+            end_time: poll.end_time,
+            metadata: poll.metadata,
+            option_metadata: poll.option_metadata,
+            tx_hex,
+        };
+
+        instruction(program_id, accounts, &data)
+    }
+}
+
+/// Builds `CastVote` from the current state of the poll and the voter's record
+#[derive(Debug, Clone)]
+pub struct CastVoteBuilder<'a> {
+    program_id: Pubkey,
+    voter: Pubkey,
+    poll: &'a Poll,
+    vote_count: &'a VoteCount,
+    option_index: u8,
+    weight: Option<u64>,
+    encrypted_data: Option<Vec<u8>>,
+    zk_proof: Option<Vec<u8>>,
+    nonce: Option<Vec<u8>>,
+    voter_record: Option<&'a VoterRecord>,
+    previous_vote: Option<&'a Vote>,
+    proxy_groups: Vec<ProxyGroup>,
+}
+
+impl<'a> CastVoteBuilder<'a> {
+    pub fn new(program_id: Pubkey, voter: Pubkey, poll: &'a Poll, vote_count: &'a VoteCount, option_index: u8) -> Self {
+        CastVoteBuilder {
+            program_id,
+            voter,
+            poll,
+            vote_count,
+            option_index,
+            weight: None,
+            encrypted_data: None,
+            zk_proof: None,
+            nonce: None,
+            voter_record: None,
+            previous_vote: None,
+            proxy_groups: Vec::new(),
+        }
+    }
+
+    /// Weight of the vote (unweighted polls only)
+    pub fn weight(mut self, weight: u64) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+
+    /// Encrypted choice and its nonce (encrypted polls)
+    pub fn encrypted(mut self, encrypted_data: Vec<u8>, nonce: Vec<u8>) -> Self {
+        self.encrypted_data = Some(encrypted_data);
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Zero-knowledge proof (private polls)
+    pub fn zk_proof(mut self, zk_proof: Vec<u8>) -> Self {
+        self.zk_proof = Some(zk_proof);
+        self
+    }
+
+    /// The voter's current record; its carriers are displaced by the vote
+    pub fn voter_record(mut self, voter_record: &'a VoterRecord) -> Self {
+        self.voter_record = Some(voter_record);
+        self
+    }
+
+    /// The vote being replaced, on a revote; the principals it carried are re-declared
+    pub fn previous_vote(mut self, previous_vote: &'a Vote) -> Self {
+        self.previous_vote = Some(previous_vote);
+        self
+    }
+
+    pub fn proxy_group(mut self, proxy_group: ProxyGroup) -> Self {
+        self.proxy_groups.push(proxy_group);
+        self
+    }
+
+    /// Check the vote against the poll as of block height `current_time` and build the instruction
+    pub fn build(self, current_time: u64, tx_hex: Vec<u8>) -> Result<Instruction, ProgramError> {
+        let program_id = &self.program_id;
+        let poll = self.poll;
+
+        if !poll.is_active {
+            return Err(VotingError::PollNotActive.into());
+        }
+
+        if current_time < poll.start_time {
+            return Err(VotingError::PollNotStarted.into());
+        }
+
+        if current_time > poll.end_time {
+            return Err(VotingError::PollEnded.into());
+        }
+
+        if self.option_index as usize >= poll.options.len() {
+            return Err(VotingError::InvalidOptionIndex.into());
+        }
+
+        let is_revote = self.voter_record.is_some_and(|record| record.has_voted);
+        if is_revote && !poll.allow_revote {
+            return Err(VotingError::AlreadyVoted.into());
+        }
+
+        if poll.is_private && self.zk_proof.as_ref().is_none_or(|proof| proof.is_empty()) {
+            return Err(VotingError::InvalidZkProof.into());
+        }
+
+        if poll.is_encrypted {
+            if self.encrypted_data.is_none() {
+                return Err(VotingError::InvalidEncryption.into());
+            }
+
+            if self.nonce.is_none() {
+                return Err(VotingError::MissingNonce.into());
+            }
+        }
+
+        if poll.is_weighted && self.weight.is_some() {
+            return Err(VotingError::InvalidVoteWeight.into());
+        }
+
+        if !poll.allow_delegation && !self.proxy_groups.is_empty() {
+            return Err(VotingError::InvalidDelegation.into());
+        }
+
+        // First-time voters are listed on the registry page currently being filled
+        let page = u32::try_from(self.vote_count.total_voters / VOTERS_PER_PAGE as u64)
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        let mut accounts = vec![
+            signer(self.voter),
+            writable(pda::find_vote_address(program_id, poll.id, &self.voter).0),
+            writable(pda::find_poll_address(program_id, poll.id).0),
+            writable(pda::find_vote_count_address(program_id, poll.id).0),
+            writable(pda::find_voter_registry_address(program_id, poll.id, page).0),
+            writable(pda::find_voter_record_address(program_id, poll.id, &self.voter).0),
+        ];
+
+        accounts.extend(weight_accounts(program_id, poll, &self.voter)?);
+
+        if poll.allow_delegation {
+            // Delegates carrying the voter's weight give up their slices
+            for carried in self.voter_record.map_or(&[][..], |record| record.carried.as_slice()) {
+                accounts.push(writable(pda::find_vote_address(program_id, poll.id, &carried.delegate).0));
+            }
+
+            // A revote re-declares the principals the previous vote carried
+            if is_revote {
+                let previous_vote = self.previous_vote.ok_or(ProgramError::NotEnoughAccountKeys)?;
+                for proxy in &previous_vote.proxies {
+                    accounts.push(writable(pda::find_voter_record_address(program_id, poll.id, &proxy.principal).0));
+                }
+            }
+
+            for group in &self.proxy_groups {
+                if group.delegation_ids.is_empty() || group.delegation_ids.len() > poll.max_delegation_depth as usize {
+                    return Err(VotingError::InvalidDelegation.into());
+                }
+
+                for delegation_id in &group.delegation_ids {
+                    accounts.push(readonly(pda::find_delegation_address(program_id, *delegation_id).0));
+                }
+
+                accounts.extend(weight_accounts(program_id, poll, &group.principal)?);
+
+                accounts.push(writable(pda::find_voter_record_address(program_id, poll.id, &group.principal).0));
+
+                for delegate in &group.displaced_delegates {
+                    accounts.push(writable(pda::find_vote_address(program_id, poll.id, delegate).0));
+                }
+            }
+        }
+
+        let data = PollInstruction::CastVote {
+            poll_id: poll.id,
+            option_index: self.option_index,
+            weight: self.weight,
+            encrypted_data: self.encrypted_data,
+            zk_proof: self.zk_proof,
+            nonce: self.nonce,
+            tx_hex,
+        };
+
+        instruction(program_id, accounts, &data)
+    }
+}
+
+/// Builds `ChangeVote`
+#[derive(Debug, Clone)]
+pub struct ChangeVoteBuilder<'a> {
+    program_id: Pubkey,
+    voter: Pubkey,
+    poll: &'a Poll,
+    new_option_index: u8,
+    new_encrypted_data: Option<Vec<u8>>,
+    new_zk_proof: Option<Vec<u8>>,
+    new_nonce: Option<Vec<u8>>,
+}
+
+impl<'a> ChangeVoteBuilder<'a> {
+    pub fn new(program_id: Pubkey, voter: Pubkey, poll: &'a Poll, new_option_index: u8) -> Self {
+        ChangeVoteBuilder {
+            program_id,
+            voter,
+            poll,
+            new_option_index,
+            new_encrypted_data: None,
+            new_zk_proof: None,
+            new_nonce: None,
+        }
+    }
+
+    /// Encrypted choice and its nonce (encrypted polls)
+    pub fn encrypted(mut self, encrypted_data: Vec<u8>, nonce: Vec<u8>) -> Self {
+        self.new_encrypted_data = Some(encrypted_data);
+        self.new_nonce = Some(nonce);
+        self
+    }
+
+    /// Zero-knowledge proof (private polls)
+    pub fn zk_proof(mut self, zk_proof: Vec<u8>) -> Self {
+        self.new_zk_proof = Some(zk_proof);
+        self
+    }
+
+    /// Check the change against the poll as of block height `current_time` and build the instruction
+    pub fn build(self, current_time: u64, tx_hex: Vec<u8>) -> Result<Instruction, ProgramError> {
+        let program_id = &self.program_id;
+        let poll = self.poll;
+
+        if !poll.is_active {
+            return Err(VotingError::PollNotActive.into());
+        }
+
+        if !poll.allow_revote {
+            return Err(VotingError::RevotingNotAllowed.into());
+        }
+
+        if current_time < poll.start_time {
+            return Err(VotingError::PollNotStarted.into());
+        }
+
+        if current_time > poll.end_time {
+            return Err(VotingError::PollEnded.into());
+        }
+
+        if self.new_option_index as usize >= poll.options.len() {
+            return Err(VotingError::InvalidOptionIndex.into());
+        }
+
+        if poll.is_private && self.new_zk_proof.as_ref().is_none_or(|proof| proof.is_empty()) {
+            return Err(VotingError::InvalidZkProof.into());
+        }
+
+        if poll.is_encrypted {
+            if self.new_encrypted_data.is_none() {
+                return Err(VotingError::InvalidEncryption.into());
+            }
+
+            if self.new_nonce.is_none() {
+                return Err(VotingError::MissingNonce.into());
+            }
+        }
+
+        let accounts = vec![
+            signer(self.voter),
+            writable(pda::find_vote_address(program_id, poll.id, &self.voter).0),
+            writable(pda::find_poll_address(program_id, poll.id).0),
+            writable(pda::find_vote_count_address(program_id, poll.id).0),
+        ];
+
+        let data = PollInstruction::ChangeVote {
+            poll_id: poll.id,
+            new_option_index: self.new_option_index,
+            new_encrypted_data: self.new_encrypted_data,
+            new_zk_proof: self.new_zk_proof,
+            new_nonce: self.new_nonce,
+            tx_hex,
+        };
+
+        instruction(program_id, accounts, &data)
+    }
+}
+
+/// Builds `DelegateVote`; without a poll or topic the delegation applies to every poll
+#[derive(Debug, Clone)]
+pub struct DelegateVoteBuilder {
+    program_id: Pubkey,
+    delegator: Pubkey,
+    id_nonce: u64,
+    shares: Vec<DelegationShare>,
+    poll_id: Option<u64>,
+    topic: Option<String>,
+    expiration: Option<u64>,
+}
+
+impl DelegateVoteBuilder {
+    /// Delegate to `shares`, whose basis points must sum to 10000
+    pub fn new(program_id: Pubkey, delegator: Pubkey, id_nonce: u64, shares: Vec<DelegationShare>) -> Self {
+        DelegateVoteBuilder {
+            program_id,
+            delegator,
+            id_nonce,
+            shares,
+            poll_id: None,
+            topic: None,
+            expiration: None,
+        }
+    }
+
+    /// ID the delegation will be created with
+    pub fn delegation_id(&self) -> u64 {
+        pda::delegation_id(&self.delegator, self.id_nonce)
+    }
+
+    pub fn poll(mut self, poll_id: u64) -> Self {
+        self.poll_id = Some(poll_id);
+        self
+    }
+
+    pub fn topic(mut self, topic: String) -> Self {
+        self.topic = Some(topic);
+        self
+    }
+
+    pub fn expiration(mut self, expiration: u64) -> Self {
+        self.expiration = Some(expiration);
+        self
+    }
+
+    /// Check the delegation as of block height `current_time` and build the instruction
+    pub fn build(self, current_time: u64, tx_hex: Vec<u8>) -> Result<Instruction, ProgramError> {
+        let program_id = &self.program_id;
+
+        if self.shares.is_empty() || self.shares.len() > MAX_DELEGATION_SHARES {
+            return Err(VotingError::InvalidPollParameters.into());
+        }
+
+        for (index, share) in self.shares.iter().enumerate() {
+            if share.delegate == self.delegator {
+                return Err(VotingError::DelegationCycle.into());
+            }
+
+            if share.bps == 0 || self.shares[..index].iter().any(|other| other.delegate == share.delegate) {
+                return Err(VotingError::InvalidPollParameters.into());
+            }
+        }
+
+        if self.shares.iter().map(|share| share.bps as u64).sum::<u64>() != BPS_DENOMINATOR {
+            return Err(VotingError::InvalidPollParameters.into());
+        }
+
+        if let Some(topic) = &self.topic {
+            if self.poll_id.is_some() || topic.is_empty() || topic.len() > MAX_TOPIC_LEN {
+                return Err(VotingError::InvalidPollParameters.into());
+            }
+        }
+
+        if self.expiration.is_some_and(|expiration| expiration <= current_time) {
+            return Err(VotingError::InvalidPollParameters.into());
+        }
+
+        let mut accounts = vec![
+            signer(self.delegator),
+            writable(pda::find_delegation_address(program_id, self.delegation_id()).0),
+        ];
+        accounts.extend(self.shares.iter().map(|share| readonly(share.delegate)));
+        accounts.extend(
+            self.shares
+                .iter()
+                .map(|share| writable(pda::find_delegate_profile_address(program_id, &share.delegate).0)),
+        );
+        if let Some(poll_id) = self.poll_id {
+            accounts.push(readonly(pda::find_poll_address(program_id, poll_id).0));
+        }
+
+        let data = PollInstruction::DelegateVote {
+            poll_id: self.poll_id,
+            topic: self.topic,
+            shares: self.shares.iter().map(|share| share.bps).collect(),
+            expiration: self.expiration,
+            id_nonce: self.id_nonce,
+            tx_hex,
+        };
+
+        instruction(program_id, accounts, &data)
+    }
+}
+
+/// Builds `RegisterDelegate`; the delegate accepts delegations without a cap unless told otherwise
+#[derive(Debug, Clone)]
+pub struct RegisterDelegateBuilder {
+    program_id: Pubkey,
+    profile: DelegateProfile,
+}
+
+impl RegisterDelegateBuilder {
+    pub fn new(
+        program_id: Pubkey,
+        delegate: Pubkey,
+        statement_uri: String,
+        statement_hash: [u8; 32],
+    ) -> Self {
+        let profile = DelegateProfile {
+            account_type: AccountType::DelegateProfile,
+            version: ACCOUNT_VERSION,
+            delegate,
+            statement_uri,
+            statement_hash,
+            voting_policy: String::new(),
+            accepting_delegations: true,
+            max_delegators: None,
+            delegator_count: 0,
+            last_updated: 0,
+        };
+
+        RegisterDelegateBuilder { program_id, profile }
+    }
+
+    pub fn voting_policy(mut self, voting_policy: String) -> Self {
+        self.profile.voting_policy = voting_policy;
+        self
+    }
+
+    pub fn accepting_delegations(mut self, accepting_delegations: bool) -> Self {
+        self.profile.accepting_delegations = accepting_delegations;
+        self
+    }
+
+    pub fn max_delegators(mut self, max_delegators: u32) -> Self {
+        self.profile.max_delegators = Some(max_delegators);
+        self
+    }
+
+    pub fn build(self, tx_hex: Vec<u8>) -> Result<Instruction, ProgramError> {
+        validate_delegate_profile(&self.profile)?;
+
+        let profile = self.profile;
+        let accounts = vec![
+            signer(profile.delegate),
+            writable(pda::find_delegate_profile_address(&self.program_id, &profile.delegate).0),
+        ];
+
+        let data = PollInstruction::RegisterDelegate {
+            statement_uri: profile.statement_uri,
+            statement_hash: profile.statement_hash,
+            voting_policy: profile.voting_policy,
+            accepting_delegations: profile.accepting_delegations,
+            max_delegators: profile.max_delegators,
+            tx_hex,
+        };
+
+        instruction(&self.program_id, accounts, &data)
+    }
+}
+
+/// `CancelPoll`
+pub fn cancel_poll(program_id: &Pubkey, creator: &Pubkey, poll_id: u64, tx_hex: Vec<u8>) -> Result<Instruction, ProgramError> {
+    let accounts = vec![signer(*creator), writable(pda::find_poll_address(program_id, poll_id).0)];
+
+    instruction(program_id, accounts, &PollInstruction::CancelPoll { poll_id, tx_hex })
+}
+
+/// `ExtendPoll`; checks the extension against `poll` as of block height `current_time`
+pub fn extend_poll(
+    program_id: &Pubkey,
+    poll: &Poll,
+    new_end_time: u64,
+    current_time: u64,
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    if !poll.is_active {
+        return Err(VotingError::PollNotActive.into());
+    }
+
+    if current_time < poll.start_time {
+        return Err(VotingError::PollNotStarted.into());
+    }
+
+    if current_time > poll.end_time {
+        return Err(VotingError::PollEnded.into());
+    }
+
+    if new_end_time <= poll.end_time {
+        return Err(VotingError::InvalidPollParameters.into());
+    }
+
+    if poll.extension_count >= poll.max_extensions {
+        return Err(VotingError::ExtensionLimitReached.into());
+    }
+
+    let accounts = vec![
+        signer(poll.creator),
+        writable(pda::find_poll_address(program_id, poll.id).0),
+        readonly(pda::find_vote_count_address(program_id, poll.id).0),
+    ];
+
+    instruction(program_id, accounts, &PollInstruction::ExtendPoll { poll_id: poll.id, new_end_time, tx_hex })
+}
+
+/// `ClosePoll`; before the end time only the creator can close the poll, or anyone once the
+/// outcome is decided (see `results::is_decided`) if the poll allows early close
+pub fn close_poll(program_id: &Pubkey, caller: &Pubkey, poll_id: u64, tx_hex: Vec<u8>) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        signer(*caller),
+        writable(pda::find_poll_address(program_id, poll_id).0),
+        writable(pda::find_vote_count_address(program_id, poll_id).0),
+    ];
+
+    instruction(program_id, accounts, &PollInstruction::ClosePoll { poll_id, tx_hex })
+}
+
+/// `GetResults`; decode the return data with `results::PollResults::decode`
+pub fn get_results(program_id: &Pubkey, poll_id: u64) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        readonly(pda::find_poll_address(program_id, poll_id).0),
+        readonly(pda::find_vote_count_address(program_id, poll_id).0),
+    ];
+
+    instruction(program_id, accounts, &PollInstruction::GetResults { poll_id })
+}
+
+/// `DecryptResults`, passing the vote accounts of `voters`
+pub fn decrypt_results(
+    program_id: &Pubkey,
+    creator: &Pubkey,
+    poll_id: u64,
+    decryption_key: Vec<u8>,
+    voters: &[Pubkey],
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        signer(*creator),
+        writable(pda::find_poll_address(program_id, poll_id).0),
+        writable(pda::find_vote_count_address(program_id, poll_id).0),
+    ];
+    accounts.extend(voters.iter().map(|voter| writable(pda::find_vote_address(program_id, poll_id, voter).0)));
+
+    instruction(program_id, accounts, &PollInstruction::DecryptResults { poll_id, decryption_key, tx_hex })
+}
+
+/// `RevokeDelegation` of `delegate`'s share of `delegation`, or of the whole delegation; a registered
+/// delegation also takes the profile of each revoked delegate
+pub fn revoke_delegation(
+    program_id: &Pubkey,
+    delegation: &Delegation,
+    delegate: Option<Pubkey>,
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let revoked: Vec<&DelegationShare> = match delegate {
+        Some(delegate) => vec![delegation
+            .shares
+            .iter()
+            .find(|share| share.delegate == delegate)
+            .ok_or(VotingError::DelegationNotFound)?],
+        None => delegation.shares.iter().collect(),
+    };
+
+    let mut accounts = vec![
+        signer(delegation.delegator),
+        writable(pda::find_delegation_address(program_id, delegation.id).0),
+    ];
+    if delegation.registered && delegation.is_active {
+        accounts.extend(
+            revoked
+                .iter()
+                .map(|share| writable(pda::find_delegate_profile_address(program_id, &share.delegate).0)),
+        );
+    }
+
+    instruction(
+        program_id,
+        accounts,
+        &PollInstruction::RevokeDelegation { delegation_id: delegation.id, delegate, tx_hex },
+    )
+}
+
+/// `UpdateTokenBalance`, co-signed by `oracle`
+pub fn update_token_balance(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    token: &Pubkey,
+    amount: u64,
+    oracle: &Pubkey,
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![
+        signer(*owner),
+        writable(pda::find_token_balance_address(program_id, owner, token).0),
+        readonly(*token),
+        signer(*oracle),
+    ];
+
+    instruction(program_id, accounts, &PollInstruction::UpdateTokenBalance { token: *token, amount, tx_hex })
+}
+
+/// `ProveUtxoWeight`; the fee transaction's inputs after the first are the proven UTXOs, which
+/// the voter signs along with the fee input
+pub fn prove_utxo_weight(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll_id: u64,
+    prev_txs: Vec<Vec<u8>>,
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let fee_tx: Transaction =
+        bitcoin::consensus::deserialize(&tx_hex).map_err(|_| VotingError::InvalidFeeTransaction)?;
+
+    let mut accounts = vec![
+        signer(*voter),
+        writable(pda::find_utxo_weight_address(program_id, poll_id, voter).0),
+        readonly(pda::find_poll_address(program_id, poll_id).0),
+    ];
+    for input in fee_tx.input.iter().skip(1) {
+        let outpoint = UtxoOutpoint {
+            txid: input.previous_output.txid.to_byte_array(),
+            vout: input.previous_output.vout,
+        };
+        accounts.push(writable(pda::find_utxo_claim_address(program_id, poll_id, &outpoint).0));
+    }
+
+    instruction(program_id, accounts, &PollInstruction::ProveUtxoWeight { poll_id, prev_txs, tx_hex })
+}
+
+/// `ProveAssetHoldings`, co-signed by the poll's oracle
+pub fn prove_asset_holdings(
+    program_id: &Pubkey,
+    voter: &Pubkey,
+    poll: &Poll,
+    proof: AssetProof,
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    // Only the poll's weight or eligibility asset can be proven
+    let weight_asset = match (&poll.weight_source, poll.is_weighted) {
+        (WeightSource::BitcoinAsset(asset), true) => Some(asset),
+        _ => None,
+    };
+    if weight_asset != Some(&proof.asset) && poll.eligibility_asset.as_ref() != Some(&proof.asset) {
+        return Err(VotingError::InvalidAssetProof.into());
+    }
+
+    let oracle = poll.balance_oracle.ok_or(VotingError::InvalidPollParameters)?;
+    let mut accounts = vec![
+        signer(*voter),
+        writable(pda::find_asset_holdings_address(program_id, poll.id, voter, &proof.asset).0),
+        readonly(pda::find_poll_address(program_id, poll.id).0),
+        signer(oracle),
+    ];
+
+    // Each holding's outpoint is claimed at its own address
+    accounts.extend(proof.holdings.iter().map(|holding| {
+        writable(pda::find_asset_claim_address(program_id, poll.id, &proof.asset, &holding.outpoint).0)
+    }));
+
+    let data = PollInstruction::ProveAssetHoldings {
+        poll_id: poll.id,
+        asset: proof.asset,
+        holdings: proof.holdings,
+        prev_txs: proof.prev_txs,
+        tx_hex,
+    };
+
+    instruction(program_id, accounts, &data)
+}
+
+/// `LockEscrow`
+pub fn lock_escrow(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    token: &Pubkey,
+    amount: u64,
+    unlock_height: u64,
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let accounts = escrow_accounts(program_id, owner, token);
+
+    instruction(program_id, accounts, &PollInstruction::LockEscrow { token: *token, amount, unlock_height, tx_hex })
+}
+
+/// `ExtendEscrow`
+pub fn extend_escrow(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    token: &Pubkey,
+    new_unlock_height: u64,
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let accounts = vec![signer(*owner), writable(pda::find_escrow_address(program_id, owner, token).0)];
+
+    instruction(program_id, accounts, &PollInstruction::ExtendEscrow { new_unlock_height, tx_hex })
+}
+
+/// `WithdrawEscrow`
+pub fn withdraw_escrow(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    token: &Pubkey,
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let accounts = escrow_accounts(program_id, owner, token);
+
+    instruction(program_id, accounts, &PollInstruction::WithdrawEscrow { tx_hex })
+}
+
+/// `Recount` of the voters on `registry` from position `next_voter` to the end of the page.
+/// Pass `next_voter` from the recount tally in progress, or 0 with `restart`.
+pub fn recount(
+    program_id: &Pubkey,
+    caller: &Pubkey,
+    registry: &VoterRegistry,
+    next_voter: u64,
+    restart: bool,
+    apply: bool,
+    tx_hex: Vec<u8>,
+) -> Result<Instruction, ProgramError> {
+    let poll_id = registry.poll_id;
+    let page_start = registry.page as u64 * VOTERS_PER_PAGE as u64;
+    if next_voter < page_start || next_voter >= page_start + VOTERS_PER_PAGE as u64 {
+        return Err(VotingError::InvalidAccountAddress.into());
+    }
+
+    let vote_count = pda::find_vote_count_address(program_id, poll_id).0;
+    let mut accounts = vec![
+        signer(*caller),
+        writable(pda::find_recount_address(program_id, poll_id).0),
+        readonly(pda::find_poll_address(program_id, poll_id).0),
+        if apply { writable(vote_count) } else { readonly(vote_count) },
+        readonly(pda::find_voter_registry_address(program_id, poll_id, registry.page).0),
+    ];
+
+    let position = (next_voter - page_start) as usize;
+    accounts.extend(
+        registry
+            .voters
+            .iter()
+            .skip(position)
+            .map(|voter| readonly(pda::find_vote_address(program_id, poll_id, voter).0)),
+    );
+
+    instruction(program_id, accounts, &PollInstruction::Recount { poll_id, restart, apply, tx_hex })
+}
+
+// Owner, escrow position and token balance of `owner`'s escrow in `token`
+fn escrow_accounts(program_id: &Pubkey, owner: &Pubkey, token: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        signer(*owner),
+        writable(pda::find_escrow_address(program_id, owner, token).0),
+        writable(pda::find_token_balance_address(program_id, owner, token).0),
+    ]
+}
+
+// Weight account, then eligibility account, backing `owner`'s share of a vote, as the poll needs them
+fn weight_accounts(program_id: &Pubkey, poll: &Poll, owner: &Pubkey) -> Result<Vec<AccountMeta>, ProgramError> {
+    let mut accounts = Vec::new();
+
+    if poll.is_weighted {
+        let weight_account = match (poll.weight_source, poll.weight_token) {
+            (WeightSource::TokenBalance, Some(token)) => pda::find_token_balance_address(program_id, owner, &token).0,
+            (WeightSource::VoteEscrow, Some(token)) => pda::find_escrow_address(program_id, owner, &token).0,
+            (WeightSource::BitcoinUtxo, _) => pda::find_utxo_weight_address(program_id, poll.id, owner).0,
+            (WeightSource::BitcoinAsset(asset), _) => pda::find_asset_holdings_address(program_id, poll.id, owner, &asset).0,
+            // Validation guarantees token-backed polls name their token
+            _ => return Err(VotingError::InvalidPollParameters.into()),
+        };
+        accounts.push(readonly(weight_account));
+
+        // Escrow weight is backed by the owner's attested balance of the escrowed token
+        if let (WeightSource::VoteEscrow, Some(token)) = (poll.weight_source, poll.weight_token) {
+            accounts.push(readonly(pda::find_token_balance_address(program_id, owner, &token).0));
+        }
+    }
+
+    if let Some(asset) = poll.eligibility_asset {
+        accounts.push(readonly(pda::find_asset_holdings_address(program_id, poll.id, owner, &asset).0));
+    }
+
+    Ok(accounts)
+}
+
+fn instruction(program_id: &Pubkey, accounts: Vec<AccountMeta>, data: &PollInstruction) -> Result<Instruction, ProgramError> {
+    let data = borsh::to_vec(&data).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    Ok(Instruction { program_id: *program_id, accounts, data })
+}
+
+fn signer(pubkey: Pubkey) -> AccountMeta {
+    AccountMeta { pubkey, is_signer: true, is_writable: false }
+}
+
+fn writable_signer(pubkey: Pubkey) -> AccountMeta {
+    AccountMeta { pubkey, is_signer: true, is_writable: true }
+}
+
+fn writable(pubkey: Pubkey) -> AccountMeta {
+    AccountMeta { pubkey, is_signer: false, is_writable: true }
+}
+
+fn readonly(pubkey: Pubkey) -> AccountMeta {
+    AccountMeta { pubkey, is_signer: false, is_writable: false }
+}
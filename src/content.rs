@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+#[cfg(not(target_os = "solana"))]
+use std::path::{Component, Path, PathBuf};
+
+use arch_program::bitcoin::hashes::{sha256, Hash};
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{Poll, VotingError, MAX_CONTENT_URI_LEN};
+
+// Content-addressed documents.
+// A poll and each of its options can point at a document kept off-chain (the full proposal,
+// an option's rationale) instead of storing it in the poll account. The SHA-256 hash stored
+// next to the URI pins the content, so a client can fetch the document from any store and
+// check it is the one the poll was created with.
+
+/// Off-chain document, located by URI and pinned by the SHA-256 hash of its content
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ContentRef {
+    /// Where the document is published (e.g. `ipfs://...` or `https://...`)
+    pub uri: String,
+    /// SHA-256 hash of the document
+    pub hash: [u8; 32],
+}
+
+impl ContentRef {
+    /// Reference to `content` published at `uri`
+    pub fn new(uri: String, content: &[u8]) -> Self {
+        ContentRef { uri, hash: content_hash(content) }
+    }
+
+    /// Check the reference against the rules `CreatePoll` enforces
+    pub fn validate(&self) -> Result<(), VotingError> {
+        if self.uri.is_empty() || self.uri.len() > MAX_CONTENT_URI_LEN {
+            return Err(VotingError::InvalidPollParameters);
+        }
+
+        Ok(())
+    }
+
+    /// Whether `content` is the referenced document
+    pub fn matches(&self, content: &[u8]) -> bool {
+        content_hash(content) == self.hash
+    }
+}
+
+/// SHA-256 hash of `content`, as stored in a `ContentRef`
+pub fn content_hash(content: &[u8]) -> [u8; 32] {
+    sha256::Hash::hash(content).to_byte_array()
+}
+
+/// Source of published documents, e.g. a local mirror or cache
+pub trait ContentStore {
+    /// Document published at `uri`, or `None` if the store does not have it
+    fn fetch(&self, uri: &str) -> Option<Vec<u8>>;
+}
+
+/// In-memory `ContentStore` keyed by URI
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    documents: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, uri: String, content: Vec<u8>) {
+        self.documents.insert(uri, content);
+    }
+}
+
+impl ContentStore for MemoryStore {
+    fn fetch(&self, uri: &str) -> Option<Vec<u8>> {
+        self.documents.get(uri).cloned()
+    }
+}
+
+/// `ContentStore` reading documents from a local directory: `scheme://path` is read from
+/// `<root>/path`, so `ipfs://<cid>` lives in `<root>/<cid>`
+#[cfg(not(target_os = "solana"))]
+#[derive(Debug, Clone)]
+pub struct DirectoryStore {
+    root: PathBuf,
+}
+
+#[cfg(not(target_os = "solana"))]
+impl DirectoryStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        DirectoryStore { root: root.into() }
+    }
+
+    /// File holding the document at `uri`; URIs that would leave the root have none
+    pub fn path(&self, uri: &str) -> Option<PathBuf> {
+        let relative = Path::new(uri.split_once("://").map_or(uri, |(_, path)| path));
+        let stays_inside = relative.components().all(|component| matches!(component, Component::Normal(_)));
+        if relative.as_os_str().is_empty() || !stays_inside {
+            return None;
+        }
+
+        Some(self.root.join(relative))
+    }
+}
+
+#[cfg(not(target_os = "solana"))]
+impl ContentStore for DirectoryStore {
+    fn fetch(&self, uri: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.path(uri)?).ok()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentError {
+    /// The store does not have the document at this URI
+    NotFound(String),
+    /// The document at `uri` does not hash to the referenced hash
+    HashMismatch { uri: String, expected: [u8; 32], actual: [u8; 32] },
+}
+
+/// Fetch the document `content` references from `store` and check its hash
+pub fn fetch_verified(store: &impl ContentStore, content: &ContentRef) -> Result<Vec<u8>, ContentError> {
+    let document = store.fetch(&content.uri).ok_or_else(|| ContentError::NotFound(content.uri.clone()))?;
+
+    let actual = content_hash(&document);
+    if actual != content.hash {
+        return Err(ContentError::HashMismatch { uri: content.uri.clone(), expected: content.hash, actual });
+    }
+
+    Ok(document)
+}
+
+/// Verified documents of a poll
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PollContent {
+    /// Document referenced by `Poll::metadata`
+    pub metadata: Option<Vec<u8>>,
+    /// Document of each option, in option order (`None` for options without one)
+    pub options: Vec<Option<Vec<u8>>>,
+}
+
+/// Fetch and verify every document `poll` references; fails on the first one that is missing
+/// or does not match its hash
+pub fn fetch_poll_content(store: &impl ContentStore, poll: &Poll) -> Result<PollContent, ContentError> {
+    let metadata = poll.metadata.as_ref().map(|content| fetch_verified(store, content)).transpose()?;
+
+    let options = if poll.option_metadata.is_empty() {
+        vec![None; poll.options.len()]
+    } else {
+        poll.option_metadata
+            .iter()
+            .map(|content| content.as_ref().map(|content| fetch_verified(store, content)).transpose())
+            .collect::<Result<_, _>>()?
+    };
+
+    Ok(PollContent { metadata, options })
+}
@@ -0,0 +1,170 @@
+use arch_program::pubkey::Pubkey;
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::hex;
+
+// Typed events for every state transition.
+// Each handler logs one line `voting-event:v<version>:<hex of the Borsh-encoded event>`
+// after its state is written, so indexers can follow polls from the program logs alone.
+
+/// Version of the event encoding; bumped whenever `VotingEvent` changes shape
+pub const EVENT_VERSION: u8 = 1;
+
+/// Marker every event log line starts with (after any runtime log prefix)
+pub const EVENT_LOG_PREFIX: &str = "voting-event:";
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum VotingEvent {
+    PollCreated {
+        poll_id: u64,
+        creator: Pubkey,
+        options: u8,
+        start_time: u64,
+        end_time: u64,
+    },
+    PollCancelled {
+        poll_id: u64,
+    },
+    VoteCast {
+        poll_id: u64,
+        voter: Pubkey,
+        option_index: u8,
+        weight: u64,
+        raw_weight: u64,
+        /// Principals whose weight the vote carries by proxy
+        proxies: Vec<Pubkey>,
+        is_revote: bool,
+    },
+    VoteChanged {
+        poll_id: u64,
+        voter: Pubkey,
+        old_option_index: u8,
+        new_option_index: u8,
+        weight: u64,
+    },
+    PollClosed {
+        poll_id: u64,
+        closed_by: Pubkey,
+        is_finalized: bool,
+    },
+    ResultsDecrypted {
+        poll_id: u64,
+    },
+    DelegationCreated {
+        delegation_id: u64,
+        delegator: Pubkey,
+        delegates: Vec<Pubkey>,
+        poll_id: Option<u64>,
+        topic: Option<String>,
+    },
+    DelegationRevoked {
+        delegation_id: u64,
+        /// Delegate whose share was revoked (None when the whole delegation was revoked)
+        delegate: Option<Pubkey>,
+    },
+    BalanceUpdated {
+        owner: Pubkey,
+        token: Pubkey,
+        amount: u64,
+        oracle: Pubkey,
+    },
+    UtxoWeightProved {
+        poll_id: u64,
+        owner: Pubkey,
+        total_sats: u64,
+    },
+    AssetHoldingsProved {
+        poll_id: u64,
+        owner: Pubkey,
+        amount: u64,
+    },
+    EscrowLocked {
+        owner: Pubkey,
+        token: Pubkey,
+        amount: u64,
+        unlock_height: u64,
+    },
+    EscrowExtended {
+        owner: Pubkey,
+        unlock_height: u64,
+    },
+    EscrowWithdrawn {
+        owner: Pubkey,
+        token: Pubkey,
+        amount: u64,
+    },
+    DelegateRegistered {
+        delegate: Pubkey,
+        accepting_delegations: bool,
+    },
+    RecountCompleted {
+        poll_id: u64,
+        discrepancies: Vec<u8>,
+        applied: bool,
+    },
+    PollExtended {
+        poll_id: u64,
+        end_time: u64,
+        extension_count: u8,
+    },
+    PollUpdated {
+        poll_id: u64,
+        options: u8,
+        start_time: u64,
+        end_time: u64,
+    },
+    /// A delegate's vote gave up the slice of `principal`'s weight it carried, because the
+    /// principal voted directly or a delegation taking precedence carries it now
+    ProxySliceReleased {
+        poll_id: u64,
+        carrier: Pubkey,
+        principal: Pubkey,
+        weight: u64,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventParseError {
+    /// Event was encoded with another version of the encoding
+    UnsupportedVersion,
+    /// Payload is not valid hex
+    InvalidHex,
+    /// Payload does not decode into a `VotingEvent`
+    InvalidEvent,
+}
+
+/// Log `event` as a single machine-parseable line
+pub fn emit(event: &VotingEvent) {
+    msg!("{}", encode_log(event));
+}
+
+/// Log line for `event`
+pub fn encode_log(event: &VotingEvent) -> String {
+    let payload = borsh::to_vec(&event).expect("events always serialize");
+
+    format!("{}v{}:{}", EVENT_LOG_PREFIX, EVENT_VERSION, hex::encode(&payload))
+}
+
+/// Parse a single program log line; lines that carry no event yield `None`
+pub fn parse_log(line: &str) -> Option<Result<VotingEvent, EventParseError>> {
+    let start = line.find(EVENT_LOG_PREFIX)?;
+    let encoded = &line[start + EVENT_LOG_PREFIX.len()..];
+
+    Some(parse_encoded(encoded.trim_end()))
+}
+
+/// Events in a transaction's program logs, in the order they were emitted
+pub fn parse_logs<'a>(logs: impl IntoIterator<Item = &'a str>) -> Result<Vec<VotingEvent>, EventParseError> {
+    logs.into_iter().filter_map(parse_log).collect()
+}
+
+fn parse_encoded(encoded: &str) -> Result<VotingEvent, EventParseError> {
+    let (version, payload) = encoded.split_once(':').ok_or(EventParseError::InvalidEvent)?;
+
+    if version != format!("v{}", EVENT_VERSION) {
+        return Err(EventParseError::UnsupportedVersion);
+    }
+
+    let bytes = hex::decode(payload).ok_or(EventParseError::InvalidHex)?;
+    VotingEvent::try_from_slice(&bytes).map_err(|_| EventParseError::InvalidEvent)
+}
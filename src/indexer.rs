@@ -0,0 +1,512 @@
+use std::collections::{BTreeMap, HashMap};
+
+use arch_program::pubkey::Pubkey;
+use borsh::BorshDeserialize;
+
+use crate::events::{self, EventParseError, VotingEvent};
+use crate::results::{self, PollResults};
+use crate::{hex, pda, AccountType, Delegation, Poll, Vote, VoteCount, ACCOUNT_VERSION};
+
+// Off-chain indexer.
+// Rebuilds polls, votes, delegations and tallies from the program's event logs: every event
+// names the accounts it changed, which are re-read from an `AccountSource` (an RPC node, or a
+// snapshot file loaded into a `MockRpc`) and kept as the crate's own account types.
+
+/// Read access to program accounts, e.g. an RPC node
+pub trait AccountSource {
+    /// Data of the account at `address`, or `None` if it does not exist
+    fn account_data(&self, address: &Pubkey) -> Option<Vec<u8>>;
+}
+
+/// In-memory `AccountSource` for tests and snapshot files
+#[derive(Debug, Clone, Default)]
+pub struct MockRpc {
+    accounts: HashMap<Pubkey, Vec<u8>>,
+}
+
+impl MockRpc {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store (or replace) the data of the account at `address`
+    pub fn set_account(&mut self, address: Pubkey, data: Vec<u8>) {
+        self.accounts.insert(address, data);
+    }
+
+    /// Every stored account, in no particular order
+    pub fn accounts(&self) -> impl Iterator<Item = (&Pubkey, &[u8])> {
+        self.accounts.iter().map(|(address, data)| (address, data.as_slice()))
+    }
+}
+
+impl AccountSource for MockRpc {
+    fn account_data(&self, address: &Pubkey) -> Option<Vec<u8>> {
+        self.accounts.get(address).cloned()
+    }
+}
+
+/// Accounts and program logs read from a snapshot file.
+///
+/// Each non-empty line is either `account <address hex> <data hex>` or `log <program log line>`;
+/// lines starting with `#` are comments.
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub accounts: MockRpc,
+    pub logs: Vec<String>,
+}
+
+impl Snapshot {
+    pub fn parse(text: &str) -> Result<Self, IndexerError> {
+        let mut snapshot = Snapshot::default();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match line.split_once(' ') {
+                Some(("account", rest)) => {
+                    let (address, data) = rest
+                        .trim()
+                        .split_once(' ')
+                        .ok_or(IndexerError::InvalidSnapshotLine(line_number))?;
+                    let address = parse_pubkey(address).ok_or(IndexerError::InvalidSnapshotLine(line_number))?;
+                    let data = hex::decode(data.trim()).ok_or(IndexerError::InvalidSnapshotLine(line_number))?;
+                    snapshot.accounts.set_account(address, data);
+                }
+                Some(("log", log)) => snapshot.logs.push(log.to_string()),
+                _ => return Err(IndexerError::InvalidSnapshotLine(line_number)),
+            }
+        }
+
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexerError {
+    /// A log line carried an event that could not be parsed
+    Event(EventParseError),
+    /// Account data carried a known discriminator but did not decode
+    InvalidAccount(Pubkey),
+    /// Snapshot line (1-based) is malformed
+    InvalidSnapshotLine(usize),
+}
+
+impl From<EventParseError> for IndexerError {
+    fn from(error: EventParseError) -> Self {
+        IndexerError::Event(error)
+    }
+}
+
+/// Polls, votes, delegations and tallies of one deployment of the program
+#[derive(Debug, Clone)]
+pub struct Indexer {
+    program_id: Pubkey,
+    polls: BTreeMap<u64, Poll>,
+    tallies: BTreeMap<u64, VoteCount>,
+    votes: BTreeMap<(u64, Pubkey), Vote>,
+    delegations: BTreeMap<u64, Delegation>,
+}
+
+impl Indexer {
+    pub fn new(program_id: Pubkey) -> Self {
+        Indexer {
+            program_id,
+            polls: BTreeMap::new(),
+            tallies: BTreeMap::new(),
+            votes: BTreeMap::new(),
+            delegations: BTreeMap::new(),
+        }
+    }
+
+    /// Apply the events in `logs`, refreshing the accounts they changed from `source`.
+    /// Returns the number of events applied.
+    pub fn ingest_logs<'a>(
+        &mut self,
+        logs: impl IntoIterator<Item = &'a str>,
+        source: &impl AccountSource,
+    ) -> Result<usize, IndexerError> {
+        let events = events::parse_logs(logs)?;
+
+        for event in &events {
+            self.apply_event(event, source)?;
+        }
+
+        Ok(events.len())
+    }
+
+    /// Refresh the accounts `event` changed from `source`
+    pub fn apply_event(&mut self, event: &VotingEvent, source: &impl AccountSource) -> Result<(), IndexerError> {
+        for address in self.changed_accounts(event) {
+            // Accounts the source no longer has keep their last indexed state
+            if let Some(data) = source.account_data(&address) {
+                self.ingest_account(&address, &data)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Index every account in `snapshot`, then apply its logs
+    pub fn ingest_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), IndexerError> {
+        for (address, data) in snapshot.accounts.accounts() {
+            self.ingest_account(address, data)?;
+        }
+
+        self.ingest_logs(snapshot.logs.iter().map(String::as_str), &snapshot.accounts)?;
+
+        Ok(())
+    }
+
+    /// Index a single account. Accounts the indexer does not track (and uninitialized or
+    /// foreign data) are ignored; returns whether the account was indexed.
+    pub fn ingest_account(&mut self, address: &Pubkey, data: &[u8]) -> Result<bool, IndexerError> {
+        if data.len() < 2 || data[1] != ACCOUNT_VERSION {
+            return Ok(false);
+        }
+
+        let account_type = data[0];
+        if account_type == AccountType::Poll as u8 {
+            let poll: Poll = decode_account(address, data)?;
+            self.polls.insert(poll.id, poll);
+        } else if account_type == AccountType::VoteCount as u8 {
+            let vote_count: VoteCount = decode_account(address, data)?;
+            self.tallies.insert(vote_count.poll_id, vote_count);
+        } else if account_type == AccountType::Vote as u8 {
+            let vote: Vote = decode_account(address, data)?;
+            self.votes.insert((vote.poll_id, vote.voter), vote);
+        } else if account_type == AccountType::Delegation as u8 {
+            let delegation: Delegation = decode_account(address, data)?;
+            self.delegations.insert(delegation.id, delegation);
+        } else {
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Addresses of the indexed accounts `event` changed
+    pub fn changed_accounts(&self, event: &VotingEvent) -> Vec<Pubkey> {
+        let program_id = &self.program_id;
+        let poll_accounts = |poll_id: u64| {
+            vec![
+                pda::find_poll_address(program_id, poll_id).0,
+                pda::find_vote_count_address(program_id, poll_id).0,
+            ]
+        };
+
+        match event {
+            VotingEvent::PollCreated { poll_id, .. }
+            | VotingEvent::PollCancelled { poll_id }
+            | VotingEvent::PollClosed { poll_id, .. }
+            | VotingEvent::ResultsDecrypted { poll_id }
+            | VotingEvent::RecountCompleted { poll_id, .. }
+            | VotingEvent::PollExtended { poll_id, .. }
+            | VotingEvent::PollUpdated { poll_id, .. } => poll_accounts(*poll_id),
+            VotingEvent::VoteCast { poll_id, voter, .. } | VotingEvent::VoteChanged { poll_id, voter, .. } => vec![
+                pda::find_vote_count_address(program_id, *poll_id).0,
+                pda::find_vote_address(program_id, *poll_id, voter).0,
+            ],
+            VotingEvent::ProxySliceReleased { poll_id, carrier, .. } => vec![
+                pda::find_vote_count_address(program_id, *poll_id).0,
+                pda::find_vote_address(program_id, *poll_id, carrier).0,
+            ],
+            VotingEvent::DelegationCreated { delegation_id, .. }
+            | VotingEvent::DelegationRevoked { delegation_id, .. } => {
+                vec![pda::find_delegation_address(program_id, *delegation_id).0]
+            }
+            // Balances, proofs, escrows and delegate profiles are not indexed
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn poll(&self, poll_id: u64) -> Option<&Poll> {
+        self.polls.get(&poll_id)
+    }
+
+    pub fn tally(&self, poll_id: u64) -> Option<&VoteCount> {
+        self.tallies.get(&poll_id)
+    }
+
+    pub fn vote(&self, poll_id: u64, voter: &Pubkey) -> Option<&Vote> {
+        self.votes.get(&(poll_id, *voter))
+    }
+
+    pub fn delegation(&self, delegation_id: u64) -> Option<&Delegation> {
+        self.delegations.get(&delegation_id)
+    }
+
+    pub fn polls(&self) -> impl Iterator<Item = &Poll> {
+        self.polls.values()
+    }
+
+    pub fn tallies(&self) -> impl Iterator<Item = &VoteCount> {
+        self.tallies.values()
+    }
+
+    pub fn votes(&self) -> impl Iterator<Item = &Vote> {
+        self.votes.values()
+    }
+
+    pub fn delegations(&self) -> impl Iterator<Item = &Delegation> {
+        self.delegations.values()
+    }
+
+    /// Polls open for voting at block height `current_time`
+    pub fn active_polls(&self, current_time: u64) -> Vec<&Poll> {
+        self.polls
+            .values()
+            .filter(|poll| poll.is_active && poll.start_time <= current_time && current_time <= poll.end_time)
+            .collect()
+    }
+
+    /// Votes cast in `poll_id`, ordered by voter
+    pub fn poll_votes(&self, poll_id: u64) -> impl Iterator<Item = &Vote> {
+        self.votes
+            .range((poll_id, Pubkey::default())..)
+            .take_while(move |((vote_poll_id, _), _)| *vote_poll_id == poll_id)
+            .map(|(_, vote)| vote)
+    }
+
+    /// Votes `voter` cast, ordered by poll
+    pub fn votes_by_voter(&self, voter: &Pubkey) -> Vec<&Vote> {
+        self.votes.values().filter(|vote| vote.voter == *voter).collect()
+    }
+
+    /// Active delegations with a share delegated to `delegate`
+    pub fn delegations_to(&self, delegate: &Pubkey) -> Vec<&Delegation> {
+        self.delegations
+            .values()
+            .filter(|delegation| {
+                delegation.is_active && delegation.shares.iter().any(|share| share.delegate == *delegate)
+            })
+            .collect()
+    }
+
+    /// Results of `poll_id` as of block height `current_time`, as `GetResults` would return them
+    pub fn results(&self, poll_id: u64, current_time: u64) -> Option<PollResults> {
+        let poll = self.polls.get(&poll_id)?;
+        let vote_count = self.tallies.get(&poll_id)?;
+
+        Some(results::poll_results(poll, vote_count, current_time))
+    }
+}
+
+/// Public key encoded as 64 hex characters
+pub fn parse_pubkey(hex: &str) -> Option<Pubkey> {
+    let bytes = hex::decode(hex)?;
+    if bytes.len() != 32 {
+        return None;
+    }
+
+    Pubkey::try_from_slice(&bytes).ok()
+}
+
+// Accounts may be larger than the struct they hold, so trailing bytes are allowed
+fn decode_account<T: BorshDeserialize>(address: &Pubkey, data: &[u8]) -> Result<T, IndexerError> {
+    T::deserialize(&mut &data[..]).map_err(|_| IndexerError::InvalidAccount(*address))
+}
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite {
+    use rusqlite::{params, Connection};
+
+    use super::Indexer;
+    use crate::hex;
+
+    // SQLite view of an `Indexer`, for ad-hoc queries.
+    // Each table mirrors the fields of the corresponding account type; pubkeys are stored as hex
+    // and per-option counts as one row per option.
+
+    const SCHEMA: &str = "
+        CREATE TABLE IF NOT EXISTS polls (
+            id INTEGER PRIMARY KEY,
+            creator TEXT NOT NULL,
+            title TEXT NOT NULL,
+            description TEXT NOT NULL,
+            start_time INTEGER NOT NULL,
+            end_time INTEGER NOT NULL,
+            is_active INTEGER NOT NULL,
+            is_weighted INTEGER NOT NULL,
+            is_encrypted INTEGER NOT NULL,
+            allow_delegation INTEGER NOT NULL,
+            topic TEXT,
+            metadata_uri TEXT,
+            metadata_hash TEXT
+        );
+        CREATE TABLE IF NOT EXISTS poll_options (
+            poll_id INTEGER NOT NULL,
+            option_index INTEGER NOT NULL,
+            label TEXT NOT NULL,
+            count INTEGER NOT NULL,
+            raw_count INTEGER NOT NULL,
+            proxy_count INTEGER NOT NULL,
+            metadata_uri TEXT,
+            metadata_hash TEXT,
+            PRIMARY KEY (poll_id, option_index)
+        );
+        CREATE TABLE IF NOT EXISTS tallies (
+            poll_id INTEGER PRIMARY KEY,
+            total_voters INTEGER NOT NULL,
+            last_updated INTEGER NOT NULL,
+            is_finalized INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS votes (
+            poll_id INTEGER NOT NULL,
+            voter TEXT NOT NULL,
+            option_index INTEGER NOT NULL,
+            timestamp INTEGER NOT NULL,
+            weight INTEGER NOT NULL,
+            raw_weight INTEGER NOT NULL,
+            is_encrypted INTEGER NOT NULL,
+            PRIMARY KEY (poll_id, voter)
+        );
+        CREATE TABLE IF NOT EXISTS vote_proxies (
+            poll_id INTEGER NOT NULL,
+            voter TEXT NOT NULL,
+            principal TEXT NOT NULL,
+            depth INTEGER NOT NULL,
+            share_bps INTEGER NOT NULL,
+            weight INTEGER NOT NULL,
+            raw_weight INTEGER NOT NULL,
+            PRIMARY KEY (poll_id, voter, principal)
+        );
+        CREATE TABLE IF NOT EXISTS delegations (
+            id INTEGER PRIMARY KEY,
+            delegator TEXT NOT NULL,
+            poll_id INTEGER,
+            topic TEXT,
+            expiration INTEGER,
+            is_active INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS delegation_shares (
+            delegation_id INTEGER NOT NULL,
+            delegate TEXT NOT NULL,
+            bps INTEGER NOT NULL,
+            PRIMARY KEY (delegation_id, delegate)
+        );
+    ";
+
+    /// Replace the contents of the view in `connection` with the state of `indexer`
+    pub fn export(indexer: &Indexer, connection: &mut Connection) -> rusqlite::Result<()> {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(SCHEMA)?;
+        transaction.execute_batch(
+            "DELETE FROM polls; DELETE FROM poll_options; DELETE FROM tallies; DELETE FROM votes;
+             DELETE FROM vote_proxies; DELETE FROM delegations; DELETE FROM delegation_shares;",
+        )?;
+
+        // SQLite integers are signed, so u64 values are stored by bit pattern
+        for poll in indexer.polls() {
+            transaction.execute(
+                "INSERT INTO polls VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    poll.id as i64,
+                    hex::encode(&poll.creator.serialize()),
+                    poll.title,
+                    poll.description,
+                    poll.start_time as i64,
+                    poll.end_time as i64,
+                    poll.is_active,
+                    poll.is_weighted,
+                    poll.is_encrypted,
+                    poll.allow_delegation,
+                    poll.topic,
+                    poll.metadata.as_ref().map(|metadata| &metadata.uri),
+                    poll.metadata.as_ref().map(|metadata| hex::encode(&metadata.hash)),
+                ],
+            )?;
+
+            let vote_count = indexer.tally(poll.id);
+            let count_at = |counts: Option<&Vec<u64>>, option: usize| {
+                counts.and_then(|counts| counts.get(option)).copied().unwrap_or(0) as i64
+            };
+            for (option, label) in poll.options.iter().enumerate() {
+                let document = poll.option_metadata.get(option).and_then(Option::as_ref);
+                transaction.execute(
+                    "INSERT INTO poll_options VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    params![
+                        poll.id as i64,
+                        option as i64,
+                        label,
+                        count_at(vote_count.map(|vote_count| &vote_count.counts), option),
+                        count_at(vote_count.map(|vote_count| &vote_count.raw_counts), option),
+                        count_at(vote_count.map(|vote_count| &vote_count.proxy_counts), option),
+                        document.map(|document| &document.uri),
+                        document.map(|document| hex::encode(&document.hash)),
+                    ],
+                )?;
+            }
+        }
+
+        for vote_count in indexer.tallies() {
+            transaction.execute(
+                "INSERT INTO tallies VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    vote_count.poll_id as i64,
+                    vote_count.total_voters as i64,
+                    vote_count.last_updated as i64,
+                    vote_count.is_finalized,
+                ],
+            )?;
+        }
+
+        for vote in indexer.votes() {
+            let voter = hex::encode(&vote.voter.serialize());
+            transaction.execute(
+                "INSERT INTO votes VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    vote.poll_id as i64,
+                    voter,
+                    vote.option_index,
+                    vote.timestamp as i64,
+                    vote.weight as i64,
+                    vote.raw_weight as i64,
+                    vote.encrypted_data.is_some(),
+                ],
+            )?;
+
+            for proxy in &vote.proxies {
+                transaction.execute(
+                    "INSERT INTO vote_proxies VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        vote.poll_id as i64,
+                        voter,
+                        hex::encode(&proxy.principal.serialize()),
+                        proxy.depth,
+                        proxy.share_bps,
+                        proxy.weight as i64,
+                        proxy.raw_weight as i64,
+                    ],
+                )?;
+            }
+        }
+
+        for delegation in indexer.delegations() {
+            transaction.execute(
+                "INSERT INTO delegations VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    delegation.id as i64,
+                    hex::encode(&delegation.delegator.serialize()),
+                    delegation.poll_id.map(|poll_id| poll_id as i64),
+                    delegation.topic,
+                    delegation.expiration.map(|expiration| expiration as i64),
+                    delegation.is_active,
+                ],
+            )?;
+
+            for share in &delegation.shares {
+                transaction.execute(
+                    "INSERT INTO delegation_shares VALUES (?1, ?2, ?3)",
+                    params![delegation.id as i64, hex::encode(&share.delegate.serialize()), share.bps],
+                )?;
+            }
+        }
+
+        transaction.commit()
+    }
+}
@@ -1,24 +1,129 @@
 use arch_program::{
     account::AccountInfo,
-    bitcoin::{self, absolute::LockTime, transaction::Version, Transaction},
-    entrypoint,
-    helper::add_state_transition,
+    bitcoin::{self, absolute::LockTime, hashes::Hash, transaction::Version, Transaction},
     input_to_sign::InputToSign,
-    msg,
-    program::{
-        get_account_script_pubkey, get_bitcoin_block_height, next_account_info,
-        set_transaction_to_sign,
-    },
+    program::next_account_info,
     program_error::ProgramError,
     pubkey::Pubkey,
-    transaction_to_sign::TransactionToSign,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
 
+/// Program log line, routed through `runtime` so a simulator can capture it
+macro_rules! msg {
+    ($($arg:tt)*) => {
+        $crate::runtime::log(&format!($($arg)*))
+    };
+}
+
+pub mod assets;
+pub mod client;
+pub mod content;
+pub mod events;
+pub mod hex;
+pub mod indexer;
+pub mod pda;
+pub mod results;
+mod runtime;
+#[cfg(not(target_os = "solana"))]
+pub mod simulator;
+pub mod weight;
+#[cfg(test)]
+mod tests;
+
+use assets::{AssetHolding, AttestedHoldings, BitcoinAsset};
+use content::ContentRef;
+use events::VotingEvent;
+use results::PollOutcome;
+use weight::{BonusSchedule, WeightTransform, BPS_DENOMINATOR};
+
+/// Maximum escrow lock duration in blocks (~4 years); a lock this long carries full weight
+pub const MAX_ESCROW_LOCK_BLOCKS: u64 = 210_240;
+
+/// Maximum age in blocks (~1 day) of the attested balance that tokens are escrowed from
+pub const MAX_ESCROW_BALANCE_AGE: u64 = 144;
+
+/// Upper bound on `max_delegation_depth`, keeping delegation chains cheap to verify
+pub const MAX_DELEGATION_DEPTH: u8 = 8;
+
+/// Maximum length in bytes of a poll or delegation topic
+pub const MAX_TOPIC_LEN: usize = 32;
+
+/// Maximum number of delegates a single delegation can be split across
+pub const MAX_DELEGATION_SHARES: usize = 10;
+
+/// Upper bound on `max_extensions`
+pub const MAX_POLL_EXTENSIONS: u8 = 10;
+
+/// Maximum length in bytes of the URI of a poll or option document
+pub const MAX_CONTENT_URI_LEN: usize = 200;
+
+/// Number of voters listed on each voter registry page
+pub const VOTERS_PER_PAGE: usize = 256;
+
+/// Layout version of every stored account; bumped whenever a stored struct changes shape
+pub const ACCOUNT_VERSION: u8 = 7;
+
 // Data Structures
 
+/// Discriminator leading the data of every account owned by the program
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountType {
+    /// Zeroed account that has not been written yet
+    Uninitialized,
+    Poll,
+    Vote,
+    VoteCount,
+    VoterRegistry,
+    Delegation,
+    DelegateProfile,
+    TokenBalance,
+    EscrowPosition,
+    UtxoWeight,
+    AssetClaim,
+    AssetHoldings,
+    VoterRecord,
+    RecountTally,
+    UtxoClaim,
+}
+
+/// Struct stored in a program account, starting with its `AccountType` and `ACCOUNT_VERSION`
+pub trait ProgramAccount: BorshSerialize + BorshDeserialize {
+    const ACCOUNT_TYPE: AccountType;
+}
+
+macro_rules! impl_program_account {
+    ($($account:ident),*) => {
+        $(
+            impl ProgramAccount for $account {
+                const ACCOUNT_TYPE: AccountType = AccountType::$account;
+            }
+        )*
+    };
+}
+
+impl_program_account!(
+    Poll,
+    Vote,
+    VoteCount,
+    VoterRegistry,
+    VoterRecord,
+    RecountTally,
+    Delegation,
+    DelegateProfile,
+    TokenBalance,
+    EscrowPosition,
+    UtxoWeight,
+    AssetClaim,
+    UtxoClaim,
+    AssetHoldings
+);
+
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct Poll {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
     /// Unique identifier for the poll
     pub id: u64,
     /// Creator of the poll
@@ -51,10 +156,74 @@ pub struct Poll {
     pub weight_token: Option<Pubkey>,
     /// Early voter bonus multiplier (0 means no bonus)
     pub early_voter_bonus: u8,
+    /// How the early voter bonus decays over the poll
+    pub bonus_schedule: BonusSchedule,
+    /// Oracle that must attest token balances used for weighting (weighted polls only)
+    pub balance_oracle: Option<Pubkey>,
+    /// Maximum age in blocks of an attested token balance before it is considered stale
+    pub max_balance_age: u64,
+    /// Where vote weight comes from (weighted polls only)
+    pub weight_source: WeightSource,
+    /// Caps and dampening curve applied to each voter's weight (weighted polls only)
+    pub weight_transform: WeightTransform,
+    /// Bitcoin-native asset voters must hold to be eligible (None means anyone may vote)
+    pub eligibility_asset: Option<BitcoinAsset>,
+    /// Maximum number of delegation hops between a principal and the voter carrying their weight
+    pub max_delegation_depth: u8,
+    /// Topic or category of the poll, used to resolve topic-scoped delegations
+    pub topic: Option<String>,
+    /// Whether delegated weight is only carried through delegations to registered delegates
+    pub require_registered_delegates: bool,
+    /// How many times the creator may push back the end time with `ExtendPoll` (0 disables extensions)
+    pub max_extensions: u8,
+    /// Number of times the end time has been pushed back
+    pub extension_count: u8,
+    /// Raw weight that counts as sufficient turnout; once it is cast the poll can no longer be extended
+    pub quorum: Option<u64>,
+    /// Whether anyone may close the poll before its end time once the outcome is decided
+    pub allow_early_close: bool,
+    /// Off-chain document with the full proposal
+    pub metadata: Option<ContentRef>,
+    /// Off-chain document of each option; empty, or one entry per option
+    pub option_metadata: Vec<Option<ContentRef>>,
+}
+
+impl Poll {
+    /// Whether `CreatePoll` takes the token account (polls weighted by token balance or escrow)
+    pub fn needs_token_account(&self) -> bool {
+        self.is_weighted && matches!(self.weight_source, WeightSource::TokenBalance | WeightSource::VoteEscrow)
+    }
+
+    /// Number of weight accounts each participant passes to `CastVote`: none for unweighted
+    /// polls, the escrow position and the token balance backing it for escrow-weighted polls,
+    /// and a single one otherwise
+    pub fn weight_account_count(&self) -> usize {
+        match (self.is_weighted, self.weight_source) {
+            (false, _) => 0,
+            (true, WeightSource::VoteEscrow) => 2,
+            (true, _) => 1,
+        }
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeightSource {
+    /// Oracle-attested token balance of the poll's `weight_token`
+    TokenBalance,
+    /// Satoshi value of Bitcoin UTXOs proven with `ProveUtxoWeight`
+    BitcoinUtxo,
+    /// Holdings of a Rune or inscription collection proven with `ProveAssetHoldings`
+    BitcoinAsset(BitcoinAsset),
+    /// Time-locked `weight_token` stake, decaying as the unlock height approaches
+    VoteEscrow,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct Vote {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
     /// Poll ID this vote belongs to
     pub poll_id: u64,
     /// Voter's public key
@@ -63,10 +232,14 @@ pub struct Vote {
     pub option_index: u8,
     /// Timestamp of the vote
     pub timestamp: u64,
-    /// Weight of the vote (for weighted voting)
+    /// Weight of the vote (for weighted voting, after caps and dampening), including proxied weight
     pub weight: u64,
+    /// Weight of the vote before caps and dampening, including proxied weight
+    pub raw_weight: u64,
     /// Delegated voter (if applicable)
     pub delegated_to: Option<Pubkey>,
+    /// Weight carried on behalf of principals who delegated to this voter
+    pub proxies: Vec<ProxyWeight>,
     /// Encrypted vote data (if applicable)
     pub encrypted_data: Option<Vec<u8>>,
     /// Zero-knowledge proof (if applicable)
@@ -75,48 +248,193 @@ pub struct Vote {
     pub nonce: Option<Vec<u8>>,
 }
 
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProxyWeight {
+    /// Principal whose weight was carried
+    pub principal: Pubkey,
+    /// Number of delegation hops from the principal to the voter
+    pub depth: u8,
+    /// Share of the principal's weight carried, in basis points (product of the shares along the chain)
+    pub share_bps: u16,
+    /// Weight carried (after caps and dampening)
+    pub weight: u64,
+    /// Weight carried before caps and dampening
+    pub raw_weight: u64,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct VoteCount {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
     /// Poll ID this count belongs to
     pub poll_id: u64,
     /// Counts for each option (index corresponds to option index)
     pub counts: Vec<u64>,
+    /// Counts for each option before weight caps and dampening
+    pub raw_counts: Vec<u64>,
+    /// Portion of each option's count carried by proxy through delegation
+    pub proxy_counts: Vec<u64>,
     /// Total number of voters
     pub total_voters: u64,
     /// Last updated timestamp
     pub last_updated: u64,
+    /// Incremented on every write. Many writes can land in the same block, so a recount compares
+    /// this instead of `last_updated` to tell whether the tally changed while it was running.
+    pub revision: u64,
     /// Whether results are finalized
     pub is_finalized: bool,
 }
 
+/// Scratch tally rebuilt from the poll's vote accounts by `Recount`
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct RecountTally {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
+    /// Poll ID this tally belongs to
+    pub poll_id: u64,
+    /// Recounted counts for each option
+    pub counts: Vec<u64>,
+    /// Recounted counts for each option before weight caps and dampening
+    pub raw_counts: Vec<u64>,
+    /// Recounted portion of each option's count carried by proxy
+    pub proxy_counts: Vec<u64>,
+    /// Registry position of the next voter whose vote is counted
+    pub next_voter: u64,
+    /// `revision` of the vote count when the recount started
+    pub vote_count_revision: u64,
+    /// Whether every voter has been counted
+    pub is_complete: bool,
+    /// Options whose recounted tally differs from the vote count (set once complete)
+    pub discrepancies: Vec<u8>,
+    /// Whether the recounted tally replaced the vote count
+    pub applied: bool,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct VoterRegistry {
-    /// Poll ID this registry belongs to
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
+    /// Poll ID this registry page belongs to
     pub poll_id: u64,
-    /// Bitmap of voters (for efficient lookup)
-    pub voter_bitmap: Vec<u8>,
-    /// List of voter public keys (for anonymous voting verification)
+    /// Index of this page; page `n` lists the voters who voted `n * VOTERS_PER_PAGE` onwards
+    pub page: u32,
+    /// Voters on this page, in the order they first voted
     pub voters: Vec<Pubkey>,
 }
 
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct VoterRecord {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
+    /// Poll ID this record belongs to
+    pub poll_id: u64,
+    /// Voter (or delegating principal) the record tracks
+    pub voter: Pubkey,
+    /// Whether the voter has cast a vote themselves
+    pub has_voted: bool,
+    /// Delegates whose votes currently carry (a share of) this voter's weight
+    pub carried: Vec<CarriedWeight>,
+}
+
+/// A slice of a principal's weight counted in a delegate's vote; each share of a
+/// principal's weight is counted at most once per poll
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CarriedWeight {
+    /// Principal whose weight is carried
+    pub principal: Pubkey,
+    /// Delegate whose vote carries it
+    pub delegate: Pubkey,
+    /// Scope of the principal's delegation that the delegate voted through
+    pub scope: DelegationScope,
+    /// ID of the principal's delegation that the delegate voted through
+    pub delegation_id: u64,
+    /// Intermediate delegates the weight passed through on its way to `delegate`, nearest the principal first
+    pub via: Vec<Pubkey>,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct Delegation {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
     /// Unique identifier for the delegation
     pub id: u64,
     /// Delegator's public key
     pub delegator: Pubkey,
-    /// Delegate's public key
-    pub delegate: Pubkey,
+    /// Delegates and the share of the delegator's weight each receives.
+    /// Shares sum to 100% when created; revoking a share leaves that part undelegated.
+    pub shares: Vec<DelegationShare>,
     /// Poll ID this delegation applies to (None means all polls)
     pub poll_id: Option<u64>,
+    /// Topic this delegation applies to (None means all topics; never set together with `poll_id`)
+    pub topic: Option<String>,
     /// Expiration time (None means no expiration)
     pub expiration: Option<u64>,
     /// Whether the delegation is active
     pub is_active: bool,
+    /// Whether every delegate was validated against the delegate registry when the delegation was created
+    pub registered: bool,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DelegationShare {
+    /// Delegate's public key
+    pub delegate: Pubkey,
+    /// Share of the delegator's weight, in basis points
+    pub bps: u16,
+}
+
+/// How specific a delegation is; when a principal has several delegations that apply to a
+/// poll, the most specific one wins (poll-specific > topic > global)
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DelegationScope {
+    /// Applies to every poll
+    Global,
+    /// Applies to polls with a matching topic
+    Topic,
+    /// Applies to a single poll
+    Poll,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct DelegateProfile {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
+    /// Delegate's public key
+    pub delegate: Pubkey,
+    /// URI of the delegate's published statement
+    pub statement_uri: String,
+    /// SHA-256 hash of the statement document
+    pub statement_hash: [u8; 32],
+    /// Short description of how the delegate intends to vote
+    pub voting_policy: String,
+    /// Whether the delegate accepts new delegations
+    pub accepting_delegations: bool,
+    /// Maximum number of delegations the delegate accepts (None means no limit)
+    pub max_delegators: Option<u32>,
+    /// Number of active registered delegations to this delegate
+    pub delegator_count: u32,
+    /// Last updated timestamp
+    pub last_updated: u64,
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub struct TokenBalance {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
     /// Owner's public key
     pub owner: Pubkey,
     /// Token address
@@ -125,6 +443,113 @@ pub struct TokenBalance {
     pub amount: u64,
     /// Last updated timestamp
     pub last_updated: u64,
+    /// Oracle that attested this balance
+    pub attested_by: Pubkey,
+    /// Portion of the balance locked in escrow (not counted as plain token weight)
+    pub locked_amount: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct EscrowPosition {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
+    /// Owner's public key
+    pub owner: Pubkey,
+    /// Token address
+    pub token: Pubkey,
+    /// Amount locked
+    pub amount: u64,
+    /// Block height at which the tokens can be withdrawn
+    pub unlock_height: u64,
+    /// Oracle that attested the locked balance
+    pub attested_by: Pubkey,
+    /// Last updated timestamp
+    pub last_updated: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UtxoOutpoint {
+    /// Transaction ID of the output
+    pub txid: [u8; 32],
+    /// Output index within the transaction
+    pub vout: u32,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct UtxoWeight {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
+    /// Poll ID this weight applies to
+    pub poll_id: u64,
+    /// Owner of the UTXOs
+    pub owner: Pubkey,
+    /// Outpoints proven by the owner
+    pub outpoints: Vec<UtxoOutpoint>,
+    /// Outputs of the proof state transitions paying the proven value back to the owner; they
+    /// carry weight that is already counted, so they cannot be proven again
+    pub refunds: Vec<UtxoOutpoint>,
+    /// Total value of the proven outpoints in satoshis
+    pub total_sats: u64,
+    /// Last updated timestamp
+    pub last_updated: u64,
+}
+
+/// Marks an outpoint as counted towards a voter's weight in a poll. One account per outpoint, at
+/// `pda::find_utxo_claim_address`, so a claim is found by address instead of searched for.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct UtxoClaim {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
+    /// Poll ID the outpoint counts towards
+    pub poll_id: u64,
+    /// Outpoint counted
+    pub outpoint: UtxoOutpoint,
+    /// Voter whose weight the outpoint counts towards
+    pub owner: Pubkey,
+}
+
+/// Marks an outpoint as counted towards a voter's holdings of a Bitcoin-native asset in a poll.
+/// One account per asset and outpoint, at `pda::find_asset_claim_address`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct AssetClaim {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
+    /// Poll ID the outpoint counts towards
+    pub poll_id: u64,
+    /// Asset the outpoint holds
+    pub asset: BitcoinAsset,
+    /// Outpoint counted
+    pub outpoint: UtxoOutpoint,
+    /// Voter whose holdings the outpoint counts towards
+    pub owner: Pubkey,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct AssetHoldings {
+    /// Account type discriminator
+    pub account_type: AccountType,
+    /// Account layout version
+    pub version: u8,
+    /// Poll ID these holdings apply to
+    pub poll_id: u64,
+    /// Owner of the outpoints
+    pub owner: Pubkey,
+    /// Asset held
+    pub asset: BitcoinAsset,
+    /// Outpoints holding the asset
+    pub outpoints: Vec<UtxoOutpoint>,
+    /// Total amount of the asset held across the outpoints
+    pub amount: u64,
+    /// Last updated timestamp
+    pub last_updated: u64,
 }
 
 // Instructions
@@ -132,12 +557,14 @@ pub struct TokenBalance {
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum PollInstruction {
     /// Create a new poll
+    /// The poll ID is `pda::poll_id(creator, id_nonce)`; the poll, vote count and voter registry
+    /// accounts must sit at the addresses derived from it (see `pda`)
     /// Accounts:
     /// 0. `[signer, writable]` Poll creator account
     /// 1. `[writable]` New poll account
     /// 2. `[writable]` Vote count account
-    /// 3. `[writable]` Voter registry account
-    /// 4. `[]` Token account (optional, only if weighted voting)
+    /// 3. `[writable]` First voter registry page
+    /// 4. `[]` Token account (optional, only if weighted by token balance)
     CreatePoll {
         title: String,
         description: String,
@@ -151,6 +578,21 @@ pub enum PollInstruction {
         is_encrypted: bool,
         weight_token: Option<Pubkey>,
         early_voter_bonus: u8,
+        bonus_schedule: BonusSchedule,
+        balance_oracle: Option<Pubkey>,
+        max_balance_age: u64,
+        weight_source: WeightSource,
+        weight_transform: WeightTransform,
+        eligibility_asset: Option<BitcoinAsset>,
+        max_delegation_depth: u8,
+        topic: Option<String>,
+        require_registered_delegates: bool,
+        max_extensions: u8,
+        quorum: Option<u64>,
+        allow_early_close: bool,
+        metadata: Option<ContentRef>,
+        option_metadata: Vec<Option<ContentRef>>,
+        id_nonce: u64, // Creator-chosen nonce the poll ID is derived from
         tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
     },
     
@@ -169,9 +611,27 @@ pub enum PollInstruction {
     /// 1. `[writable]` Vote account
     /// 2. `[writable]` Poll account
     /// 3. `[writable]` Vote count account
-    /// 4. `[writable]` Voter registry account
-    /// 5. `[]` Delegation account (optional, only if voting as delegate)
-    /// 6. `[]` Token balance account (optional, only if weighted voting)
+    /// 4. `[writable]` Voter registry page being filled (page `total_voters / VOTERS_PER_PAGE`;
+    ///    written on the voter's first vote)
+    /// 5. `[writable]` Voter record account
+    /// 6. `[]` Weight account (required for weighted polls).
+    ///    A token balance, UTXO weight or asset holdings account, depending on the poll's weight source.
+    ///    Escrow-weighted polls take the escrow position followed by the token balance backing it
+    ///    (see `Poll::weight_account_count`)
+    /// 7. `[]` Asset holdings account proving eligibility (only if the poll requires an eligibility asset;
+    ///    takes slot 6 for unweighted polls)
+    /// 8. `[writable]` Vote accounts of the delegates currently carrying the voter's weight, in voter
+    ///    record order (only if the poll allows delegation; the direct vote overrides their slices)
+    /// 9. `[writable]` On a revote, the voter records of the principals the previous vote carried,
+    ///    in its proxy order (only if the poll allows delegation)
+    /// 10.. `[]` Proxy groups (only if the poll allows delegation), one per principal whose weight the
+    ///    voter carries: the delegation accounts from the principal to the voter in chain order, the
+    ///    principal's weight account and eligibility account where the poll requires them, then the
+    ///    principal's voter record. Principals who voted directly are skipped, as are principals carried
+    ///    by another delegate through a more specific delegation (poll > topic > global), an equally
+    ///    specific one counted first, or the same delegation at a delegate nearer the principal.
+    ///    Slices this chain takes precedence over are displaced: the group ends with the vote accounts
+    ///    of the delegates currently carrying them, in the principal's record order
     CastVote {
         poll_id: u64,
         option_index: u8,
@@ -197,7 +657,9 @@ pub enum PollInstruction {
         tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
     },
     
-    /// Close a poll (automatically done after end time, but can be manually triggered)
+    /// Close a poll (automatically done after end time, but can be manually triggered).
+    /// Before the end time only the creator may close it, or anyone once the outcome is decided
+    /// if the poll allows early close.
     /// Accounts:
     /// 0. `[signer]` Any account (verification happens against poll end time)
     /// 1. `[writable]` Poll account
@@ -207,7 +669,7 @@ pub enum PollInstruction {
         tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
     },
     
-    /// Get poll results
+    /// Get poll results, returned as a Borsh-encoded `results::PollResults` in the return data
     /// Accounts:
     /// 0. `[]` Poll account
     /// 1. `[]` Vote count account
@@ -231,10 +693,16 @@ pub enum PollInstruction {
     /// Accounts:
     /// 0. `[signer]` Delegator account
     /// 1. `[writable]` Delegation account
-    /// 2. `[]` Delegate account
+    /// 2.. `[]` Delegate accounts, one per share (a single delegate if `shares` is empty)
+    /// n.. `[writable]` Delegate profile accounts, one per delegate in the same order; empty for
+    ///    unregistered delegates
+    /// m. `[]` Poll account (poll-scoped delegations only)
     DelegateVote {
         poll_id: Option<u64>,
+        topic: Option<String>,
+        shares: Vec<u16>, // Basis points per delegate account, summing to 10000 (empty delegates everything)
         expiration: Option<u64>,
+        id_nonce: u64, // Delegator-chosen nonce the delegation ID is derived from
         tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
     },
     
@@ -242,8 +710,11 @@ pub enum PollInstruction {
     /// Accounts:
     /// 0. `[signer]` Delegator account
     /// 1. `[writable]` Delegation account
+    /// 2.. `[writable]` Profile accounts of the delegates whose shares are revoked, in share order
+    ///    (only for delegations validated against the delegate registry)
     RevokeDelegation {
         delegation_id: u64,
+        delegate: Option<Pubkey>, // Revoke only this delegate's share (None revokes the whole delegation)
         tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
     },
     
@@ -252,11 +723,134 @@ pub enum PollInstruction {
     /// 0. `[signer]` Owner account
     /// 1. `[writable]` Token balance account
     /// 2. `[]` Token account
+    /// 3. `[signer]` Balance oracle attesting the amount
     UpdateTokenBalance {
         token: Pubkey,
         amount: u64,
         tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
     },
+    
+    /// Prove control of Bitcoin UTXOs for weighted voting.
+    /// Every fee transaction input after the first (which pays the fee) is counted
+    /// if it pays to the voter's account script pubkey. The state transition spends
+    /// the proven UTXOs along with the fee input, so the voter must sign all of them,
+    /// and pays their full value back to the voter's account script pubkey in one output.
+    /// Accounts:
+    /// 0. `[signer]` Voter account
+    /// 1. `[writable]` UTXO weight account
+    /// 2. `[]` Poll account
+    /// 3.. `[writable]` UTXO claim accounts, one per proven input in input order
+    ProveUtxoWeight {
+        poll_id: u64,
+        prev_txs: Vec<Vec<u8>>, // Raw transactions creating the spent outputs
+        tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
+    },
+    
+    /// Prove holdings of a Rune or inscription collection for eligibility or weighting.
+    /// The holdings are attested by the poll's oracle, which indexes the asset off-chain.
+    /// Accounts:
+    /// 0. `[signer]` Voter account
+    /// 1. `[writable]` Asset holdings account
+    /// 2. `[]` Poll account
+    /// 3. `[signer]` Oracle attesting the holdings
+    /// 4.. `[writable]` Asset claim accounts, one per holding in order
+    ProveAssetHoldings {
+        poll_id: u64,
+        asset: BitcoinAsset,
+        holdings: Vec<AssetHolding>,
+        prev_txs: Vec<Vec<u8>>, // Raw transactions creating the holding outputs
+        tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
+    },
+    
+    /// Lock tokens in escrow for vote-escrowed weighting
+    /// Accounts:
+    /// 0. `[signer]` Owner account
+    /// 1. `[writable]` Escrow position account
+    /// 2. `[writable]` Token balance account
+    LockEscrow {
+        token: Pubkey,
+        amount: u64,
+        unlock_height: u64,
+        tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
+    },
+    
+    /// Extend an escrow lock to a later unlock height
+    /// Accounts:
+    /// 0. `[signer]` Owner account
+    /// 1. `[writable]` Escrow position account
+    ExtendEscrow {
+        new_unlock_height: u64,
+        tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
+    },
+    
+    /// Withdraw tokens from an expired escrow lock
+    /// Accounts:
+    /// 0. `[signer]` Owner account
+    /// 1. `[writable]` Escrow position account
+    /// 2. `[writable]` Token balance account
+    WithdrawEscrow {
+        tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
+    },
+    
+    /// Register as a delegate, or update an existing delegate profile
+    /// Accounts:
+    /// 0. `[signer]` Delegate account
+    /// 1. `[writable]` Delegate profile account
+    RegisterDelegate {
+        statement_uri: String,
+        statement_hash: [u8; 32],
+        voting_policy: String,
+        accepting_delegations: bool,
+        max_delegators: Option<u32>,
+        tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
+    },
+    
+    /// Recount a batch of votes into the poll's recount tally (permissionless).
+    /// Voters are counted in registry order; once the last one is counted the tally is compared
+    /// against the vote count, and every option that differs is flagged.
+    /// Accounts:
+    /// 0. `[signer]` Caller paying the fee (anyone)
+    /// 1. `[writable]` Recount tally account
+    /// 2. `[]` Poll account
+    /// 3. `[writable]` Vote count account (only written when the recount is applied)
+    /// 4. `[]` Voter registry page holding the next voter to count (page `next_voter / VOTERS_PER_PAGE`)
+    /// 5.. `[]` Vote accounts of the voters on that page, in registry order from the next voter to count
+    Recount {
+        poll_id: u64,
+        restart: bool, // Discard any recount in progress and start from the first voter
+        apply: bool, // Replace the vote count with the recounted tally once complete (voting must be over)
+        tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
+    },
+    
+    /// Push back the end time of a running poll (only creator, at most `max_extensions` times,
+    /// and only while the quorum, if any, has not been met)
+    /// Accounts:
+    /// 0. `[signer]` Poll creator account
+    /// 1. `[writable]` Poll account
+    /// 2. `[]` Vote count account
+    ExtendPoll {
+        poll_id: u64,
+        new_end_time: u64,
+        tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
+    },
+    
+    /// Edit a poll's title, description, options, times and documents (only creator, before start
+    /// time and before any vote); the poll is checked with the same rules as `CreatePoll`
+    /// Accounts:
+    /// 0. `[signer]` Poll creator account
+    /// 1. `[writable]` Poll account
+    /// 2. `[writable]` Vote count account (resized to the new options)
+    UpdatePoll {
+        poll_id: u64,
+        title: String,
+        description: String,
+        options: Vec<String>,
+        start_time: u64,
+        end_time: u64,
+        metadata: Option<ContentRef>,
+        option_metadata: Vec<Option<ContentRef>>,
+        tx_hex: Vec<u8>, // Raw Bitcoin transaction for fees
+    },
 }
 
 // Error Handling
@@ -317,6 +911,46 @@ pub enum VotingError {
     InvalidToken,
     /// Missing nonce
     MissingNonce,
+    /// Token balance not attested by the poll's oracle
+    UnverifiedTokenBalance,
+    /// Token balance attestation is too old
+    StaleTokenBalance,
+    /// UTXO has already been counted for this poll
+    UtxoAlreadyCounted,
+    /// UTXO proof does not match the claimed outpoint or owner
+    InvalidUtxoProof,
+    /// Voter does not hold the poll's eligibility asset
+    NotEligible,
+    /// Asset holdings proof is invalid
+    InvalidAssetProof,
+    /// Tokens are still locked in escrow
+    EscrowLocked,
+    /// Invalid escrow lock parameters
+    InvalidEscrowLock,
+    /// Delegation chain visits the same account twice
+    DelegationCycle,
+    /// Delegation chain is longer than the poll allows
+    DelegationTooDeep,
+    /// Delegate is not registered in the delegate registry
+    DelegateNotRegistered,
+    /// Delegate does not accept (further) delegations
+    DelegateNotAccepting,
+    /// Account is not at the address derived for it
+    InvalidAccountAddress,
+    /// Account is not owned by the program
+    InvalidAccountOwner,
+    /// Account holds a different account type or layout version than expected
+    InvalidAccountType,
+    /// Vote count changed since the recount started
+    RecountOutdated,
+    /// Poll has been extended as many times as it allows
+    ExtensionLimitReached,
+    /// Poll has met its quorum and can no longer be extended
+    QuorumReached,
+    /// Poll can no longer be edited because votes have been cast
+    PollHasVotes,
+    /// The latest attested balance no longer covers the tokens locked in escrow
+    EscrowNotBacked,
 }
 
 impl From<VotingError> for ProgramError {
@@ -349,13 +983,36 @@ impl From<VotingError> for ProgramError {
             VotingError::TokenBalanceNotFound => 1025,
             VotingError::InvalidToken => 1026,
             VotingError::MissingNonce => 1027,
+            VotingError::UnverifiedTokenBalance => 1028,
+            VotingError::StaleTokenBalance => 1029,
+            VotingError::UtxoAlreadyCounted => 1030,
+            VotingError::InvalidUtxoProof => 1031,
+            VotingError::NotEligible => 1032,
+            VotingError::InvalidAssetProof => 1033,
+            VotingError::EscrowLocked => 1034,
+            VotingError::InvalidEscrowLock => 1035,
+            VotingError::DelegationCycle => 1036,
+            VotingError::DelegationTooDeep => 1037,
+            VotingError::DelegateNotRegistered => 1038,
+            VotingError::DelegateNotAccepting => 1039,
+            VotingError::InvalidAccountAddress => 1040,
+            VotingError::InvalidAccountOwner => 1041,
+            VotingError::InvalidAccountType => 1042,
+            VotingError::RecountOutdated => 1043,
+            VotingError::ExtensionLimitReached => 1044,
+            VotingError::QuorumReached => 1045,
+            VotingError::PollHasVotes => 1046,
+            VotingError::EscrowNotBacked => 1047,
         })
     }
 }
 
 // Program Entry Point
+// Only on-chain: the entry point installs a bump allocator over the runtime's heap region, which
+// does not exist in host builds (client library, binaries, simulator)
 
-entrypoint!(process_instruction);
+#[cfg(target_os = "solana")]
+arch_program::entrypoint!(process_instruction);
 
 pub fn process_instruction(
     program_id: &Pubkey,
@@ -381,6 +1038,21 @@ pub fn process_instruction(
             is_encrypted,
             weight_token,
             early_voter_bonus,
+            bonus_schedule,
+            balance_oracle,
+            max_balance_age,
+            weight_source,
+            weight_transform,
+            eligibility_asset,
+            max_delegation_depth,
+            topic,
+            require_registered_delegates,
+            max_extensions,
+            quorum,
+            allow_early_close,
+            metadata,
+            option_metadata,
+            id_nonce,
             tx_hex,
         } => {
             process_create_poll(
@@ -398,6 +1070,21 @@ pub fn process_instruction(
                 is_encrypted,
                 weight_token,
                 early_voter_bonus,
+                bonus_schedule,
+                balance_oracle,
+                max_balance_age,
+                weight_source,
+                weight_transform,
+                eligibility_asset,
+                max_delegation_depth,
+                topic,
+                require_registered_delegates,
+                max_extensions,
+                quorum,
+                allow_early_close,
+                metadata,
+                option_metadata,
+                id_nonce,
                 tx_hex,
             )
         }
@@ -459,16 +1146,22 @@ pub fn process_instruction(
         }
         PollInstruction::DelegateVote {
             poll_id,
+            topic,
+            shares,
             expiration,
+            id_nonce,
             tx_hex,
         } => {
-            process_delegate_vote(program_id, accounts, poll_id, expiration, tx_hex)
+            process_delegate_vote(
+                program_id, accounts, poll_id, topic, shares, expiration, id_nonce, tx_hex,
+            )
         }
         PollInstruction::RevokeDelegation {
             delegation_id,
+            delegate,
             tx_hex,
         } => {
-            process_revoke_delegation(program_id, accounts, delegation_id, tx_hex)
+            process_revoke_delegation(program_id, accounts, delegation_id, delegate, tx_hex)
         }
         PollInstruction::UpdateTokenBalance {
             token,
@@ -477,6 +1170,98 @@ pub fn process_instruction(
         } => {
             process_update_token_balance(program_id, accounts, token, amount, tx_hex)
         }
+        PollInstruction::ProveUtxoWeight {
+            poll_id,
+            prev_txs,
+            tx_hex,
+        } => {
+            process_prove_utxo_weight(program_id, accounts, poll_id, prev_txs, tx_hex)
+        }
+        PollInstruction::ProveAssetHoldings {
+            poll_id,
+            asset,
+            holdings,
+            prev_txs,
+            tx_hex,
+        } => {
+            process_prove_asset_holdings(program_id, accounts, poll_id, asset, holdings, prev_txs, tx_hex)
+        }
+        PollInstruction::LockEscrow {
+            token,
+            amount,
+            unlock_height,
+            tx_hex,
+        } => {
+            process_lock_escrow(program_id, accounts, token, amount, unlock_height, tx_hex)
+        }
+        PollInstruction::ExtendEscrow {
+            new_unlock_height,
+            tx_hex,
+        } => {
+            process_extend_escrow(program_id, accounts, new_unlock_height, tx_hex)
+        }
+        PollInstruction::WithdrawEscrow { tx_hex } => {
+            process_withdraw_escrow(program_id, accounts, tx_hex)
+        }
+        PollInstruction::RegisterDelegate {
+            statement_uri,
+            statement_hash,
+            voting_policy,
+            accepting_delegations,
+            max_delegators,
+            tx_hex,
+        } => {
+            process_register_delegate(
+                program_id,
+                accounts,
+                statement_uri,
+                statement_hash,
+                voting_policy,
+                accepting_delegations,
+                max_delegators,
+                tx_hex,
+            )
+        }
+        PollInstruction::Recount {
+            poll_id,
+            restart,
+            apply,
+            tx_hex,
+        } => {
+            process_recount(program_id, accounts, poll_id, restart, apply, tx_hex)
+        }
+        PollInstruction::ExtendPoll {
+            poll_id,
+            new_end_time,
+            tx_hex,
+        } => {
+            process_extend_poll(program_id, accounts, poll_id, new_end_time, tx_hex)
+        }
+        PollInstruction::UpdatePoll {
+            poll_id,
+            title,
+            description,
+            options,
+            start_time,
+            end_time,
+            metadata,
+            option_metadata,
+            tx_hex,
+        } => {
+            process_update_poll(
+                program_id,
+                accounts,
+                poll_id,
+                title,
+                description,
+                options,
+                start_time,
+                end_time,
+                metadata,
+                option_metadata,
+                tx_hex,
+            )
+        }
     }
 }
 
@@ -497,10 +1282,69 @@ fn process_create_poll(
     is_encrypted: bool,
     weight_token: Option<Pubkey>,
     early_voter_bonus: u8,
+    bonus_schedule: BonusSchedule,
+    balance_oracle: Option<Pubkey>,
+    max_balance_age: u64,
+    weight_source: WeightSource,
+    weight_transform: WeightTransform,
+    eligibility_asset: Option<BitcoinAsset>,
+    max_delegation_depth: u8,
+    topic: Option<String>,
+    require_registered_delegates: bool,
+    max_extensions: u8,
+    quorum: Option<u64>,
+    allow_early_close: bool,
+    metadata: Option<ContentRef>,
+    option_metadata: Vec<Option<ContentRef>>,
+    id_nonce: u64,
     tx_hex: Vec<u8>,
 ) -> Result<(), ProgramError> {
+    // The poll ID is derived from the creator and their nonce; the block height is left out so
+    // clients can compute the ID and account addresses before submitting
+    let creator_key = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?.key;
+    let poll_id = pda::poll_id(creator_key, id_nonce);
+
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: poll_id,
+        creator: *creator_key,
+        title,
+        description,
+        options,
+        start_time,
+        end_time,
+        is_private,
+        allow_revote,
+        is_active: true,
+        is_weighted,
+        allow_delegation,
+        is_encrypted,
+        decryption_key: None,
+        weight_token,
+        early_voter_bonus,
+        bonus_schedule,
+        balance_oracle,
+        max_balance_age,
+        weight_source,
+        weight_transform,
+        eligibility_asset,
+        max_delegation_depth,
+        topic,
+        require_registered_delegates,
+        max_extensions,
+        extension_count: 0,
+        quorum,
+        allow_early_close,
+        metadata,
+        option_metadata,
+    };
+
+    // Token-weighted polls pass the token account
+    let needs_token_account = poll.needs_token_account();
+
     // Validate accounts
-    let min_accounts = if is_weighted && weight_token.is_some() { 5 } else { 4 };
+    let min_accounts = 4 + needs_token_account as usize;
     if accounts.len() < min_accounts {
         return Err(ProgramError::NotEnoughAccountKeys);
     }
@@ -513,8 +1357,8 @@ fn process_create_poll(
     let vote_count_account = next_account_info(account_iter)?;
     let voter_registry_account = next_account_info(account_iter)?;
     
-    // Optional token account for weighted voting
-    let token_account = if is_weighted && weight_token.is_some() {
+    // Optional token account for token-weighted voting
+    let _token_account = if needs_token_account {
         Some(next_account_info(account_iter)?)
     } else {
         None
@@ -530,112 +1374,73 @@ fn process_create_poll(
     }
 
     // Validate poll parameters
-    if title.is_empty() || title.len() > 100 {
-        return Err(VotingError::InvalidPollParameters.into());
-    }
+    let current_time = runtime::block_height();
+    validate_poll(&poll, current_time)?;
 
-    if description.len() > 1000 {
-        return Err(VotingError::InvalidPollParameters.into());
-    }
+    verify_poll_addresses(program_id, poll_id, poll_account, Some(vote_count_account))?;
+    verify_account_address(voter_registry_account, &pda::find_voter_registry_address(program_id, poll_id, 0).0)?;
 
-    if options.is_empty() || options.len() > 20 {
-        return Err(VotingError::InvalidPollParameters.into());
+    for account in [poll_account, vote_count_account, voter_registry_account] {
+        verify_account_owner(program_id, account)?;
     }
 
-    for option in &options {
-        if option.is_empty() || option.len() > 100 {
-            return Err(VotingError::InvalidPollParameters.into());
-        }
+    // A nonce can only be used once per creator
+    if poll_account.data.borrow().iter().any(|byte| *byte != 0) {
+        return Err(VotingError::PollAlreadyExists.into());
     }
 
-    // Validate time parameters
-    let current_time = get_bitcoin_block_height() as u64;
-    
-    if start_time >= end_time {
-        return Err(VotingError::InvalidPollParameters.into());
-    }
+    // Create vote count
+    let vote_count = VoteCount {
+        account_type: AccountType::VoteCount,
+        version: ACCOUNT_VERSION,
+        poll_id,
+        counts: vec![0; poll.options.len()],
+        raw_counts: vec![0; poll.options.len()],
+        proxy_counts: vec![0; poll.options.len()],
+        total_voters: 0,
+        last_updated: current_time,
+        revision: 0,
+        is_finalized: false,
+    };
 
-    if end_time <= current_time {
-        return Err(VotingError::InvalidPollParameters.into());
-    }
-
-    // Validate weighted voting parameters
-    if is_weighted && weight_token.is_none() {
-        return Err(VotingError::InvalidPollParameters.into());
-    }
-
-    if early_voter_bonus > 100 {
-        return Err(VotingError::InvalidPollParameters.into());
-    }
-
-    // Generate a unique poll ID (using current time and creator's pubkey)
-    let poll_id = current_time.wrapping_add(creator_account.key.to_bytes()[0] as u64);
-
-    // Create the poll
-    let poll = Poll {
-        id: poll_id,
-        creator: *creator_account.key,
-        title,
-        description,
-        options: options.clone(),
-        start_time,
-        end_time,
-        is_private,
-        allow_revote,
-        is_active: true,
-        is_weighted,
-        allow_delegation,
-        is_encrypted,
-        decryption_key: None,
-        weight_token,
-        early_voter_bonus,
-    };
-
-    // Create vote count
-    let vote_count = VoteCount {
-        poll_id,
-        counts: vec![0; options.len()],
-        total_voters: 0,
-        last_updated: current_time,
-        is_finalized: false,
-    };
-
-    // Create voter registry
-    let voter_registry = VoterRegistry {
-        poll_id,
-        voter_bitmap: vec![0; 1024], // Space for up to 8192 voters (1024 bytes * 8 bits)
-        voters: Vec::new(),
-    };
+    // Create the first voter registry page; later pages are created as they fill up
+    let voter_registry = VoterRegistry {
+        account_type: AccountType::VoterRegistry,
+        version: ACCOUNT_VERSION,
+        poll_id,
+        page: 0,
+        voters: Vec::new(),
+    };
 
     // Serialize and save poll data
-    let poll_data = poll.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let poll_data = borsh::to_vec(&poll).map_err(|_| ProgramError::InvalidAccountData)?;
     
     // Ensure account has enough space
     let poll_data_len = poll_account.data.borrow().len();
     if poll_data.len() > poll_data_len {
-        poll_account.realloc(poll_data.len(), false)?;
+        runtime::realloc(poll_account, poll_data.len())?;
     }
     
     poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
 
     // Serialize and save vote count data
-    let vote_count_data = vote_count.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let vote_count_data = borsh::to_vec(&vote_count).map_err(|_| ProgramError::InvalidAccountData)?;
     
     // Ensure account has enough space
     let vote_count_data_len = vote_count_account.data.borrow().len();
     if vote_count_data.len() > vote_count_data_len {
-        vote_count_account.realloc(vote_count_data.len(), false)?;
+        runtime::realloc(vote_count_account, vote_count_data.len())?;
     }
     
     vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
 
     // Serialize and save voter registry data
-    let voter_registry_data = voter_registry.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let voter_registry_data = borsh::to_vec(&voter_registry).map_err(|_| ProgramError::InvalidAccountData)?;
     
     // Ensure account has enough space
     let voter_registry_data_len = voter_registry_account.data.borrow().len();
     if voter_registry_data.len() > voter_registry_data_len {
-        voter_registry_account.realloc(voter_registry_data.len(), false)?;
+        runtime::realloc(voter_registry_account, voter_registry_data.len())?;
     }
     
     voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
@@ -643,6 +1448,15 @@ fn process_create_poll(
     // Process fee transaction
     process_fee_transaction(accounts, tx_hex)?;
 
+    // Emit event
+    events::emit(&VotingEvent::PollCreated {
+        poll_id,
+        creator: poll.creator,
+        options: poll.options.len() as u8,
+        start_time: poll.start_time,
+        end_time: poll.end_time,
+    });
+
     // Log success
     msg!("Poll created successfully with ID: {}", poll_id);
     
@@ -675,9 +1489,10 @@ fn process_cancel_poll(
         return Err(ProgramError::InvalidAccountData);
     }
 
+    verify_poll_addresses(program_id, poll_id, poll_account, None)?;
+
     // Deserialize poll data
-    let mut poll = Poll::try_from_slice(&poll_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut poll = load_account::<Poll>(program_id, poll_account)?;
 
     // Verify poll exists and creator is authorized
     if poll.id != poll_id {
@@ -689,7 +1504,7 @@ fn process_cancel_poll(
     }
 
     // Verify poll hasn't started yet
-    let current_time = get_bitcoin_block_height() as u64;
+    let current_time = runtime::block_height();
     if current_time >= poll.start_time {
         return Err(VotingError::PollAlreadyStarted.into());
     }
@@ -698,12 +1513,15 @@ fn process_cancel_poll(
     poll.is_active = false;
 
     // Serialize and save updated poll data
-    let poll_data = poll.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let poll_data = borsh::to_vec(&poll).map_err(|_| ProgramError::InvalidAccountData)?;
     poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
 
     // Process fee transaction
     process_fee_transaction(accounts, tx_hex)?;
 
+    // Emit event
+    events::emit(&VotingEvent::PollCancelled { poll_id });
+
     // Log success
     msg!("Poll cancelled successfully: {}", poll_id);
     
@@ -722,7 +1540,7 @@ fn process_cast_vote(
     tx_hex: Vec<u8>,
 ) -> Result<(), ProgramError> {
     // Validate accounts
-    if accounts.len() < 5 {
+    if accounts.len() < 6 {
         return Err(ProgramError::NotEnoughAccountKeys);
     }
 
@@ -734,33 +1552,29 @@ fn process_cast_vote(
     let poll_account = next_account_info(account_iter)?;
     let vote_count_account = next_account_info(account_iter)?;
     let voter_registry_account = next_account_info(account_iter)?;
+    let voter_record_account = next_account_info(account_iter)?;
     
-    // Optional accounts
-    let delegation_account = if account_iter.len() > 0 {
-        Some(next_account_info(account_iter)?)
-    } else {
-        None
-    };
-    
-    let token_balance_account = if account_iter.len() > 0 {
-        Some(next_account_info(account_iter)?)
-    } else {
-        None
-    };
+    // Optional accounts (resolved once the poll settings are known)
+    let optional_accounts: Vec<&AccountInfo> = account_iter.collect();
 
     // Verify account permissions
     if !voter_account.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
     
-    if !vote_account.is_writable || !poll_account.is_writable || 
-       !vote_count_account.is_writable || !voter_registry_account.is_writable {
+    if !vote_account.is_writable || !poll_account.is_writable || !vote_count_account.is_writable
+        || !voter_registry_account.is_writable || !voter_record_account.is_writable
+    {
         return Err(ProgramError::InvalidAccountData);
     }
 
+    // Poll state and the voter's vote live at addresses derived from the poll ID
+    verify_poll_addresses(program_id, poll_id, poll_account, Some(vote_count_account))?;
+    verify_account_address(vote_account, &pda::find_vote_address(program_id, poll_id, voter_account.key).0)?;
+    verify_account_owner(program_id, vote_account)?;
+
     // Deserialize poll data
-    let poll = Poll::try_from_slice(&poll_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let poll = load_account::<Poll>(program_id, poll_account)?;
 
     // Verify poll exists and is active
     if poll.id != poll_id {
@@ -772,7 +1586,7 @@ fn process_cast_vote(
     }
 
     // Verify poll timing
-    let current_time = get_bitcoin_block_height() as u64;
+    let current_time = runtime::block_height();
     
     if current_time < poll.start_time {
         return Err(VotingError::PollNotStarted.into());
@@ -787,15 +1601,28 @@ fn process_cast_vote(
         return Err(VotingError::InvalidOptionIndex.into());
     }
 
-    // Deserialize voter registry
-    let mut voter_registry = VoterRegistry::try_from_slice(&voter_registry_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
-
-    // Check if voter has already voted (one wallet = one vote enforcement)
+    // Optional slots only exist when the poll settings call for them
     let voter_key = voter_account.key;
-    let voter_index = find_voter_index(&voter_registry, voter_key);
+    let mut optional_iter = optional_accounts.into_iter();
+    let weight_accounts: Vec<&AccountInfo> = optional_iter.by_ref().take(poll.weight_account_count()).collect();
+    let eligibility_account = if poll.eligibility_asset.is_some() { optional_iter.next() } else { None };
+
+    // Verify the voter holds the eligibility asset
+    verify_eligibility(program_id, &poll, eligibility_account, voter_key, current_time)?;
+
+    // The voter's record tells whether they already voted (one wallet = one vote enforcement)
+    let mut voter_record = load_voter_record(program_id, voter_record_account, poll_id, voter_key)?;
+    let is_revote = voter_record.has_voted;
     
-    if let Some(index) = voter_index {
+    // Read the previous vote before the vote account is overwritten
+    let previous_vote = if is_revote {
+        load_optional_account::<Vote>(program_id, vote_account)?
+            .filter(|vote| vote.voter == *voter_key && vote.poll_id == poll_id)
+    } else {
+        None
+    };
+    
+    if is_revote {
         // Voter has already voted
         if !poll.allow_revote {
             return Err(VotingError::AlreadyVoted.into());
@@ -804,47 +1631,12 @@ fn process_cast_vote(
         // If revoting is allowed, we'll update the existing vote
         // This will be handled in the vote count update below
     } else {
-        // Add voter to registry
-        add_voter_to_registry(&mut voter_registry, voter_key)?;
+        // List the voter on the registry page currently being filled
+        let voters_so_far = load_account::<VoteCount>(program_id, vote_count_account)?.total_voters;
+        add_voter_to_registry(program_id, voter_registry_account, poll_id, voters_so_far, voter_key)?;
+        voter_record.has_voted = true;
     }
 
-    // Handle delegation if applicable
-    let effective_voter = if let Some(delegation_acc) = delegation_account {
-        if !poll.allow_delegation {
-            return Err(VotingError::InvalidDelegation.into());
-        }
-        
-        let delegation = Delegation::try_from_slice(&delegation_acc.data.borrow())
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-        
-        // Verify delegation is valid for this poll and voter
-        if delegation.delegator != *voter_key {
-            return Err(VotingError::InvalidDelegation.into());
-        }
-        
-        if !delegation.is_active {
-            return Err(VotingError::InvalidDelegation.into());
-        }
-        
-        if let Some(delegation_poll_id) = delegation.poll_id {
-            if delegation_poll_id != poll_id {
-                return Err(VotingError::InvalidDelegation.into());
-            }
-        }
-        
-        // Check if delegation has expired
-        if let Some(expiration) = delegation.expiration {
-            if current_time > expiration {
-                return Err(VotingError::DelegationExpired.into());
-            }
-        }
-        
-        // Use delegate as effective voter
-        delegation.delegate
-    } else {
-        *voter_key
-    };
-
     // Verify zero-knowledge proof if poll is private
     if poll.is_private {
         if let Some(proof) = &zk_proof {
@@ -869,87 +1661,172 @@ fn process_cast_vote(
         }
     }
 
-    // Determine vote weight
-    let vote_weight = if poll.is_weighted {
-        if let Some(token_balance_acc) = token_balance_account {
-            // Get token balance for weighted voting
-            let token_balance = TokenBalance::try_from_slice(&token_balance_acc.data.borrow())
-                .map_err(|_| VotingError::TokenBalanceNotFound)?;
+    // Weighted polls never trust a caller-supplied weight
+    if poll.is_weighted && weight.is_some() {
+        return Err(VotingError::InvalidVoteWeight.into());
+    }
+
+    // Determine the voter's own vote weight
+    let (mut vote_weight, mut raw_weight) = participant_weight(program_id, &poll, &weight_accounts, voter_key, current_time)?;
+
+    // Carry the weight of every principal who delegated to this voter, directly or through a chain.
+    // Each principal contributes its delegation links, then its weight, eligibility and record accounts.
+    let mut proxies: Vec<ProxyWeight> = Vec::new();
+    if poll.allow_delegation {
+        // A direct vote overrides every delegate currently carrying this voter's weight;
+        // their vote accounts are passed first, in record order, so their slices can be removed
+        for displaced in std::mem::take(&mut voter_record.carried) {
+            let carrier_vote_account = optional_iter.next().ok_or(ProgramError::NotEnoughAccountKeys)?;
+            release_proxy_slice(program_id, carrier_vote_account, vote_count_account, poll_id, &displaced.delegate, voter_key)?;
+        }
+        
+        // A revote re-declares every principal this voter carries; the records of the principals
+        // carried by the previous vote follow, in its proxy order
+        if let Some(previous) = &previous_vote {
+            for proxy in &previous.proxies {
+                let principal_record_account = optional_iter.next().ok_or(ProgramError::NotEnoughAccountKeys)?;
+                let mut principal_record =
+                    load_voter_record(program_id, principal_record_account, poll_id, &proxy.principal)?;
+                principal_record.carried.retain(|carried| carried.delegate != *voter_key);
+                save_voter_record(principal_record_account, &principal_record)?;
+            }
+        }
+        
+        let mut remaining = optional_iter.peekable();
+        let mut declared_principals: Vec<Pubkey> = Vec::new();
+        
+        while remaining.peek().is_some() {
+            let path = resolve_delegation_chain(program_id, &mut remaining, voter_key, &poll, current_time)?;
+            let principal = path.principal;
             
-            // Verify token balance belongs to voter and is for the correct token
-            if token_balance.owner != *voter_key {
-                return Err(ProgramError::InvalidAccountData);
+            // A principal can only be declared once per vote, whether or not their weight ends up carried
+            if declared_principals.contains(&principal) {
+                return Err(VotingError::InvalidDelegation.into());
             }
+            declared_principals.push(principal);
             
-            if let Some(weight_token) = poll.weight_token {
-                if token_balance.token != weight_token {
-                    return Err(VotingError::InvalidToken.into());
-                }
+            let principal_weight_accounts: Vec<&AccountInfo> = remaining.by_ref().take(poll.weight_account_count()).collect();
+            let principal_eligibility_account = if poll.eligibility_asset.is_some() { remaining.next() } else { None };
+            let principal_record_account = remaining.next().ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let mut principal_record = load_voter_record(program_id, principal_record_account, poll_id, &principal)?;
+            
+            // Principals who voted directly keep that vote
+            if principal_record.has_voted {
+                continue;
             }
             
-            // Apply early voter bonus if applicable
-            let time_elapsed = current_time.saturating_sub(poll.start_time);
-            let poll_duration = poll.end_time.saturating_sub(poll.start_time);
+            // A slice of this principal already counted stands unless this chain takes precedence
+            // over it; other shares of the same split delegation are carried alongside it
+            let superseded_by_existing = principal_record
+                .carried
+                .iter()
+                .any(|carried| carried_precedence(carried, &path, voter_key) == CarriedPrecedence::Existing);
+            if superseded_by_existing {
+                continue;
+            }
             
-            if poll.early_voter_bonus > 0 && poll_duration > 0 {
-                let progress = (time_elapsed as f64) / (poll_duration as f64);
-                let bonus_multiplier = 1.0 + (poll.early_voter_bonus as f64 / 100.0) * (1.0 - progress);
-                
-                // Apply bonus (with ceiling to ensure early voters get at least some bonus)
-                let bonus_weight = (token_balance.amount as f64 * bonus_multiplier).ceil() as u64;
-                bonus_weight
-            } else {
-                token_balance.amount
+            // Slices this chain takes precedence over are displaced; the carrying delegates'
+            // vote accounts follow, in record order
+            while let Some(position) = principal_record
+                .carried
+                .iter()
+                .position(|carried| carried_precedence(carried, &path, voter_key) == CarriedPrecedence::New)
+            {
+                let displaced = principal_record.carried.remove(position);
+                let carrier_vote_account = remaining.next().ok_or(ProgramError::NotEnoughAccountKeys)?;
+                release_proxy_slice(program_id, carrier_vote_account, vote_count_account, poll_id, &displaced.delegate, &principal)?;
             }
-        } else {
-            // Use provided weight or default to 1
-            weight.unwrap_or(1)
+            
+            verify_eligibility(program_id, &poll, principal_eligibility_account, &principal, current_time)?;
+            let (principal_weight, principal_raw_weight) =
+                participant_weight(program_id, &poll, &principal_weight_accounts, &principal, current_time)?;
+            
+            // Only the principal's share along this chain is carried
+            let proxy_weight = weight::apply_share(principal_weight, path.share_bps);
+            let proxy_raw_weight = weight::apply_share(principal_raw_weight, path.share_bps);
+            
+            vote_weight = vote_weight.checked_add(proxy_weight).ok_or(VotingError::InvalidVoteWeight)?;
+            raw_weight = raw_weight.checked_add(proxy_raw_weight).ok_or(VotingError::InvalidVoteWeight)?;
+            
+            principal_record.carried.push(CarriedWeight {
+                principal,
+                delegate: *voter_key,
+                scope: path.scope,
+                delegation_id: path.delegation_id,
+                via: path.via,
+            });
+            save_voter_record(principal_record_account, &principal_record)?;
+            
+            proxies.push(ProxyWeight {
+                principal,
+                depth: path.depth,
+                share_bps: path.share_bps,
+                weight: proxy_weight,
+                raw_weight: proxy_raw_weight,
+            });
         }
-    } else {
-        // For non-weighted polls, all votes have weight 1
-        1
-    };
+    }
+    
+    let proxy_weight: u64 = proxies.iter().map(|proxy| proxy.weight).sum();
 
     // Create or update vote
     let vote = Vote {
+        account_type: AccountType::Vote,
+        version: ACCOUNT_VERSION,
         poll_id,
         voter: *voter_key,
         option_index,
         timestamp: current_time,
         weight: vote_weight,
-        delegated_to: if delegation_account.is_some() { Some(effective_voter) } else { None },
+        raw_weight,
+        delegated_to: None,
+        proxies,
         encrypted_data,
         zk_proof,
         nonce,
     };
 
     // Serialize and save vote data
-    let vote_data = vote.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let vote_data = borsh::to_vec(&vote).map_err(|_| ProgramError::InvalidAccountData)?;
     
     // Ensure account has enough space
     let vote_data_len = vote_account.data.borrow().len();
     if vote_data.len() > vote_data_len {
-        vote_account.realloc(vote_data.len(), false)?;
+        runtime::realloc(vote_account, vote_data.len())?;
     }
     
     vote_account.data.borrow_mut()[..vote_data.len()].copy_from_slice(&vote_data);
 
     // Update vote count
     update_vote_count(
+        program_id,
         vote_count_account,
         option_index,
         vote_weight,
-        voter_index.is_some(), // is_revote
-        voter_index.map(|i| get_previous_vote(vote_account, poll_id, voter_key).unwrap_or(0) as u8),
+        raw_weight,
+        proxy_weight,
+        is_revote,
+        previous_vote.as_ref(),
+        poll.allow_early_close.then(|| results::max_raw_weight(&poll)),
     )?;
 
-    // Update voter registry
-    let voter_registry_data = voter_registry.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
-    voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
+    // Update voter record
+    save_voter_record(voter_record_account, &voter_record)?;
 
     // Process fee transaction
     process_fee_transaction(accounts, tx_hex)?;
 
+    // Emit event
+    events::emit(&VotingEvent::VoteCast {
+        poll_id,
+        voter: *voter_key,
+        option_index,
+        weight: vote.weight,
+        raw_weight: vote.raw_weight,
+        proxies: vote.proxies.iter().map(|proxy| proxy.principal).collect(),
+        is_revote,
+    });
+
     // Log success
     msg!("Vote cast successfully for poll {}: option {}", poll_id, option_index);
     
@@ -988,9 +1865,11 @@ fn process_change_vote(
         return Err(ProgramError::InvalidAccountData);
     }
 
+    verify_poll_addresses(program_id, poll_id, poll_account, Some(vote_count_account))?;
+    verify_account_address(vote_account, &pda::find_vote_address(program_id, poll_id, voter_account.key).0)?;
+
     // Deserialize poll data
-    let poll = Poll::try_from_slice(&poll_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let poll = load_account::<Poll>(program_id, poll_account)?;
 
     // Verify poll exists, is active, and allows revoting
     if poll.id != poll_id {
@@ -1006,7 +1885,7 @@ fn process_change_vote(
     }
 
     // Verify poll timing
-    let current_time = get_bitcoin_block_height() as u64;
+    let current_time = runtime::block_height();
     
     if current_time < poll.start_time {
         return Err(VotingError::PollNotStarted.into());
@@ -1022,8 +1901,7 @@ fn process_change_vote(
     }
 
     // Deserialize existing vote
-    let mut vote = Vote::try_from_slice(&vote_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut vote = load_account::<Vote>(program_id, vote_account)?;
 
     // Verify vote belongs to this voter and poll
     if vote.voter != *voter_account.key {
@@ -1069,20 +1947,32 @@ fn process_change_vote(
     }
 
     // Serialize and save updated vote data
-    let vote_data = vote.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let vote_data = borsh::to_vec(&vote).map_err(|_| ProgramError::InvalidAccountData)?;
     vote_account.data.borrow_mut()[..vote_data.len()].copy_from_slice(&vote_data);
 
     // Update vote count (decrement old option, increment new option)
     update_vote_count_change(
+        program_id,
         vote_count_account,
         old_option_index,
         new_option_index,
         vote.weight,
+        vote.raw_weight,
+        vote.proxies.iter().map(|proxy| proxy.weight).sum(),
     )?;
 
     // Process fee transaction
     process_fee_transaction(accounts, tx_hex)?;
 
+    // Emit event
+    events::emit(&VotingEvent::VoteChanged {
+        poll_id,
+        voter: *voter_account.key,
+        old_option_index,
+        new_option_index,
+        weight: vote.weight,
+    });
+
     // Log success
     msg!("Vote changed successfully for poll {}: option {} -> {}", poll_id, old_option_index, new_option_index);
     
@@ -1116,9 +2006,10 @@ fn process_close_poll(
         return Err(ProgramError::InvalidAccountData);
     }
 
+    verify_poll_addresses(program_id, poll_id, poll_account, Some(vote_count_account))?;
+
     // Deserialize poll data
-    let mut poll = Poll::try_from_slice(&poll_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut poll = load_account::<Poll>(program_id, poll_account)?;
 
     // Verify poll exists and is active
     if poll.id != poll_id {
@@ -1130,12 +2021,17 @@ fn process_close_poll(
     }
 
     // Check if poll can be closed
-    let current_time = get_bitcoin_block_height() as u64;
+    let current_time = runtime::block_height();
     
-    // Poll can be closed by anyone after end time
-    // Or by the creator at any time
+    // Poll can be closed by anyone after end time, or once its outcome is decided if it allows
+    // early close. Or by the creator at any time
     if current_time <= poll.end_time && poll.creator != *caller_account.key {
-        return Err(VotingError::NotPollCreator.into());
+        let is_decided = poll.allow_early_close
+            && results::is_decided(&poll, &load_account::<VoteCount>(program_id, vote_count_account)?);
+
+        if !is_decided {
+            return Err(VotingError::NotPollCreator.into());
+        }
     }
 
     // Update poll status
@@ -1143,24 +2039,31 @@ fn process_close_poll(
 
     // Finalize vote count if not encrypted
     if !poll.is_encrypted {
-        let mut vote_count = VoteCount::try_from_slice(&vote_count_account.data.borrow())
-            .map_err(|_| ProgramError::InvalidAccountData)?;
+        let mut vote_count = load_account::<VoteCount>(program_id, vote_count_account)?;
         
         vote_count.is_finalized = true;
         vote_count.last_updated = current_time;
+        vote_count.revision += 1;
         
         // Serialize and save updated vote count
-        let vote_count_data = vote_count.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+        let vote_count_data = borsh::to_vec(&vote_count).map_err(|_| ProgramError::InvalidAccountData)?;
         vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
     }
 
     // Serialize and save updated poll data
-    let poll_data = poll.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let poll_data = borsh::to_vec(&poll).map_err(|_| ProgramError::InvalidAccountData)?;
     poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
 
     // Process fee transaction
     process_fee_transaction(accounts, tx_hex)?;
 
+    // Emit event
+    events::emit(&VotingEvent::PollClosed {
+        poll_id,
+        closed_by: *caller_account.key,
+        is_finalized: !poll.is_encrypted,
+    });
+
     // Log success
     msg!("Poll closed successfully: {}", poll_id);
     
@@ -1183,9 +2086,10 @@ fn process_get_results(
     let poll_account = next_account_info(account_iter)?;
     let vote_count_account = next_account_info(account_iter)?;
 
+    verify_poll_addresses(program_id, poll_id, poll_account, Some(vote_count_account))?;
+
     // Deserialize poll data
-    let poll = Poll::try_from_slice(&poll_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let poll = load_account::<Poll>(program_id, poll_account)?;
 
     // Verify poll exists
     if poll.id != poll_id {
@@ -1193,41 +2097,48 @@ fn process_get_results(
     }
 
     // Deserialize vote count
-    let vote_count = VoteCount::try_from_slice(&vote_count_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let vote_count = load_account::<VoteCount>(program_id, vote_count_account)?;
 
     // Verify vote count belongs to this poll
     if vote_count.poll_id != poll_id {
         return Err(ProgramError::InvalidAccountData);
     }
 
-    // Check if results are available
-    let current_time = get_bitcoin_block_height() as u64;
-    
+    // Build the structured results and return them to the caller
+    let current_time = runtime::block_height();
+    let results = results::poll_results(&poll, &vote_count, current_time);
+    let results_data = borsh::to_vec(&results).map_err(|_| ProgramError::InvalidAccountData)?;
+    runtime::set_return_data(&results_data);
+
     // For encrypted polls, results are only available after decryption
-    if poll.is_encrypted && !vote_count.is_finalized {
+    if results.outcome == PollOutcome::Hidden {
         msg!("Results for encrypted poll {} are not yet available", poll_id);
         return Ok(());
     }
 
-    // Log results
+    // Log a readable summary of the returned results
     msg!("Poll results for poll {}", poll_id);
     msg!("Title: {}", poll.title);
-    msg!("Total voters: {}", vote_count.total_voters);
+    msg!("Total voters: {}", results.total_voters);
     
-    for (i, option) in poll.options.iter().enumerate() {
-        let count = if i < vote_count.counts.len() { vote_count.counts[i] } else { 0 };
-        let percentage = if vote_count.total_voters > 0 {
-            (count as f64 / vote_count.total_voters as f64) * 100.0
+    for (i, (option, result)) in poll.options.iter().zip(&results.options).enumerate() {
+        if poll.is_weighted {
+            msg!(
+                "Option {}: {} - {} effective weight, {} raw weight ({} bps)",
+                i, option, result.count, result.raw_count, result.share_bps
+            );
         } else {
-            0.0
-        };
+            msg!("Option {}: {} - {} votes ({} bps)", i, option, result.count, result.share_bps);
+        }
         
-        msg!("Option {}: {} - {} votes ({}%)", i, option, count, percentage);
+        if poll.allow_delegation {
+            msg!("Option {}: {} carried by proxy", i, result.proxy_count);
+        }
     }
     
-    msg!("Poll status: {}", if poll.is_active { "Active" } else { "Closed" });
-    msg!("Results finalized: {}", vote_count.is_finalized);
+    msg!("Poll status: {:?}", results.status);
+    msg!("Outcome: {:?}", results.outcome);
+    msg!("Results finalized: {}", results.is_finalized);
     
     Ok(())
 }
@@ -1269,9 +2180,10 @@ fn process_decrypt_results(
         }
     }
 
+    verify_poll_addresses(program_id, poll_id, poll_account, Some(vote_count_account))?;
+
     // Deserialize poll data
-    let mut poll = Poll::try_from_slice(&poll_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut poll = load_account::<Poll>(program_id, poll_account)?;
 
     // Verify poll exists, is encrypted, and creator is authorized
     if poll.id != poll_id {
@@ -1288,7 +2200,7 @@ fn process_decrypt_results(
 
     // Verify poll is closed
     if poll.is_active {
-        let current_time = get_bitcoin_block_height() as u64;
+        let current_time = runtime::block_height();
         if current_time <= poll.end_time {
             return Err(VotingError::PollStillActive.into());
         }
@@ -1298,8 +2210,7 @@ fn process_decrypt_results(
     }
 
     // Deserialize vote count
-    let mut vote_count = VoteCount::try_from_slice(&vote_count_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut vote_count = load_account::<VoteCount>(program_id, vote_count_account)?;
 
     // Verify vote count belongs to this poll and is not already finalized
     if vote_count.poll_id != poll_id {
@@ -1320,19 +2231,23 @@ fn process_decrypt_results(
     
     // For simplicity, we'll just mark the results as finalized
     vote_count.is_finalized = true;
-    vote_count.last_updated = get_bitcoin_block_height() as u64;
+    vote_count.last_updated = runtime::block_height();
+    vote_count.revision += 1;
 
     // Serialize and save updated poll data
-    let poll_data = poll.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let poll_data = borsh::to_vec(&poll).map_err(|_| ProgramError::InvalidAccountData)?;
     poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
 
     // Serialize and save updated vote count
-    let vote_count_data = vote_count.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let vote_count_data = borsh::to_vec(&vote_count).map_err(|_| ProgramError::InvalidAccountData)?;
     vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
 
     // Process fee transaction
     process_fee_transaction(accounts, tx_hex)?;
 
+    // Emit event
+    events::emit(&VotingEvent::ResultsDecrypted { poll_id });
+
     // Log success
     msg!("Poll results decrypted successfully: {}", poll_id);
     
@@ -1343,11 +2258,21 @@ fn process_delegate_vote(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     poll_id: Option<u64>,
+    topic: Option<String>,
+    shares: Vec<u16>,
     expiration: Option<u64>,
+    id_nonce: u64,
     tx_hex: Vec<u8>,
 ) -> Result<(), ProgramError> {
+    // An unsplit delegation gives everything to a single delegate
+    let shares = if shares.is_empty() { vec![BPS_DENOMINATOR as u16] } else { shares };
+    
+    if shares.len() > MAX_DELEGATION_SHARES {
+        return Err(VotingError::InvalidPollParameters.into());
+    }
+
     // Validate accounts
-    if accounts.len() < 3 {
+    if accounts.len() < 2 + 2 * shares.len() + poll_id.is_some() as usize {
         return Err(ProgramError::NotEnoughAccountKeys);
     }
 
@@ -1356,7 +2281,7 @@ fn process_delegate_vote(
     // Get accounts
     let delegator_account = next_account_info(account_iter)?;
     let delegation_account = next_account_info(account_iter)?;
-    let delegate_account = next_account_info(account_iter)?;
+    let delegate_accounts: Vec<&AccountInfo> = account_iter.by_ref().take(shares.len()).collect();
 
     // Verify account permissions
     if !delegator_account.is_signer {
@@ -1367,35 +2292,127 @@ fn process_delegate_vote(
         return Err(ProgramError::InvalidAccountData);
     }
 
+    // Derive the delegation ID from the delegator and their nonce, like poll IDs
+    let delegation_id = pda::delegation_id(delegator_account.key, id_nonce);
+    verify_account_address(delegation_account, &pda::find_delegation_address(program_id, delegation_id).0)?;
+    verify_account_owner(program_id, delegation_account)?;
+
+    // A nonce can only be used once per delegator
+    if delegation_account.data.borrow().iter().any(|byte| *byte != 0) {
+        return Err(VotingError::InvalidDelegation.into());
+    }
+
+    // Shares must be non-zero, go to distinct delegates and sum to 100%
+    let mut delegation_shares: Vec<DelegationShare> = Vec::with_capacity(shares.len());
+    for (delegate_account, bps) in delegate_accounts.iter().zip(shares.iter()) {
+        // Delegating to yourself would be a one-hop cycle
+        if delegate_account.key == delegator_account.key {
+            return Err(VotingError::DelegationCycle.into());
+        }
+        
+        if *bps == 0 || delegation_shares.iter().any(|share| share.delegate == *delegate_account.key) {
+            return Err(VotingError::InvalidPollParameters.into());
+        }
+        
+        delegation_shares.push(DelegationShare {
+            delegate: *delegate_account.key,
+            bps: *bps,
+        });
+    }
+    
+    if shares.iter().map(|bps| *bps as u64).sum::<u64>() != BPS_DENOMINATOR {
+        return Err(VotingError::InvalidPollParameters.into());
+    }
+
+    // Every delegate's profile account is passed, so registered delegates cannot be bypassed
+    let profile_accounts: Vec<&AccountInfo> = account_iter.by_ref().take(delegation_shares.len()).collect();
+    if profile_accounts.len() != delegation_shares.len() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    
+    let mut profiles: Vec<DelegateProfile> = Vec::with_capacity(profile_accounts.len());
+    for (profile_account, share) in profile_accounts.iter().zip(delegation_shares.iter()) {
+        if !profile_account.is_writable {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        
+        verify_account_address(profile_account, &pda::find_delegate_profile_address(program_id, &share.delegate).0)?;
+        
+        if let Some(profile) = load_optional_account::<DelegateProfile>(program_id, profile_account)? {
+            if profile.delegate != share.delegate {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            profiles.push(profile);
+        }
+    }
+    
+    // A delegation is registered when every delegate is; a split cannot mix the two
+    let registered = !profiles.is_empty();
+    if registered && profiles.len() != delegation_shares.len() {
+        return Err(VotingError::DelegateNotRegistered.into());
+    }
+    
+    // Delegations scoped to a poll that requires registered delegates must be registered
+    if let Some(poll_id) = poll_id {
+        let poll_account = next_account_info(account_iter)?;
+        verify_poll_addresses(program_id, poll_id, poll_account, None)?;
+        
+        let poll = load_account::<Poll>(program_id, poll_account)?;
+        if poll.require_registered_delegates && !registered {
+            return Err(VotingError::DelegateNotRegistered.into());
+        }
+    }
+    
+    for (profile_account, mut profile) in profile_accounts.iter().zip(profiles) {
+        // Delegates can refuse delegations outright or cap how many they accept
+        if !profile.accepting_delegations
+            || profile.max_delegators.is_some_and(|max| profile.delegator_count >= max)
+        {
+            return Err(VotingError::DelegateNotAccepting.into());
+        }
+        
+        profile.delegator_count += 1;
+        
+        let profile_data = borsh::to_vec(&profile).map_err(|_| ProgramError::InvalidAccountData)?;
+        profile_account.data.borrow_mut()[..profile_data.len()].copy_from_slice(&profile_data);
+    }
+
+    // A delegation is scoped to one poll, one topic, or everything
+    if let Some(topic) = &topic {
+        if poll_id.is_some() || topic.is_empty() || topic.len() > MAX_TOPIC_LEN {
+            return Err(VotingError::InvalidPollParameters.into());
+        }
+    }
+
     // Validate expiration if provided
     if let Some(exp) = expiration {
-        let current_time = get_bitcoin_block_height() as u64;
+        let current_time = runtime::block_height();
         if exp <= current_time {
             return Err(VotingError::InvalidPollParameters.into());
         }
     }
 
-    // Generate a unique delegation ID
-    let current_time = get_bitcoin_block_height() as u64;
-    let delegation_id = current_time.wrapping_add(delegator_account.key.to_bytes()[0] as u64);
-
     // Create delegation
     let delegation = Delegation {
+        account_type: AccountType::Delegation,
+        version: ACCOUNT_VERSION,
         id: delegation_id,
         delegator: *delegator_account.key,
-        delegate: *delegate_account.key,
+        shares: delegation_shares,
         poll_id,
+        topic,
         expiration,
         is_active: true,
+        registered,
     };
 
     // Serialize and save delegation data
-    let delegation_data = delegation.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let delegation_data = borsh::to_vec(&delegation).map_err(|_| ProgramError::InvalidAccountData)?;
     
     // Ensure account has enough space
     let delegation_data_len = delegation_account.data.borrow().len();
     if delegation_data.len() > delegation_data_len {
-        delegation_account.realloc(delegation_data.len(), false)?;
+        runtime::realloc(delegation_account, delegation_data.len())?;
     }
     
     delegation_account.data.borrow_mut()[..delegation_data.len()].copy_from_slice(&delegation_data);
@@ -1403,6 +2420,15 @@ fn process_delegate_vote(
     // Process fee transaction
     process_fee_transaction(accounts, tx_hex)?;
 
+    // Emit event
+    events::emit(&VotingEvent::DelegationCreated {
+        delegation_id,
+        delegator: *delegator_account.key,
+        delegates: delegation.shares.iter().map(|share| share.delegate).collect(),
+        poll_id: delegation.poll_id,
+        topic: delegation.topic.clone(),
+    });
+
     // Log success
     msg!("Vote delegation created successfully with ID: {}", delegation_id);
     
@@ -1413,6 +2439,7 @@ fn process_revoke_delegation(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
     delegation_id: u64,
+    delegate: Option<Pubkey>,
     tx_hex: Vec<u8>,
 ) -> Result<(), ProgramError> {
     // Validate accounts
@@ -1435,9 +2462,11 @@ fn process_revoke_delegation(
         return Err(ProgramError::InvalidAccountData);
     }
 
+    verify_account_address(delegation_account, &pda::find_delegation_address(program_id, delegation_id).0)?;
+
     // Deserialize delegation data
-    let mut delegation = Delegation::try_from_slice(&delegation_account.data.borrow())
-        .map_err(|_| VotingError::DelegationNotFound)?;
+    let mut delegation = load_optional_account::<Delegation>(program_id, delegation_account)?
+        .ok_or(VotingError::DelegationNotFound)?;
 
     // Verify delegation exists and delegator is authorized
     if delegation.id != delegation_id {
@@ -1448,16 +2477,55 @@ fn process_revoke_delegation(
         return Err(VotingError::NotDelegator.into());
     }
 
+    // Revoke a single share, or the whole delegation
+    let revoked: Vec<DelegationShare> = if let Some(delegate) = delegate {
+        let position = delegation
+            .shares
+            .iter()
+            .position(|share| share.delegate == delegate)
+            .ok_or(VotingError::DelegationNotFound)?;
+        vec![delegation.shares.remove(position)]
+    } else {
+        std::mem::take(&mut delegation.shares)
+    };
+
+    // Registered delegations release their slot with each revoked delegate
+    if delegation.registered && delegation.is_active {
+        for share in &revoked {
+            let profile_account = next_account_info(account_iter)?;
+            if !profile_account.is_writable {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            
+            verify_account_address(profile_account, &pda::find_delegate_profile_address(program_id, &share.delegate).0)?;
+            
+            let mut profile = load_optional_account::<DelegateProfile>(program_id, profile_account)?
+                .ok_or(VotingError::DelegateNotRegistered)?;
+            
+            if profile.delegate != share.delegate {
+                return Err(VotingError::DelegateNotRegistered.into());
+            }
+            
+            profile.delegator_count = profile.delegator_count.saturating_sub(1);
+            
+            let profile_data = borsh::to_vec(&profile).map_err(|_| ProgramError::InvalidAccountData)?;
+            profile_account.data.borrow_mut()[..profile_data.len()].copy_from_slice(&profile_data);
+        }
+    }
+
     // Update delegation status
-    delegation.is_active = false;
+    delegation.is_active = !delegation.shares.is_empty();
 
     // Serialize and save updated delegation data
-    let delegation_data = delegation.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let delegation_data = borsh::to_vec(&delegation).map_err(|_| ProgramError::InvalidAccountData)?;
     delegation_account.data.borrow_mut()[..delegation_data.len()].copy_from_slice(&delegation_data);
 
     // Process fee transaction
     process_fee_transaction(accounts, tx_hex)?;
 
+    // Emit event
+    events::emit(&VotingEvent::DelegationRevoked { delegation_id, delegate });
+
     // Log success
     msg!("Delegation revoked successfully: {}", delegation_id);
     
@@ -1472,7 +2540,7 @@ fn process_update_token_balance(
     tx_hex: Vec<u8>,
 ) -> Result<(), ProgramError> {
     // Validate accounts
-    if accounts.len() < 3 {
+    if accounts.len() < 4 {
         return Err(ProgramError::NotEnoughAccountKeys);
     }
 
@@ -1482,35 +2550,57 @@ fn process_update_token_balance(
     let owner_account = next_account_info(account_iter)?;
     let token_balance_account = next_account_info(account_iter)?;
     let token_account = next_account_info(account_iter)?;
+    let oracle_account = next_account_info(account_iter)?;
 
     // Verify account permissions
     if !owner_account.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
     
+    // The oracle co-signs the transaction to attest the owner's balance
+    if !oracle_account.is_signer {
+        return Err(VotingError::UnverifiedTokenBalance.into());
+    }
+    
     if !token_balance_account.is_writable {
         return Err(ProgramError::InvalidAccountData);
     }
 
-    // Verify token account
-    // In a real implementation, we would verify the token account belongs to the owner
-    // and contains the specified amount of tokens
+    // Verify token account matches the token being attested
+    if *token_account.key != token {
+        return Err(VotingError::InvalidToken.into());
+    }
+
+    verify_account_address(
+        token_balance_account,
+        &pda::find_token_balance_address(program_id, owner_account.key, &token).0,
+    )?;
+    
+    // Keep any escrow lock recorded against the existing balance
+    let locked_amount = match load_optional_account::<TokenBalance>(program_id, token_balance_account)? {
+        Some(existing) if existing.owner == *owner_account.key && existing.token == token => existing.locked_amount,
+        _ => 0,
+    };
     
     // Create or update token balance
     let token_balance = TokenBalance {
+        account_type: AccountType::TokenBalance,
+        version: ACCOUNT_VERSION,
         owner: *owner_account.key,
         token,
         amount,
-        last_updated: get_bitcoin_block_height() as u64,
+        last_updated: runtime::block_height(),
+        attested_by: *oracle_account.key,
+        locked_amount,
     };
 
     // Serialize and save token balance data
-    let token_balance_data = token_balance.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let token_balance_data = borsh::to_vec(&token_balance).map_err(|_| ProgramError::InvalidAccountData)?;
     
     // Ensure account has enough space
     let token_balance_data_len = token_balance_account.data.borrow().len();
     if token_balance_data.len() > token_balance_data_len {
-        token_balance_account.realloc(token_balance_data.len(), false)?;
+        runtime::realloc(token_balance_account, token_balance_data.len())?;
     }
     
     token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
@@ -1518,128 +2608,1943 @@ fn process_update_token_balance(
     // Process fee transaction
     process_fee_transaction(accounts, tx_hex)?;
 
+    // Emit event
+    events::emit(&VotingEvent::BalanceUpdated {
+        owner: *owner_account.key,
+        token,
+        amount,
+        oracle: *oracle_account.key,
+    });
+
     // Log success
-    msg!("Token balance updated successfully: {} tokens for {}", amount, token);
+    msg!("Token balance updated successfully: {} tokens for {} (attested by {})", amount, token, oracle_account.key);
     
     Ok(())
 }
 
-// Helper Functions
-
-fn process_fee_transaction(
+fn process_prove_utxo_weight(
+    program_id: &Pubkey,
     accounts: &[AccountInfo],
+    poll_id: u64,
+    prev_txs: Vec<Vec<u8>>,
     tx_hex: Vec<u8>,
 ) -> Result<(), ProgramError> {
-    // Deserialize fee transaction
-    let fees_tx: Transaction = bitcoin::consensus::deserialize(&tx_hex)
-        .map_err(|_| VotingError::InvalidFeeTransaction)?;
-    
-    // Create a new Bitcoin transaction for our state update
-    let mut tx = Transaction {
-        version: Version::TWO,
-        lock_time: LockTime::ZERO,
-        input: vec![],
-        output: vec![],
-    };
-    
-    // Add state transitions for all writable accounts
-    for account in accounts.iter().filter(|a| a.is_writable) {
-        add_state_transition(&mut tx, account);
+    // Validate accounts
+    if accounts.len() < 3 {
+        return Err(ProgramError::NotEnoughAccountKeys);
     }
+
+    let account_iter = &mut accounts.iter();
     
-    // Add fee input
-    if fees_tx.input.is_empty() {
-        return Err(VotingError::InsufficientFees.into());
+    // Get accounts
+    let voter_account = next_account_info(account_iter)?;
+    let utxo_weight_account = next_account_info(account_iter)?;
+    let poll_account = next_account_info(account_iter)?;
+    let claim_accounts: Vec<&AccountInfo> = account_iter.collect();
+
+    // Verify account permissions
+    if !voter_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
     }
     
-    tx.input.push(fees_tx.input[0].clone());
-    
-    // Prepare transaction for signing
-    let tx_to_sign = TransactionToSign {
-        tx_bytes: &bitcoin::consensus::serialize(&tx),
-        inputs_to_sign: &[InputToSign {
-            index: 0,
-            signer: accounts[0].key.clone(), // First account is always the signer
-        }],
+    if !utxo_weight_account.is_writable || claim_accounts.iter().any(|account| !account.is_writable) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    verify_poll_addresses(program_id, poll_id, poll_account, None)?;
+    verify_account_address(
+        utxo_weight_account,
+        &pda::find_utxo_weight_address(program_id, poll_id, voter_account.key).0,
+    )?;
+
+    // Deserialize poll data
+    let poll = load_account::<Poll>(program_id, poll_account)?;
+
+    // Verify poll exists, is active and is weighted by Bitcoin holdings
+    if poll.id != poll_id {
+        return Err(VotingError::PollDoesNotExist.into());
+    }
+
+    if !poll.is_active {
+        return Err(VotingError::PollNotActive.into());
+    }
+
+    if !poll.is_weighted || poll.weight_source != WeightSource::BitcoinUtxo {
+        return Err(VotingError::InvalidPollParameters.into());
+    }
+
+    let current_time = runtime::block_height();
+    if current_time > poll.end_time {
+        return Err(VotingError::PollEnded.into());
+    }
+
+    // Load existing UTXO weight for this voter and poll, or start a new one
+    let mut utxo_weight = match load_optional_account::<UtxoWeight>(program_id, utxo_weight_account)? {
+        Some(existing) if existing.poll_id == poll_id && existing.owner == *voter_account.key => existing,
+        _ => UtxoWeight {
+            account_type: AccountType::UtxoWeight,
+            version: ACCOUNT_VERSION,
+            poll_id,
+            owner: *voter_account.key,
+            outpoints: Vec::new(),
+            refunds: Vec::new(),
+            total_sats: 0,
+            last_updated: current_time,
+        },
+    };
+
+    // The fee transaction inputs are the UTXOs being proven
+    let fees_tx: Transaction = bitcoin::consensus::deserialize(&tx_hex)
+        .map_err(|_| VotingError::InvalidFeeTransaction)?;
+
+    if fees_tx.input.len() < 2 {
+        return Err(VotingError::InvalidUtxoProof.into());
+    }
+
+    // Every proven input comes with the account recording its claim
+    if claim_accounts.len() != fees_tx.input.len() - 1 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    // Decode the previous transactions so output values and owners can be checked
+    let prev_txs = decode_prev_txs(&prev_txs)?;
+    let voter_script_pubkey = runtime::account_script_pubkey(voter_account.key);
+
+    // Skip the first input, which pays the fee
+    let mut proven_sats: u64 = 0;
+    for (input, claim_account) in fees_tx.input.iter().skip(1).zip(claim_accounts) {
+        let outpoint = UtxoOutpoint {
+            txid: input.previous_output.txid.to_byte_array(),
+            vout: input.previous_output.vout,
+        };
+
+        // The refund of an earlier proof carries value that already counts for this voter
+        if utxo_weight.refunds.contains(&outpoint) {
+            return Err(VotingError::UtxoAlreadyCounted.into());
+        }
+
+        // An outpoint is claimed at most once per poll, by whichever voter proves it first
+        verify_account_address(claim_account, &pda::find_utxo_claim_address(program_id, poll_id, &outpoint).0)?;
+        if load_optional_account::<UtxoClaim>(program_id, claim_account)?.is_some() {
+            return Err(VotingError::UtxoAlreadyCounted.into());
+        }
+
+        // Only outputs locked to the voter's own key count towards their weight
+        let output = find_owned_output(&prev_txs, &outpoint, &voter_script_pubkey)?;
+
+        proven_sats = proven_sats
+            .checked_add(output.value.to_sat())
+            .ok_or(VotingError::InvalidVoteWeight)?;
+        utxo_weight.outpoints.push(outpoint.clone());
+
+        let claim = UtxoClaim {
+            account_type: AccountType::UtxoClaim,
+            version: ACCOUNT_VERSION,
+            poll_id,
+            outpoint,
+            owner: *voter_account.key,
+        };
+        let claim_data = borsh::to_vec(&claim).map_err(|_| ProgramError::InvalidAccountData)?;
+
+        // Ensure account has enough space
+        let claim_data_len = claim_account.data.borrow().len();
+        if claim_data.len() > claim_data_len {
+            runtime::realloc(claim_account, claim_data.len())?;
+        }
+
+        claim_account.data.borrow_mut()[..claim_data.len()].copy_from_slice(&claim_data);
+    }
+
+    utxo_weight.total_sats = utxo_weight
+        .total_sats
+        .checked_add(proven_sats)
+        .ok_or(VotingError::InvalidVoteWeight)?;
+    utxo_weight.last_updated = current_time;
+
+    // Process fee transaction, spending the proven UTXOs with the fee input and paying their
+    // value back to the voter. The refund is recorded before the weight account is saved.
+    let refund = bitcoin::TxOut {
+        value: bitcoin::Amount::from_sat(proven_sats),
+        script_pubkey: bitcoin::ScriptBuf::from_bytes(voter_script_pubkey),
+    };
+    let proven_inputs = fees_tx.input.len() - 1;
+    let state_tx = process_fee_transaction_spending(accounts, tx_hex, proven_inputs, vec![refund])?;
+    utxo_weight.refunds.push(UtxoOutpoint {
+        txid: state_tx.compute_txid().to_byte_array(),
+        vout: (state_tx.output.len() - 1) as u32,
+    });
+
+    // Serialize and save UTXO weight data
+    let utxo_weight_data = borsh::to_vec(&utxo_weight).map_err(|_| ProgramError::InvalidAccountData)?;
+    
+    // Ensure account has enough space
+    let utxo_weight_data_len = utxo_weight_account.data.borrow().len();
+    if utxo_weight_data.len() > utxo_weight_data_len {
+        runtime::realloc(utxo_weight_account, utxo_weight_data.len())?;
+    }
+    
+    utxo_weight_account.data.borrow_mut()[..utxo_weight_data.len()].copy_from_slice(&utxo_weight_data);
+
+    // Emit event
+    events::emit(&VotingEvent::UtxoWeightProved {
+        poll_id,
+        owner: *voter_account.key,
+        total_sats: utxo_weight.total_sats,
+    });
+
+    // Log success
+    msg!("UTXO weight proven for poll {}: {} sats", poll_id, utxo_weight.total_sats);
+    
+    Ok(())
+}
+
+fn process_prove_asset_holdings(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    poll_id: u64,
+    asset: BitcoinAsset,
+    holdings: Vec<AssetHolding>,
+    prev_txs: Vec<Vec<u8>>,
+    tx_hex: Vec<u8>,
+) -> Result<(), ProgramError> {
+    // Validate accounts, one claim account per holding
+    if accounts.len() < 4 + holdings.len() {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let account_iter = &mut accounts.iter();
+    
+    // Get accounts
+    let voter_account = next_account_info(account_iter)?;
+    let asset_holdings_account = next_account_info(account_iter)?;
+    let poll_account = next_account_info(account_iter)?;
+    let oracle_account = next_account_info(account_iter)?;
+    let claim_accounts: Vec<&AccountInfo> = account_iter.take(holdings.len()).collect();
+
+    // Verify account permissions
+    if !voter_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !asset_holdings_account.is_writable || claim_accounts.iter().any(|account| !account.is_writable) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    verify_poll_addresses(program_id, poll_id, poll_account, None)?;
+    verify_account_address(
+        asset_holdings_account,
+        &pda::find_asset_holdings_address(program_id, poll_id, voter_account.key, &asset).0,
+    )?;
+    for (holding, claim_account) in holdings.iter().zip(&claim_accounts) {
+        verify_account_address(
+            claim_account,
+            &pda::find_asset_claim_address(program_id, poll_id, &asset, &holding.outpoint).0,
+        )?;
+    }
+
+    // Deserialize poll data
+    let poll = load_account::<Poll>(program_id, poll_account)?;
+
+    // Verify poll exists and is active
+    if poll.id != poll_id {
+        return Err(VotingError::PollDoesNotExist.into());
+    }
+
+    if !poll.is_active {
+        return Err(VotingError::PollNotActive.into());
+    }
+
+    // The asset must be one the poll uses for weighting or eligibility
+    let is_weight_asset = poll.is_weighted && poll.weight_source == WeightSource::BitcoinAsset(asset);
+    if !is_weight_asset && poll.eligibility_asset != Some(asset) {
+        return Err(VotingError::InvalidAssetProof.into());
+    }
+
+    // The oracle co-signs the transaction to attest the indexed holdings
+    if !oracle_account.is_signer || Some(*oracle_account.key) != poll.balance_oracle {
+        return Err(VotingError::InvalidAssetProof.into());
+    }
+
+    let current_time = runtime::block_height();
+    if current_time > poll.end_time {
+        return Err(VotingError::PollEnded.into());
+    }
+
+    // Load existing holdings for this voter, poll and asset, or start new ones
+    let mut asset_holdings = match load_optional_account::<AssetHoldings>(program_id, asset_holdings_account)? {
+        Some(existing)
+            if existing.poll_id == poll_id
+                && existing.owner == *voter_account.key
+                && existing.asset == asset =>
+        {
+            existing
+        }
+        _ => AssetHoldings {
+            account_type: AccountType::AssetHoldings,
+            version: ACCOUNT_VERSION,
+            poll_id,
+            owner: *voter_account.key,
+            asset,
+            outpoints: Vec::new(),
+            amount: 0,
+            last_updated: current_time,
+        },
+    };
+
+    // Verify the voter owns every outpoint and none has been claimed before
+    let prev_txs = decode_prev_txs(&prev_txs)?;
+    let voter_script_pubkey = runtime::account_script_pubkey(voter_account.key);
+    let outpoints: Vec<UtxoOutpoint> = holdings.iter().map(|holding| holding.outpoint.clone()).collect();
+
+    for (outpoint, claim_account) in outpoints.iter().zip(claim_accounts) {
+        // An outpoint counts at most once per poll and asset, for whichever voter proves it first
+        if load_optional_account::<AssetClaim>(program_id, claim_account)?.is_some() {
+            return Err(VotingError::UtxoAlreadyCounted.into());
+        }
+
+        find_owned_output(&prev_txs, outpoint, &voter_script_pubkey)?;
+
+        let claim = AssetClaim {
+            account_type: AccountType::AssetClaim,
+            version: ACCOUNT_VERSION,
+            poll_id,
+            asset,
+            outpoint: outpoint.clone(),
+            owner: *voter_account.key,
+        };
+        let claim_data = borsh::to_vec(&claim).map_err(|_| ProgramError::InvalidAccountData)?;
+
+        // Ensure account has enough space
+        let claim_data_len = claim_account.data.borrow().len();
+        if claim_data.len() > claim_data_len {
+            runtime::realloc(claim_account, claim_data.len())?;
+        }
+
+        claim_account.data.borrow_mut()[..claim_data.len()].copy_from_slice(&claim_data);
+    }
+
+    // Total the attested holdings
+    let amount = assets::total_holdings(&AttestedHoldings::new(asset, &holdings), &asset, &outpoints)?;
+
+    asset_holdings.amount = asset_holdings
+        .amount
+        .checked_add(amount)
+        .ok_or(VotingError::InvalidVoteWeight)?;
+    asset_holdings.outpoints.extend(outpoints);
+    asset_holdings.last_updated = current_time;
+
+    // Serialize and save asset holdings data
+    let asset_holdings_data = borsh::to_vec(&asset_holdings).map_err(|_| ProgramError::InvalidAccountData)?;
+    
+    // Ensure account has enough space
+    let asset_holdings_data_len = asset_holdings_account.data.borrow().len();
+    if asset_holdings_data.len() > asset_holdings_data_len {
+        runtime::realloc(asset_holdings_account, asset_holdings_data.len())?;
+    }
+    
+    asset_holdings_account.data.borrow_mut()[..asset_holdings_data.len()].copy_from_slice(&asset_holdings_data);
+
+    // Process fee transaction
+    process_fee_transaction(accounts, tx_hex)?;
+
+    // Emit event
+    events::emit(&VotingEvent::AssetHoldingsProved {
+        poll_id,
+        owner: *voter_account.key,
+        amount: asset_holdings.amount,
+    });
+
+    // Log success
+    msg!("Asset holdings proven for poll {}: {}", poll_id, asset_holdings.amount);
+    
+    Ok(())
+}
+
+fn process_lock_escrow(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    token: Pubkey,
+    amount: u64,
+    unlock_height: u64,
+    tx_hex: Vec<u8>,
+) -> Result<(), ProgramError> {
+    // Validate accounts
+    if accounts.len() < 3 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let account_iter = &mut accounts.iter();
+    
+    // Get accounts
+    let owner_account = next_account_info(account_iter)?;
+    let escrow_account = next_account_info(account_iter)?;
+    let token_balance_account = next_account_info(account_iter)?;
+
+    // Verify account permissions
+    if !owner_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !escrow_account.is_writable || !token_balance_account.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    verify_account_address(escrow_account, &pda::find_escrow_address(program_id, owner_account.key, &token).0)?;
+    verify_account_address(
+        token_balance_account,
+        &pda::find_token_balance_address(program_id, owner_account.key, &token).0,
+    )?;
+
+    // Validate lock parameters
+    let current_time = runtime::block_height();
+    if amount == 0
+        || unlock_height <= current_time
+        || unlock_height - current_time > MAX_ESCROW_LOCK_BLOCKS
+    {
+        return Err(VotingError::InvalidEscrowLock.into());
+    }
+
+    // An escrow account holds a single position at a time
+    if let Some(existing) = load_optional_account::<EscrowPosition>(program_id, escrow_account)? {
+        if existing.amount > 0 {
+            return Err(VotingError::EscrowLocked.into());
+        }
+    }
+
+    // Deserialize token balance
+    let mut token_balance = load_optional_account::<TokenBalance>(program_id, token_balance_account)?
+        .ok_or(VotingError::TokenBalanceNotFound)?;
+
+    if token_balance.owner != *owner_account.key {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    if token_balance.token != token {
+        return Err(VotingError::InvalidToken.into());
+    }
+
+    // Tokens are escrowed from a recent attestation, not one the owner may have since sold
+    if current_time.saturating_sub(token_balance.last_updated) > MAX_ESCROW_BALANCE_AGE {
+        return Err(VotingError::StaleTokenBalance.into());
+    }
+
+    // Only unlocked tokens can be escrowed
+    let unlocked = token_balance.amount.saturating_sub(token_balance.locked_amount);
+    if amount > unlocked {
+        return Err(VotingError::InvalidEscrowLock.into());
+    }
+
+    token_balance.locked_amount += amount;
+
+    // Create escrow position
+    let position = EscrowPosition {
+        account_type: AccountType::EscrowPosition,
+        version: ACCOUNT_VERSION,
+        owner: *owner_account.key,
+        token,
+        amount,
+        unlock_height,
+        attested_by: token_balance.attested_by,
+        last_updated: current_time,
+    };
+
+    // Serialize and save escrow position data
+    let position_data = borsh::to_vec(&position).map_err(|_| ProgramError::InvalidAccountData)?;
+    
+    // Ensure account has enough space
+    let position_data_len = escrow_account.data.borrow().len();
+    if position_data.len() > position_data_len {
+        runtime::realloc(escrow_account, position_data.len())?;
+    }
+    
+    escrow_account.data.borrow_mut()[..position_data.len()].copy_from_slice(&position_data);
+
+    // Serialize and save updated token balance
+    let token_balance_data = borsh::to_vec(&token_balance).map_err(|_| ProgramError::InvalidAccountData)?;
+    token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
+
+    // Process fee transaction
+    process_fee_transaction(accounts, tx_hex)?;
+
+    // Emit event
+    events::emit(&VotingEvent::EscrowLocked {
+        owner: *owner_account.key,
+        token,
+        amount,
+        unlock_height,
+    });
+
+    // Log success
+    msg!("Escrow locked: {} tokens of {} until block {}", amount, token, unlock_height);
+    
+    Ok(())
+}
+
+fn process_extend_escrow(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_unlock_height: u64,
+    tx_hex: Vec<u8>,
+) -> Result<(), ProgramError> {
+    // Validate accounts
+    if accounts.len() < 2 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let account_iter = &mut accounts.iter();
+    
+    // Get accounts
+    let owner_account = next_account_info(account_iter)?;
+    let escrow_account = next_account_info(account_iter)?;
+
+    // Verify account permissions
+    if !owner_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !escrow_account.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // Deserialize escrow position
+    let mut position = load_account::<EscrowPosition>(program_id, escrow_account)?;
+
+    if position.owner != *owner_account.key {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    verify_account_address(
+        escrow_account,
+        &pda::find_escrow_address(program_id, owner_account.key, &position.token).0,
+    )?;
+
+    // Locks can only be extended, and never beyond the maximum lock duration
+    let current_time = runtime::block_height();
+    if position.amount == 0
+        || new_unlock_height <= position.unlock_height
+        || new_unlock_height.saturating_sub(current_time) > MAX_ESCROW_LOCK_BLOCKS
+    {
+        return Err(VotingError::InvalidEscrowLock.into());
+    }
+
+    position.unlock_height = new_unlock_height;
+    position.last_updated = current_time;
+
+    // Serialize and save updated escrow position
+    let position_data = borsh::to_vec(&position).map_err(|_| ProgramError::InvalidAccountData)?;
+    escrow_account.data.borrow_mut()[..position_data.len()].copy_from_slice(&position_data);
+
+    // Process fee transaction
+    process_fee_transaction(accounts, tx_hex)?;
+
+    // Emit event
+    events::emit(&VotingEvent::EscrowExtended {
+        owner: *owner_account.key,
+        unlock_height: new_unlock_height,
+    });
+
+    // Log success
+    msg!("Escrow extended until block {}", new_unlock_height);
+    
+    Ok(())
+}
+
+fn process_withdraw_escrow(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    tx_hex: Vec<u8>,
+) -> Result<(), ProgramError> {
+    // Validate accounts
+    if accounts.len() < 3 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let account_iter = &mut accounts.iter();
+    
+    // Get accounts
+    let owner_account = next_account_info(account_iter)?;
+    let escrow_account = next_account_info(account_iter)?;
+    let token_balance_account = next_account_info(account_iter)?;
+
+    // Verify account permissions
+    if !owner_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !escrow_account.is_writable || !token_balance_account.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // Deserialize escrow position and token balance
+    let mut position = load_account::<EscrowPosition>(program_id, escrow_account)?;
+    let mut token_balance = load_optional_account::<TokenBalance>(program_id, token_balance_account)?
+        .ok_or(VotingError::TokenBalanceNotFound)?;
+
+    if position.owner != *owner_account.key || token_balance.owner != *owner_account.key {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    if token_balance.token != position.token {
+        return Err(VotingError::InvalidToken.into());
+    }
+
+    verify_account_address(
+        escrow_account,
+        &pda::find_escrow_address(program_id, owner_account.key, &position.token).0,
+    )?;
+    verify_account_address(
+        token_balance_account,
+        &pda::find_token_balance_address(program_id, owner_account.key, &position.token).0,
+    )?;
+
+    // Tokens stay locked until the unlock height
+    let current_time = runtime::block_height();
+    if current_time < position.unlock_height {
+        return Err(VotingError::EscrowLocked.into());
+    }
+
+    let withdrawn = position.amount;
+    token_balance.locked_amount = token_balance.locked_amount.saturating_sub(withdrawn);
+    position.amount = 0;
+    position.last_updated = current_time;
+
+    // Serialize and save updated escrow position and token balance
+    let position_data = borsh::to_vec(&position).map_err(|_| ProgramError::InvalidAccountData)?;
+    escrow_account.data.borrow_mut()[..position_data.len()].copy_from_slice(&position_data);
+
+    let token_balance_data = borsh::to_vec(&token_balance).map_err(|_| ProgramError::InvalidAccountData)?;
+    token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
+
+    // Process fee transaction
+    process_fee_transaction(accounts, tx_hex)?;
+
+    // Emit event
+    events::emit(&VotingEvent::EscrowWithdrawn {
+        owner: *owner_account.key,
+        token: position.token,
+        amount: withdrawn,
+    });
+
+    // Log success
+    msg!("Escrow withdrawn: {} tokens", withdrawn);
+    
+    Ok(())
+}
+
+fn process_register_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    statement_uri: String,
+    statement_hash: [u8; 32],
+    voting_policy: String,
+    accepting_delegations: bool,
+    max_delegators: Option<u32>,
+    tx_hex: Vec<u8>,
+) -> Result<(), ProgramError> {
+    // Validate accounts
+    if accounts.len() < 2 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let account_iter = &mut accounts.iter();
+    
+    // Get accounts
+    let delegate_account = next_account_info(account_iter)?;
+    let profile_account = next_account_info(account_iter)?;
+
+    // Verify account permissions
+    if !delegate_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !profile_account.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    verify_account_address(profile_account, &pda::find_delegate_profile_address(program_id, delegate_account.key).0)?;
+
+    let mut profile = DelegateProfile {
+        account_type: AccountType::DelegateProfile,
+        version: ACCOUNT_VERSION,
+        delegate: *delegate_account.key,
+        statement_uri,
+        statement_hash,
+        voting_policy,
+        accepting_delegations,
+        max_delegators,
+        delegator_count: 0,
+        last_updated: runtime::block_height(),
+    };
+
+    // Validate profile parameters
+    validate_delegate_profile(&profile)?;
+
+    // Updating an existing profile keeps its delegator count
+    if let Some(existing) = load_optional_account::<DelegateProfile>(program_id, profile_account)? {
+        if existing.delegate != *delegate_account.key {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        profile.delegator_count = existing.delegator_count;
+    }
+
+    // Serialize and save profile data
+    let profile_data = borsh::to_vec(&profile).map_err(|_| ProgramError::InvalidAccountData)?;
+    
+    // Ensure account has enough space
+    let profile_data_len = profile_account.data.borrow().len();
+    if profile_data.len() > profile_data_len {
+        runtime::realloc(profile_account, profile_data.len())?;
+    }
+    
+    profile_account.data.borrow_mut()[..profile_data.len()].copy_from_slice(&profile_data);
+
+    // Process fee transaction
+    process_fee_transaction(accounts, tx_hex)?;
+
+    // Emit event
+    events::emit(&VotingEvent::DelegateRegistered {
+        delegate: *delegate_account.key,
+        accepting_delegations,
+    });
+
+    // Log success
+    msg!("Delegate profile registered, accepting delegations: {}", accepting_delegations);
+    
+    Ok(())
+}
+
+fn process_recount(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    poll_id: u64,
+    restart: bool,
+    apply: bool,
+    tx_hex: Vec<u8>,
+) -> Result<(), ProgramError> {
+    // Validate accounts
+    if accounts.len() < 5 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let account_iter = &mut accounts.iter();
+    
+    // Get accounts
+    let caller_account = next_account_info(account_iter)?;
+    let tally_account = next_account_info(account_iter)?;
+    let poll_account = next_account_info(account_iter)?;
+    let vote_count_account = next_account_info(account_iter)?;
+    let voter_registry_account = next_account_info(account_iter)?;
+    
+    // Vote accounts to count in this batch (remaining accounts)
+    let vote_accounts: Vec<&AccountInfo> = account_iter.collect();
+
+    // Verify account permissions
+    if !caller_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !tally_account.is_writable || (apply && !vote_count_account.is_writable) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    verify_poll_addresses(program_id, poll_id, poll_account, Some(vote_count_account))?;
+    verify_account_address(tally_account, &pda::find_recount_address(program_id, poll_id).0)?;
+
+    // Deserialize poll data
+    let poll = load_account::<Poll>(program_id, poll_account)?;
+
+    if poll.id != poll_id {
+        return Err(VotingError::PollDoesNotExist.into());
+    }
+
+    // Deserialize vote count
+    let mut vote_count = load_account::<VoteCount>(program_id, vote_count_account)?;
+
+    if vote_count.poll_id != poll_id {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // Replacing the vote count is only safe once no more votes can arrive
+    let current_time = runtime::block_height();
+    if apply && poll.is_active && current_time <= poll.end_time {
+        return Err(VotingError::PollStillActive.into());
+    }
+
+    // Continue the recount in progress, or start a new one
+    let mut tally = match load_optional_account::<RecountTally>(program_id, tally_account)? {
+        Some(tally) if !restart && !tally.is_complete => {
+            if tally.poll_id != poll_id {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            
+            // Votes cast since the recount started may already have been walked past
+            if tally.vote_count_revision != vote_count.revision {
+                return Err(VotingError::RecountOutdated.into());
+            }
+            
+            tally
+        }
+        _ => RecountTally {
+            account_type: AccountType::RecountTally,
+            version: ACCOUNT_VERSION,
+            poll_id,
+            counts: vec![0; poll.options.len()],
+            raw_counts: vec![0; poll.options.len()],
+            proxy_counts: vec![0; poll.options.len()],
+            next_voter: 0,
+            vote_count_revision: vote_count.revision,
+            is_complete: false,
+            discrepancies: Vec::new(),
+            applied: false,
+        },
+    };
+
+    // The batch comes from the registry page holding the next voter
+    let page = u32::try_from(tally.next_voter / VOTERS_PER_PAGE as u64)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    verify_account_address(
+        voter_registry_account,
+        &pda::find_voter_registry_address(program_id, poll_id, page).0,
+    )?;
+    
+    let voter_registry = load_account::<VoterRegistry>(program_id, voter_registry_account)?;
+    
+    if voter_registry.poll_id != poll_id || voter_registry.page != page {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    for vote_account in vote_accounts {
+        if tally.next_voter >= vote_count.total_voters {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        
+        // Every vote account must belong to the next voter on the page
+        let position = (tally.next_voter % VOTERS_PER_PAGE as u64) as usize;
+        if tally.next_voter / VOTERS_PER_PAGE as u64 != page as u64 {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let voter = voter_registry.voters.get(position).ok_or(ProgramError::InvalidAccountData)?;
+        
+        verify_account_address(vote_account, &pda::find_vote_address(program_id, poll_id, voter).0)?;
+        let vote = load_account::<Vote>(program_id, vote_account)?;
+        
+        if vote.voter != *voter || vote.poll_id != poll_id {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        
+        // Count the vote exactly as CastVote added it
+        let option = vote.option_index as usize;
+        if option < tally.counts.len() {
+            let vote_proxy_weight: u64 = vote.proxies.iter().map(|proxy| proxy.weight).sum();
+            tally.counts[option] = tally.counts[option].saturating_add(vote.weight);
+            tally.raw_counts[option] = tally.raw_counts[option].saturating_add(vote.raw_weight);
+            tally.proxy_counts[option] = tally.proxy_counts[option].saturating_add(vote_proxy_weight);
+        }
+        
+        tally.next_voter += 1;
+    }
+
+    // Once every voter is counted, compare the recount with the vote count
+    if tally.next_voter == vote_count.total_voters {
+        tally.is_complete = true;
+        tally.discrepancies = (0..tally.counts.len())
+            .filter(|&option| {
+                vote_count.counts.get(option) != Some(&tally.counts[option])
+                    || vote_count.raw_counts.get(option) != Some(&tally.raw_counts[option])
+                    || vote_count.proxy_counts.get(option) != Some(&tally.proxy_counts[option])
+            })
+            .map(|option| option as u8)
+            .collect();
+        
+        for option in &tally.discrepancies {
+            let option = *option as usize;
+            msg!(
+                "Recount discrepancy on option {}: counted {}, recounted {}",
+                option,
+                vote_count.counts.get(option).copied().unwrap_or(0),
+                tally.counts[option]
+            );
+        }
+        
+        if apply && !tally.discrepancies.is_empty() {
+            vote_count.counts = tally.counts.clone();
+            vote_count.raw_counts = tally.raw_counts.clone();
+            vote_count.proxy_counts = tally.proxy_counts.clone();
+            vote_count.last_updated = current_time;
+            vote_count.revision += 1;
+            
+            // Serialize and save repaired vote count
+            let vote_count_data = borsh::to_vec(&vote_count).map_err(|_| ProgramError::InvalidAccountData)?;
+            
+            // Ensure account has enough space
+            let vote_count_data_len = vote_count_account.data.borrow().len();
+            if vote_count_data.len() > vote_count_data_len {
+                runtime::realloc(vote_count_account, vote_count_data.len())?;
+            }
+            
+            vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+            tally.applied = true;
+        }
+    }
+
+    // Serialize and save tally data
+    let tally_data = borsh::to_vec(&tally).map_err(|_| ProgramError::InvalidAccountData)?;
+    
+    // Ensure account has enough space
+    let tally_data_len = tally_account.data.borrow().len();
+    if tally_data.len() > tally_data_len {
+        runtime::realloc(tally_account, tally_data.len())?;
+    }
+    
+    tally_account.data.borrow_mut()[..tally_data.len()].copy_from_slice(&tally_data);
+
+    // Process fee transaction
+    process_fee_transaction(accounts, tx_hex)?;
+
+    // Emit event once the recount is complete
+    if tally.is_complete {
+        events::emit(&VotingEvent::RecountCompleted {
+            poll_id,
+            discrepancies: tally.discrepancies.clone(),
+            applied: tally.applied,
+        });
+    }
+
+    // Log success
+    if tally.is_complete {
+        msg!(
+            "Recount of poll {} complete: {} discrepancies{}",
+            poll_id,
+            tally.discrepancies.len(),
+            if tally.applied { ", vote count repaired" } else { "" }
+        );
+    } else {
+        msg!("Recount of poll {}: {} of {} voters counted", poll_id, tally.next_voter, vote_count.total_voters);
+    }
+    
+    Ok(())
+}
+
+fn process_extend_poll(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    poll_id: u64,
+    new_end_time: u64,
+    tx_hex: Vec<u8>,
+) -> Result<(), ProgramError> {
+    // Validate accounts
+    if accounts.len() < 3 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let account_iter = &mut accounts.iter();
+    
+    // Get accounts
+    let creator_account = next_account_info(account_iter)?;
+    let poll_account = next_account_info(account_iter)?;
+    let vote_count_account = next_account_info(account_iter)?;
+
+    // Verify account permissions
+    if !creator_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !poll_account.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    verify_poll_addresses(program_id, poll_id, poll_account, Some(vote_count_account))?;
+
+    // Deserialize poll and vote count data
+    let mut poll = load_account::<Poll>(program_id, poll_account)?;
+    let vote_count = load_account::<VoteCount>(program_id, vote_count_account)?;
+
+    // Verify poll exists and creator is authorized
+    if poll.id != poll_id || vote_count.poll_id != poll_id {
+        return Err(VotingError::PollDoesNotExist.into());
+    }
+
+    if poll.creator != *creator_account.key {
+        return Err(VotingError::NotPollCreator.into());
+    }
+
+    // Only a running poll can be extended
+    let current_time = runtime::block_height();
+    if !poll.is_active {
+        return Err(VotingError::PollNotActive.into());
+    }
+
+    if current_time < poll.start_time {
+        return Err(VotingError::PollNotStarted.into());
+    }
+
+    if current_time > poll.end_time {
+        return Err(VotingError::PollEnded.into());
+    }
+
+    if new_end_time <= poll.end_time {
+        return Err(VotingError::InvalidPollParameters.into());
+    }
+
+    if poll.extension_count >= poll.max_extensions {
+        return Err(VotingError::ExtensionLimitReached.into());
+    }
+
+    // Polls with a quorum are only extended while turnout falls short of it
+    if let Some(quorum) = poll.quorum {
+        let raw_weight = vote_count.raw_counts.iter().fold(0u64, |total, count| total.saturating_add(*count));
+        if raw_weight >= quorum {
+            return Err(VotingError::QuorumReached.into());
+        }
+    }
+
+    // Apply the extension and check the result like any other poll
+    poll.end_time = new_end_time;
+    poll.extension_count += 1;
+    validate_poll(&poll, current_time)?;
+
+    // Serialize and save updated poll data
+    let poll_data = borsh::to_vec(&poll).map_err(|_| ProgramError::InvalidAccountData)?;
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+
+    // Process fee transaction
+    process_fee_transaction(accounts, tx_hex)?;
+
+    // Emit event
+    events::emit(&VotingEvent::PollExtended {
+        poll_id,
+        end_time: new_end_time,
+        extension_count: poll.extension_count,
+    });
+
+    // Log success
+    msg!("Poll {} extended to block {} ({} of {} extensions)", poll_id, new_end_time, poll.extension_count, poll.max_extensions);
+    
+    Ok(())
+}
+
+fn process_update_poll(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    poll_id: u64,
+    title: String,
+    description: String,
+    options: Vec<String>,
+    start_time: u64,
+    end_time: u64,
+    metadata: Option<ContentRef>,
+    option_metadata: Vec<Option<ContentRef>>,
+    tx_hex: Vec<u8>,
+) -> Result<(), ProgramError> {
+    // Validate accounts
+    if accounts.len() < 3 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+
+    let account_iter = &mut accounts.iter();
+    
+    // Get accounts
+    let creator_account = next_account_info(account_iter)?;
+    let poll_account = next_account_info(account_iter)?;
+    let vote_count_account = next_account_info(account_iter)?;
+
+    // Verify account permissions
+    if !creator_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    
+    if !poll_account.is_writable || !vote_count_account.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    verify_poll_addresses(program_id, poll_id, poll_account, Some(vote_count_account))?;
+
+    // Deserialize poll and vote count data
+    let mut poll = load_account::<Poll>(program_id, poll_account)?;
+    let mut vote_count = load_account::<VoteCount>(program_id, vote_count_account)?;
+
+    // Verify poll exists and creator is authorized
+    if poll.id != poll_id || vote_count.poll_id != poll_id {
+        return Err(VotingError::PollDoesNotExist.into());
+    }
+
+    if poll.creator != *creator_account.key {
+        return Err(VotingError::NotPollCreator.into());
+    }
+
+    if !poll.is_active {
+        return Err(VotingError::PollNotActive.into());
+    }
+
+    // Edits are only allowed before voting starts, and never once a vote exists
+    let current_time = runtime::block_height();
+    if current_time >= poll.start_time {
+        return Err(VotingError::PollAlreadyStarted.into());
+    }
+
+    if vote_count.total_voters > 0 {
+        return Err(VotingError::PollHasVotes.into());
+    }
+
+    // Apply the edits and check the result as if the poll were created now
+    poll.title = title;
+    poll.description = description;
+    poll.options = options;
+    poll.start_time = start_time;
+    poll.end_time = end_time;
+    poll.metadata = metadata;
+    poll.option_metadata = option_metadata;
+    validate_poll(&poll, current_time)?;
+
+    // Resize the (still empty) counts to the new options
+    let option_count = poll.options.len();
+    vote_count.counts = vec![0; option_count];
+    vote_count.raw_counts = vec![0; option_count];
+    vote_count.proxy_counts = vec![0; option_count];
+    vote_count.last_updated = current_time;
+    vote_count.revision += 1;
+
+    // Serialize and save poll data
+    let poll_data = borsh::to_vec(&poll).map_err(|_| ProgramError::InvalidAccountData)?;
+    
+    // Fit the account to the edited poll, which may be shorter than before
+    let poll_data_len = poll_account.data.borrow().len();
+    if poll_data.len() != poll_data_len {
+        runtime::realloc(poll_account, poll_data.len())?;
+    }
+    
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+
+    // Serialize and save vote count data
+    let vote_count_data = borsh::to_vec(&vote_count).map_err(|_| ProgramError::InvalidAccountData)?;
+    
+    // Fit the account to the resized counts
+    let vote_count_data_len = vote_count_account.data.borrow().len();
+    if vote_count_data.len() != vote_count_data_len {
+        runtime::realloc(vote_count_account, vote_count_data.len())?;
+    }
+    
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+
+    // Process fee transaction
+    process_fee_transaction(accounts, tx_hex)?;
+
+    // Emit event
+    events::emit(&VotingEvent::PollUpdated {
+        poll_id,
+        options: option_count as u8,
+        start_time: poll.start_time,
+        end_time: poll.end_time,
+    });
+
+    // Log success
+    msg!("Poll updated successfully: {}", poll_id);
+    
+    Ok(())
+}
+
+// Helper Functions
+
+fn process_fee_transaction(
+    accounts: &[AccountInfo],
+    tx_hex: Vec<u8>,
+) -> Result<(), ProgramError> {
+    process_fee_transaction_spending(accounts, tx_hex, 0, Vec::new()).map(|_| ())
+}
+
+// Like `process_fee_transaction`, but the state transition also spends the `extra_inputs`
+// fee transaction inputs after the fee input, all signed by the first account, and ends with
+// `extra_outputs`. Returns the state transition transaction.
+fn process_fee_transaction_spending(
+    accounts: &[AccountInfo],
+    tx_hex: Vec<u8>,
+    extra_inputs: usize,
+    extra_outputs: Vec<bitcoin::TxOut>,
+) -> Result<Transaction, ProgramError> {
+    // Deserialize fee transaction
+    let fees_tx: Transaction = bitcoin::consensus::deserialize(&tx_hex)
+        .map_err(|_| VotingError::InvalidFeeTransaction)?;
+    
+    // Create a new Bitcoin transaction for our state update
+    let mut tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![],
+        output: vec![],
+    };
+    
+    // Add state transitions for all writable accounts
+    for account in accounts.iter().filter(|a| a.is_writable) {
+        runtime::add_state_transition(&mut tx, account)?;
+    }
+    
+    // Add fee input
+    if fees_tx.input.is_empty() {
+        return Err(VotingError::InsufficientFees.into());
+    }
+    
+    if fees_tx.input.len() <= extra_inputs {
+        return Err(VotingError::InvalidFeeTransaction.into());
+    }
+    
+    // Add the fee input and any extra inputs, each signed by the first account
+    let mut inputs_to_sign = Vec::with_capacity(1 + extra_inputs);
+    for input in &fees_tx.input[..=extra_inputs] {
+        inputs_to_sign.push(InputToSign {
+            index: tx.input.len() as u32,
+            signer: *accounts[0].key, // First account is always the signer
+        });
+        tx.input.push(input.clone());
+    }
+    tx.output.extend(extra_outputs);
+    
+    // Submit transaction to be signed
+    runtime::set_transaction_to_sign(accounts, &tx, &inputs_to_sign)?;
+    Ok(tx)
+}
+
+fn decode_prev_txs(prev_txs: &[Vec<u8>]) -> Result<Vec<Transaction>, VotingError> {
+    prev_txs
+        .iter()
+        .map(|raw| bitcoin::consensus::deserialize(raw))
+        .collect::<Result<_, _>>()
+        .map_err(|_| VotingError::InvalidUtxoProof)
+}
+
+fn find_owned_output<'a>(
+    prev_txs: &'a [Transaction],
+    outpoint: &UtxoOutpoint,
+    owner_script_pubkey: &[u8],
+) -> Result<&'a bitcoin::TxOut, VotingError> {
+    // Find the transaction that created the outpoint
+    let prev_tx = prev_txs
+        .iter()
+        .find(|tx| tx.compute_txid().to_byte_array() == outpoint.txid)
+        .ok_or(VotingError::InvalidUtxoProof)?;
+
+    let output = prev_tx
+        .output
+        .get(outpoint.vout as usize)
+        .ok_or(VotingError::InvalidUtxoProof)?;
+
+    // The output must be locked to the owner's key
+    if output.script_pubkey.as_bytes() != owner_script_pubkey {
+        return Err(VotingError::InvalidUtxoProof);
+    }
+
+    Ok(output)
+}
+
+/// Verify that `owner` holds the poll's eligibility asset (no-op if the poll has none)
+fn verify_eligibility(
+    program_id: &Pubkey,
+    poll: &Poll,
+    eligibility_account: Option<&AccountInfo>,
+    owner: &Pubkey,
+    current_time: u64,
+) -> Result<(), ProgramError> {
+    let eligibility_asset = match poll.eligibility_asset {
+        Some(asset) => asset,
+        None => return Ok(()),
+    };
+
+    let eligibility_acc = eligibility_account.ok_or(VotingError::NotEligible)?;
+    let holdings = load_optional_account::<AssetHoldings>(program_id, eligibility_acc)?
+        .ok_or(VotingError::NotEligible)?;
+    
+    if holdings.owner != *owner
+        || holdings.poll_id != poll.id
+        || holdings.asset != eligibility_asset
+        || holdings.amount == 0
+    {
+        return Err(VotingError::NotEligible.into());
+    }
+    
+    verify_account_address(
+        eligibility_acc,
+        &pda::find_asset_holdings_address(program_id, poll.id, owner, &eligibility_asset).0,
+    )?;
+    
+    if current_time.saturating_sub(holdings.last_updated) > poll.max_balance_age {
+        return Err(VotingError::StaleTokenBalance.into());
+    }
+    
+    Ok(())
+}
+
+/// Effective and raw weight of `owner`'s share of a vote.
+/// Unweighted polls count every participant as one.
+fn participant_weight(
+    program_id: &Pubkey,
+    poll: &Poll,
+    weight_accounts: &[&AccountInfo],
+    owner: &Pubkey,
+    current_time: u64,
+) -> Result<(u64, u64), ProgramError> {
+    if !poll.is_weighted {
+        return Ok((1, 1));
+    }
+    
+    if weight_accounts.len() < poll.weight_account_count() {
+        return Err(VotingError::TokenBalanceNotFound.into());
+    }
+    let base_weight = base_vote_weight(program_id, poll, weight_accounts, owner, current_time)?;
+    
+    // Apply early voter bonus if applicable
+    let raw_weight = weight::apply_early_voter_bonus(
+        base_weight,
+        poll.early_voter_bonus,
+        &poll.bonus_schedule,
+        poll.start_time,
+        poll.end_time,
+        current_time,
+    )?;
+    
+    // Apply per-voter caps and dampening
+    Ok((weight::apply_transform(raw_weight, &poll.weight_transform), raw_weight))
+}
+
+/// Weight of `owner` read from their weight account, before the early voter bonus
+fn base_vote_weight(
+    program_id: &Pubkey,
+    poll: &Poll,
+    weight_accounts: &[&AccountInfo],
+    owner: &Pubkey,
+    current_time: u64,
+) -> Result<u64, ProgramError> {
+    let weight_acc = weight_accounts[0];
+    let base_weight = match poll.weight_source {
+        WeightSource::TokenBalance => {
+            let token_balance = load_optional_account::<TokenBalance>(program_id, weight_acc)?
+                .ok_or(VotingError::TokenBalanceNotFound)?;
+            
+            // Verify token balance belongs to voter and is for the correct token
+            if token_balance.owner != *owner {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            
+            if let Some(weight_token) = poll.weight_token {
+                if token_balance.token != weight_token {
+                    return Err(VotingError::InvalidToken.into());
+                }
+            }
+            
+            verify_account_address(
+                weight_acc,
+                &pda::find_token_balance_address(program_id, owner, &token_balance.token).0,
+            )?;
+            
+            // Verify the balance was attested by the poll's oracle and is recent enough
+            if Some(token_balance.attested_by) != poll.balance_oracle {
+                return Err(VotingError::UnverifiedTokenBalance.into());
+            }
+            
+            if current_time.saturating_sub(token_balance.last_updated) > poll.max_balance_age {
+                return Err(VotingError::StaleTokenBalance.into());
+            }
+            
+            // Escrowed tokens carry weight through their escrow position instead
+            token_balance.amount.saturating_sub(token_balance.locked_amount)
+        }
+        WeightSource::BitcoinUtxo => {
+            let utxo_weight = load_optional_account::<UtxoWeight>(program_id, weight_acc)?
+                .ok_or(VotingError::InvalidUtxoProof)?;
+            
+            // Verify the proven UTXOs belong to this voter and poll
+            if utxo_weight.owner != *owner || utxo_weight.poll_id != poll.id {
+                return Err(VotingError::InvalidUtxoProof.into());
+            }
+            
+            verify_account_address(weight_acc, &pda::find_utxo_weight_address(program_id, poll.id, owner).0)?;
+            
+            utxo_weight.total_sats
+        }
+        WeightSource::BitcoinAsset(weight_asset) => {
+            let holdings = load_optional_account::<AssetHoldings>(program_id, weight_acc)?
+                .ok_or(VotingError::InvalidAssetProof)?;
+            
+            // Verify the holdings belong to this voter, poll and asset
+            if holdings.owner != *owner || holdings.poll_id != poll.id || holdings.asset != weight_asset {
+                return Err(VotingError::InvalidAssetProof.into());
+            }
+            
+            verify_account_address(
+                weight_acc,
+                &pda::find_asset_holdings_address(program_id, poll.id, owner, &weight_asset).0,
+            )?;
+            
+            if current_time.saturating_sub(holdings.last_updated) > poll.max_balance_age {
+                return Err(VotingError::StaleTokenBalance.into());
+            }
+            
+            holdings.amount
+        }
+        WeightSource::VoteEscrow => {
+            let position = load_account::<EscrowPosition>(program_id, weight_acc)?;
+            
+            // Verify the escrow belongs to voter, is for the correct token and was attested
+            if position.owner != *owner {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            
+            if Some(position.token) != poll.weight_token {
+                return Err(VotingError::InvalidToken.into());
+            }
+            
+            verify_account_address(weight_acc, &pda::find_escrow_address(program_id, owner, &position.token).0)?;
+            
+            if Some(position.attested_by) != poll.balance_oracle {
+                return Err(VotingError::UnverifiedTokenBalance.into());
+            }
+            
+            // Escrow does not hold the tokens, so a recent attestation must show they are still held
+            let balance_acc = weight_accounts[1];
+            verify_account_address(
+                balance_acc,
+                &pda::find_token_balance_address(program_id, owner, &position.token).0,
+            )?;
+            let token_balance = load_optional_account::<TokenBalance>(program_id, balance_acc)?
+                .ok_or(VotingError::TokenBalanceNotFound)?;
+            
+            if token_balance.owner != *owner || token_balance.token != position.token {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            
+            if Some(token_balance.attested_by) != poll.balance_oracle {
+                return Err(VotingError::UnverifiedTokenBalance.into());
+            }
+            
+            if current_time.saturating_sub(token_balance.last_updated) > poll.max_balance_age {
+                return Err(VotingError::StaleTokenBalance.into());
+            }
+            
+            if token_balance.amount < position.amount {
+                return Err(VotingError::EscrowNotBacked.into());
+            }
+            
+            weight::escrow_weight(&position, current_time)
+        }
     };
     
-    // Submit transaction to be signed
-    set_transaction_to_sign(accounts, tx_to_sign)
+    Ok(base_weight)
+}
+
+/// Scope through which `delegation` applies to `poll`, or None if it does not apply
+fn delegation_scope(delegation: &Delegation, poll: &Poll) -> Option<DelegationScope> {
+    match (delegation.poll_id, &delegation.topic) {
+        (Some(delegation_poll_id), _) => (delegation_poll_id == poll.id).then_some(DelegationScope::Poll),
+        (None, Some(topic)) => (poll.topic.as_ref() == Some(topic)).then_some(DelegationScope::Topic),
+        (None, None) => Some(DelegationScope::Global),
+    }
 }
 
-fn find_voter_index(registry: &VoterRegistry, voter: &Pubkey) -> Option<usize> {
-    // First check bitmap for efficiency
-    let voter_hash = hash_pubkey(voter);
-    let byte_index = (voter_hash % 8192) / 8;
-    let bit_index = (voter_hash % 8192) % 8;
+/// A verified delegation chain from a principal to the voter carrying their weight
+struct DelegationPath {
+    /// Principal whose weight is carried
+    principal: Pubkey,
+    /// Number of hops from the principal to the voter
+    depth: u8,
+    /// Scope of the principal's own delegation
+    scope: DelegationScope,
+    /// ID of the principal's own delegation
+    delegation_id: u64,
+    /// Share of the principal's weight reaching the voter, in basis points
+    share_bps: u16,
+    /// Intermediate delegates between the principal and the voter, nearest the principal first
+    via: Vec<Pubkey>,
+}
+
+/// Which of two slices of the same principal's weight counts
+#[derive(Debug, PartialEq, Eq)]
+enum CarriedPrecedence {
+    /// The slice already carried stands; the new chain is skipped
+    Existing,
+    /// The new chain displaces the slice already carried
+    New,
+    /// Both carry different shares of the same split delegation
+    Both,
+}
+
+/// Precedence between a slice another vote already carries and a new chain to `voter`.
+/// A more specific delegation wins (poll > topic > global), and among equally specific
+/// delegations the first one counted stands. Along a single delegation, the delegate nearest
+/// the principal wins, so a share is never counted both by a delegate and by one further down
+/// its chain; shares that split off into different branches are carried side by side.
+fn carried_precedence(carried: &CarriedWeight, path: &DelegationPath, voter: &Pubkey) -> CarriedPrecedence {
+    if carried.scope != path.scope {
+        return if carried.scope > path.scope { CarriedPrecedence::Existing } else { CarriedPrecedence::New };
+    }
     
-    if byte_index < registry.voter_bitmap.len() as u64 {
-        let byte = registry.voter_bitmap[byte_index as usize];
-        if (byte & (1 << bit_index)) == 0 {
-            // Bit not set, voter definitely not in registry
-            return None;
-        }
+    if carried.delegation_id != path.delegation_id {
+        return CarriedPrecedence::Existing;
+    }
+    
+    let carried_route: Vec<&Pubkey> = carried.via.iter().chain(std::iter::once(&carried.delegate)).collect();
+    let new_route: Vec<&Pubkey> = path.via.iter().chain(std::iter::once(voter)).collect();
+    
+    if new_route.starts_with(&carried_route) {
+        CarriedPrecedence::Existing
+    } else if carried_route.starts_with(&new_route) {
+        CarriedPrecedence::New
     } else {
-        // Bitmap not large enough, voter definitely not in registry
-        return None;
+        CarriedPrecedence::Both
     }
+}
+
+/// Share of `delegation` given to `delegate`, in basis points
+fn delegated_share(delegation: &Delegation, delegate: &Pubkey) -> Option<u16> {
+    delegation
+        .shares
+        .iter()
+        .find(|share| share.delegate == *delegate)
+        .map(|share| share.bps)
+}
+
+/// Follow a chain of delegation accounts from a principal to `voter`.
+/// Each link must be active, unexpired and valid for the poll, and continue through one of
+/// the previous link's shares; the share reaching the voter is the product of those shares.
+fn resolve_delegation_chain<'a, 'b: 'a>(
+    program_id: &Pubkey,
+    accounts: &mut impl Iterator<Item = &'a AccountInfo<'b>>,
+    voter: &Pubkey,
+    poll: &Poll,
+    current_time: u64,
+) -> Result<DelegationPath, ProgramError> {
+    let mut path: Vec<Pubkey> = Vec::new();
+    let mut principal_scope = DelegationScope::Global;
+    let mut principal_delegation_id = 0;
+    let mut share_bps = BPS_DENOMINATOR;
+    let mut previous: Option<Delegation> = None;
     
-    // Bit is set, check actual voters list to handle collisions
-    registry.voters.iter().position(|v| v == voter)
+    loop {
+        let link_account = accounts.next().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let link = load_account::<Delegation>(program_id, link_account)?;
+        
+        if let Some(previous) = &previous {
+            let bps = delegated_share(previous, &link.delegator).ok_or(VotingError::InvalidDelegation)?;
+            share_bps = share_bps * bps as u64 / BPS_DENOMINATOR;
+        }
+        
+        if !link.is_active {
+            return Err(VotingError::InvalidDelegation.into());
+        }
+        
+        if poll.require_registered_delegates && !link.registered {
+            return Err(VotingError::DelegateNotRegistered.into());
+        }
+        
+        let scope = delegation_scope(&link, poll).ok_or(VotingError::InvalidDelegation)?;
+        if path.is_empty() {
+            principal_scope = scope;
+            principal_delegation_id = link.id;
+        }
+        
+        // Check if delegation has expired
+        if let Some(expiration) = link.expiration {
+            if current_time > expiration {
+                return Err(VotingError::DelegationExpired.into());
+            }
+        }
+        
+        // A chain that revisits an account, including the voter, is a cycle
+        if link.delegator == *voter || path.contains(&link.delegator) {
+            return Err(VotingError::DelegationCycle.into());
+        }
+        
+        path.push(link.delegator);
+        
+        if path.len() > poll.max_delegation_depth as usize {
+            return Err(VotingError::DelegationTooDeep.into());
+        }
+        
+        if let Some(bps) = delegated_share(&link, voter) {
+            share_bps = share_bps * bps as u64 / BPS_DENOMINATOR;
+            
+            return Ok(DelegationPath {
+                principal: path[0],
+                depth: path.len() as u8,
+                scope: principal_scope,
+                delegation_id: principal_delegation_id,
+                share_bps: share_bps as u16,
+                via: path[1..].to_vec(),
+            });
+        }
+        
+        previous = Some(link);
+    }
 }
 
-fn add_voter_to_registry(registry: &mut VoterRegistry, voter: &Pubkey) -> Result<(), ProgramError> {
-    // Set bit in bitmap
-    let voter_hash = hash_pubkey(voter);
-    let byte_index = (voter_hash % 8192) / 8;
-    let bit_index = (voter_hash % 8192) % 8;
+/// Remove `principal`'s slice from the vote `delegate` cast on their behalf, and from the tally
+fn release_proxy_slice(
+    program_id: &Pubkey,
+    carrier_vote_account: &AccountInfo,
+    vote_count_account: &AccountInfo,
+    poll_id: u64,
+    delegate: &Pubkey,
+    principal: &Pubkey,
+) -> Result<(), ProgramError> {
+    if !carrier_vote_account.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
     
-    if byte_index < registry.voter_bitmap.len() as u64 {
-        registry.voter_bitmap[byte_index as usize] |= 1 << bit_index;
-    } else {
-        // Resize bitmap if needed
-        while byte_index >= registry.voter_bitmap.len() as u64 {
-            registry.voter_bitmap.push(0);
+    verify_account_address(carrier_vote_account, &pda::find_vote_address(program_id, poll_id, delegate).0)?;
+    
+    let mut carrier_vote = load_account::<Vote>(program_id, carrier_vote_account)?;
+    
+    if carrier_vote.voter != *delegate || carrier_vote.poll_id != poll_id {
+        return Err(VotingError::InvalidDelegation.into());
+    }
+    
+    let position = carrier_vote
+        .proxies
+        .iter()
+        .position(|proxy| proxy.principal == *principal)
+        .ok_or(VotingError::InvalidDelegation)?;
+    let slice = carrier_vote.proxies.remove(position);
+    
+    carrier_vote.weight = carrier_vote.weight.saturating_sub(slice.weight);
+    carrier_vote.raw_weight = carrier_vote.raw_weight.saturating_sub(slice.raw_weight);
+    
+    // The vote only shrinks, so it still fits the account
+    let carrier_vote_data = borsh::to_vec(&carrier_vote).map_err(|_| ProgramError::InvalidAccountData)?;
+    carrier_vote_account.data.borrow_mut()[..carrier_vote_data.len()].copy_from_slice(&carrier_vote_data);
+    
+    let mut vote_count = load_account::<VoteCount>(program_id, vote_count_account)?;
+    
+    let option = carrier_vote.option_index as usize;
+    if option < vote_count.counts.len() {
+        vote_count.counts[option] = vote_count.counts[option].saturating_sub(slice.weight);
+        vote_count.raw_counts[option] = vote_count.raw_counts[option].saturating_sub(slice.raw_weight);
+        vote_count.proxy_counts[option] = vote_count.proxy_counts[option].saturating_sub(slice.weight);
+    }
+    vote_count.revision += 1;
+    
+    let vote_count_data = borsh::to_vec(&vote_count).map_err(|_| ProgramError::InvalidAccountData)?;
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+
+    events::emit(&VotingEvent::ProxySliceReleased {
+        poll_id,
+        carrier: *delegate,
+        principal: *principal,
+        weight: slice.weight,
+    });
+
+    Ok(())
+}
+
+fn verify_account_address(account: &AccountInfo, expected: &Pubkey) -> Result<(), ProgramError> {
+    if account.key != expected {
+        return Err(VotingError::InvalidAccountAddress.into());
+    }
+
+    Ok(())
+}
+
+// Checks the poll account and, when passed, the vote count account
+fn verify_poll_addresses(
+    program_id: &Pubkey,
+    poll_id: u64,
+    poll_account: &AccountInfo,
+    vote_count_account: Option<&AccountInfo>,
+) -> Result<(), ProgramError> {
+    verify_account_address(poll_account, &pda::find_poll_address(program_id, poll_id).0)?;
+
+    if let Some(vote_count_account) = vote_count_account {
+        verify_account_address(vote_count_account, &pda::find_vote_count_address(program_id, poll_id).0)?;
+    }
+
+    Ok(())
+}
+
+fn verify_account_owner(program_id: &Pubkey, account: &AccountInfo) -> Result<(), ProgramError> {
+    if *account.owner != *program_id {
+        return Err(VotingError::InvalidAccountOwner.into());
+    }
+
+    Ok(())
+}
+
+// Loads a program account, checking its owner, discriminator and layout version
+fn load_account<T: ProgramAccount>(program_id: &Pubkey, account: &AccountInfo) -> Result<T, ProgramError> {
+    verify_account_owner(program_id, account)?;
+
+    let data = account.data.borrow();
+    if data.len() < 2 || data[0] != T::ACCOUNT_TYPE as u8 || data[1] != ACCOUNT_VERSION {
+        return Err(VotingError::InvalidAccountType.into());
+    }
+
+    // Accounts are only grown, never shrunk, so a rewrite with less data leaves stale bytes after
+    // the value; decode the value and ignore whatever follows it
+    T::deserialize(&mut &data[..]).map_err(|_| ProgramError::InvalidAccountData)
+}
+
+// Like `load_account`, but a program account that has not been written yet loads as `None`
+fn load_optional_account<T: ProgramAccount>(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<Option<T>, ProgramError> {
+    verify_account_owner(program_id, account)?;
+
+    let is_uninitialized = account
+        .data
+        .borrow()
+        .first()
+        .is_none_or(|account_type| *account_type == AccountType::Uninitialized as u8);
+    if is_uninitialized {
+        return Ok(None);
+    }
+
+    load_account(program_id, account).map(Some)
+}
+
+// Loads `voter`'s record for the poll; a record that has not been written yet starts empty
+fn load_voter_record(
+    program_id: &Pubkey,
+    voter_record_account: &AccountInfo,
+    poll_id: u64,
+    voter: &Pubkey,
+) -> Result<VoterRecord, ProgramError> {
+    verify_account_address(
+        voter_record_account,
+        &pda::find_voter_record_address(program_id, poll_id, voter).0,
+    )?;
+
+    match load_optional_account::<VoterRecord>(program_id, voter_record_account)? {
+        Some(record) => {
+            if record.poll_id != poll_id || record.voter != *voter {
+                return Err(ProgramError::InvalidAccountData);
+            }
+            Ok(record)
         }
-        registry.voter_bitmap[byte_index as usize] |= 1 << bit_index;
+        None => Ok(VoterRecord {
+            account_type: AccountType::VoterRecord,
+            version: ACCOUNT_VERSION,
+            poll_id,
+            voter: *voter,
+            has_voted: false,
+            carried: Vec::new(),
+        }),
     }
+}
+
+fn save_voter_record(voter_record_account: &AccountInfo, voter_record: &VoterRecord) -> Result<(), ProgramError> {
+    let voter_record_data = borsh::to_vec(&voter_record).map_err(|_| ProgramError::InvalidAccountData)?;
     
-    // Add to voters list
-    registry.voters.push(*voter);
+    // Ensure account has enough space
+    let voter_record_data_len = voter_record_account.data.borrow().len();
+    if voter_record_data.len() > voter_record_data_len {
+        runtime::realloc(voter_record_account, voter_record_data.len())?;
+    }
     
+    voter_record_account.data.borrow_mut()[..voter_record_data.len()].copy_from_slice(&voter_record_data);
+
     Ok(())
 }
 
-fn hash_pubkey(pubkey: &Pubkey) -> u64 {
-    // Simple hash function for demo purposes
-    // In production, use a proper hash function
-    let bytes = pubkey.to_bytes();
-    let mut hash: u64 = 0;
+// Appends a first-time voter to the registry page being filled; `voters_so_far` voters
+// are already listed, so the page is `voters_so_far / VOTERS_PER_PAGE`
+fn add_voter_to_registry(
+    program_id: &Pubkey,
+    voter_registry_account: &AccountInfo,
+    poll_id: u64,
+    voters_so_far: u64,
+    voter: &Pubkey,
+) -> Result<(), ProgramError> {
+    let page = u32::try_from(voters_so_far / VOTERS_PER_PAGE as u64)
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+    verify_account_address(
+        voter_registry_account,
+        &pda::find_voter_registry_address(program_id, poll_id, page).0,
+    )?;
+
+    let mut voter_registry = load_optional_account::<VoterRegistry>(program_id, voter_registry_account)?
+        .unwrap_or(VoterRegistry {
+            account_type: AccountType::VoterRegistry,
+            version: ACCOUNT_VERSION,
+            poll_id,
+            page,
+            voters: Vec::new(),
+        });
+
+    if voter_registry.poll_id != poll_id
+        || voter_registry.page != page
+        || voter_registry.voters.len() >= VOTERS_PER_PAGE
+    {
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    voter_registry.voters.push(*voter);
+
+    let voter_registry_data = borsh::to_vec(&voter_registry).map_err(|_| ProgramError::InvalidAccountData)?;
     
-    for i in 0..8 {
-        hash = hash.wrapping_add((bytes[i] as u64).wrapping_shl(i as u32 * 8));
+    // Ensure account has enough space
+    let voter_registry_data_len = voter_registry_account.data.borrow().len();
+    if voter_registry_data.len() > voter_registry_data_len {
+        runtime::realloc(voter_registry_account, voter_registry_data.len())?;
     }
     
-    hash
+    voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
+
+    Ok(())
+}
+
+/// Check the settings of a poll about to be created against the rules `CreatePoll` enforces.
+/// Shared with the client builders so invalid polls are rejected before they are submitted.
+pub fn validate_poll(poll: &Poll, current_time: u64) -> Result<(), VotingError> {
+    if poll.title.is_empty() || poll.title.len() > 100 {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    if poll.description.len() > 1000 {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    if poll.options.is_empty() || poll.options.len() > 20 {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    for option in &poll.options {
+        if option.is_empty() || option.len() > 100 {
+            return Err(VotingError::InvalidPollParameters);
+        }
+    }
+
+    // Validate off-chain documents; options either all have a slot or none do
+    if !poll.option_metadata.is_empty() && poll.option_metadata.len() != poll.options.len() {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    for content in poll.metadata.iter().chain(poll.option_metadata.iter().flatten()) {
+        content.validate()?;
+    }
+
+    // Validate time parameters
+    if poll.start_time >= poll.end_time {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    if poll.end_time <= current_time {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    // Validate weighted voting parameters
+    if poll.is_weighted {
+        match poll.weight_source {
+            WeightSource::TokenBalance => {
+                if poll.weight_token.is_none() {
+                    return Err(VotingError::InvalidPollParameters);
+                }
+
+                // Token-weighted polls only accept balances attested by an oracle
+                if poll.balance_oracle.is_none() || poll.max_balance_age == 0 {
+                    return Err(VotingError::InvalidPollParameters);
+                }
+            }
+            WeightSource::BitcoinUtxo => {
+                if poll.weight_token.is_some() {
+                    return Err(VotingError::InvalidPollParameters);
+                }
+            }
+            WeightSource::BitcoinAsset(_) => {
+                // Asset holdings are attested by the oracle instead of a token balance
+                if poll.weight_token.is_some() || poll.balance_oracle.is_none() || poll.max_balance_age == 0 {
+                    return Err(VotingError::InvalidPollParameters);
+                }
+            }
+            WeightSource::VoteEscrow => {
+                // Escrowed tokens must be attested by the poll's oracle, when locked and when voting
+                if poll.weight_token.is_none() || poll.balance_oracle.is_none() || poll.max_balance_age == 0 {
+                    return Err(VotingError::InvalidPollParameters);
+                }
+            }
+        }
+    }
+
+    if !weight::is_valid_transform(&poll.weight_transform) {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    // Eligibility assets are attested by the oracle as well
+    if poll.eligibility_asset.is_some() && (poll.balance_oracle.is_none() || poll.max_balance_age == 0) {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    if poll.early_voter_bonus > 100 || !weight::is_valid_schedule(&poll.bonus_schedule) {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    // Delegating polls must allow at least one hop; other polls must not set a depth
+    if poll.allow_delegation {
+        if poll.max_delegation_depth == 0 || poll.max_delegation_depth > MAX_DELEGATION_DEPTH {
+            return Err(VotingError::InvalidPollParameters);
+        }
+    } else if poll.max_delegation_depth != 0 || poll.require_registered_delegates {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    if let Some(topic) = &poll.topic {
+        if topic.is_empty() || topic.len() > MAX_TOPIC_LEN {
+            return Err(VotingError::InvalidPollParameters);
+        }
+    }
+
+    // A quorum only limits extensions, so it needs a poll that can be extended
+    if poll.max_extensions > MAX_POLL_EXTENSIONS || poll.extension_count > poll.max_extensions {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    if poll.quorum == Some(0) || (poll.quorum.is_some() && poll.max_extensions == 0) {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    // Deciding the outcome early needs a bounded supply of weight still to be cast, and counts
+    // that are visible and cannot move through revotes
+    if poll.allow_early_close
+        && (!poll.is_weighted || poll.weight_transform.total_supply == 0 || poll.is_encrypted || poll.allow_revote)
+    {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    Ok(())
+}
+
+/// Check a delegate profile against the rules `RegisterDelegate` enforces
+pub fn validate_delegate_profile(profile: &DelegateProfile) -> Result<(), VotingError> {
+    if profile.statement_uri.is_empty() || profile.statement_uri.len() > 200 {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    if profile.voting_policy.len() > 500 || profile.max_delegators == Some(0) {
+        return Err(VotingError::InvalidPollParameters);
+    }
+
+    Ok(())
 }
 
 fn update_vote_count(
+    program_id: &Pubkey,
     vote_count_account: &AccountInfo,
     option_index: u8,
     weight: u64,
+    raw_weight: u64,
+    proxy_weight: u64,
     is_revote: bool,
-    previous_option: Option<u8>,
+    previous_vote: Option<&Vote>,
+    max_raw_weight: Option<u128>,
 ) -> Result<(), ProgramError> {
     // Deserialize vote count
-    let mut vote_count = VoteCount::try_from_slice(&vote_count_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut vote_count = load_account::<VoteCount>(program_id, vote_count_account)?;
     
     // Update counts
     if is_revote {
-        if let Some(prev_option) = previous_option {
-            // Decrement previous option count
-            if prev_option as usize < vote_count.counts.len() {
-                vote_count.counts[prev_option as usize] = vote_count.counts[prev_option as usize].saturating_sub(weight);
+        if let Some(previous) = previous_vote {
+            // Remove the previous vote, including any weight it carried by proxy
+            let prev_option = previous.option_index as usize;
+            if prev_option < vote_count.counts.len() {
+                let previous_proxy_weight: u64 = previous.proxies.iter().map(|proxy| proxy.weight).sum();
+                vote_count.counts[prev_option] = vote_count.counts[prev_option].saturating_sub(previous.weight);
+                vote_count.raw_counts[prev_option] = vote_count.raw_counts[prev_option].saturating_sub(previous.raw_weight);
+                vote_count.proxy_counts[prev_option] = vote_count.proxy_counts[prev_option].saturating_sub(previous_proxy_weight);
             }
         }
     } else {
@@ -1648,69 +4553,68 @@ fn update_vote_count(
     }
     
     // Increment new option count
-    if option_index as usize < vote_count.counts.len() {
+    if (option_index as usize) < vote_count.counts.len() {
         vote_count.counts[option_index as usize] = vote_count.counts[option_index as usize].saturating_add(weight);
+        vote_count.raw_counts[option_index as usize] = vote_count.raw_counts[option_index as usize].saturating_add(raw_weight);
+        vote_count.proxy_counts[option_index as usize] = vote_count.proxy_counts[option_index as usize].saturating_add(proxy_weight);
     }
     
-    // Update timestamp
-    vote_count.last_updated = get_bitcoin_block_height() as u64;
+    // Early close relies on the declared supply bounding the weight counted
+    if let Some(max_raw_weight) = max_raw_weight {
+        let total_raw_weight: u128 = vote_count.raw_counts.iter().map(|count| *count as u128).sum();
+        if total_raw_weight > max_raw_weight {
+            return Err(VotingError::InvalidVoteWeight.into());
+        }
+    }
+    
+    // Update timestamp and revision
+    vote_count.last_updated = runtime::block_height();
+    vote_count.revision += 1;
     
     // Serialize and save updated vote count
-    let vote_count_data = vote_count.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let vote_count_data = borsh::to_vec(&vote_count).map_err(|_| ProgramError::InvalidAccountData)?;
     vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
     
     Ok(())
 }
 
 fn update_vote_count_change(
+    program_id: &Pubkey,
     vote_count_account: &AccountInfo,
     old_option_index: u8,
     new_option_index: u8,
     weight: u64,
+    raw_weight: u64,
+    proxy_weight: u64,
 ) -> Result<(), ProgramError> {
     // Deserialize vote count
-    let mut vote_count = VoteCount::try_from_slice(&vote_count_account.data.borrow())
-        .map_err(|_| ProgramError::InvalidAccountData)?;
+    let mut vote_count = load_account::<VoteCount>(program_id, vote_count_account)?;
     
     // Decrement old option count
-    if old_option_index as usize < vote_count.counts.len() {
+    if (old_option_index as usize) < vote_count.counts.len() {
         vote_count.counts[old_option_index as usize] = vote_count.counts[old_option_index as usize].saturating_sub(weight);
+        vote_count.raw_counts[old_option_index as usize] = vote_count.raw_counts[old_option_index as usize].saturating_sub(raw_weight);
+        vote_count.proxy_counts[old_option_index as usize] = vote_count.proxy_counts[old_option_index as usize].saturating_sub(proxy_weight);
     }
     
     // Increment new option count
-    if new_option_index as usize < vote_count.counts.len() {
+    if (new_option_index as usize) < vote_count.counts.len() {
         vote_count.counts[new_option_index as usize] = vote_count.counts[new_option_index as usize].saturating_add(weight);
+        vote_count.raw_counts[new_option_index as usize] = vote_count.raw_counts[new_option_index as usize].saturating_add(raw_weight);
+        vote_count.proxy_counts[new_option_index as usize] = vote_count.proxy_counts[new_option_index as usize].saturating_add(proxy_weight);
     }
     
-    // Update timestamp
-    vote_count.last_updated = get_bitcoin_block_height() as u64;
+    // Update timestamp and revision
+    vote_count.last_updated = runtime::block_height();
+    vote_count.revision += 1;
     
     // Serialize and save updated vote count
-    let vote_count_data = vote_count.try_to_vec().map_err(|_| ProgramError::InvalidAccountData)?;
+    let vote_count_data = borsh::to_vec(&vote_count).map_err(|_| ProgramError::InvalidAccountData)?;
     vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
     
     Ok(())
 }
 
-fn get_previous_vote(
-    vote_account: &AccountInfo,
-    poll_id: u64,
-    voter: &Pubkey,
-) -> Option<u64> {
-    // Try to deserialize existing vote
-    match Vote::try_from_slice(&vote_account.data.borrow()) {
-        Ok(vote) => {
-            // Verify vote belongs to this voter and poll
-            if vote.voter == *voter && vote.poll_id == poll_id {
-                Some(vote.option_index as u64)
-            } else {
-                None
-            }
-        }
-        Err(_) => None,
-    }
-}
-
-pub fn create_poll(title: String, options: Vec<String>, end_time: u64) {
+pub fn create_poll(_title: String, _options: Vec<String>, _end_time: u64) {
     // Logic for creating a decentralized poll
 }
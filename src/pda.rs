@@ -0,0 +1,157 @@
+use arch_program::{
+    bitcoin::hashes::{sha256, Hash},
+    pubkey::Pubkey,
+};
+
+use crate::{assets::BitcoinAsset, UtxoOutpoint};
+
+// Deterministic IDs and program-derived account addresses.
+// IDs are derived from the owner's key and an owner-chosen nonce, so clients can
+// compute every ID and account address up front without any lookups.
+
+pub const POLL_SEED: &[u8] = b"poll";
+pub const VOTE_COUNT_SEED: &[u8] = b"vote_count";
+pub const VOTER_REGISTRY_SEED: &[u8] = b"voter_registry";
+pub const VOTER_RECORD_SEED: &[u8] = b"voter_record";
+pub const VOTE_SEED: &[u8] = b"vote";
+pub const DELEGATION_SEED: &[u8] = b"delegation";
+pub const RECOUNT_SEED: &[u8] = b"recount";
+pub const UTXO_CLAIM_SEED: &[u8] = b"utxo_claim";
+pub const UTXO_WEIGHT_SEED: &[u8] = b"utxo_weight";
+pub const ASSET_HOLDINGS_SEED: &[u8] = b"asset_holdings";
+pub const ASSET_CLAIM_SEED: &[u8] = b"asset_claim";
+pub const TOKEN_BALANCE_SEED: &[u8] = b"token_balance";
+pub const ESCROW_SEED: &[u8] = b"escrow";
+pub const DELEGATE_PROFILE_SEED: &[u8] = b"delegate_profile";
+
+/// ID derived from the first 8 bytes of `sha256(domain || owner || nonce)`
+pub fn derive_id(domain: &[u8], owner: &Pubkey, nonce: u64) -> u64 {
+    let mut preimage = Vec::with_capacity(domain.len() + 32 + 8);
+    preimage.extend_from_slice(domain);
+    preimage.extend_from_slice(&owner.serialize());
+    preimage.extend_from_slice(&nonce.to_le_bytes());
+
+    let digest = sha256::Hash::hash(&preimage).to_byte_array();
+    let mut id_bytes = [0u8; 8];
+    id_bytes.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(id_bytes)
+}
+
+/// ID of the poll `creator` creates with `nonce`
+pub fn poll_id(creator: &Pubkey, nonce: u64) -> u64 {
+    derive_id(POLL_SEED, creator, nonce)
+}
+
+/// ID of the delegation `delegator` creates with `nonce`
+pub fn delegation_id(delegator: &Pubkey, nonce: u64) -> u64 {
+    derive_id(DELEGATION_SEED, delegator, nonce)
+}
+
+/// Address of the poll account for `poll_id`
+pub fn find_poll_address(program_id: &Pubkey, poll_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[POLL_SEED, &poll_id.to_le_bytes()], program_id)
+}
+
+/// Address of the vote count account for `poll_id`
+pub fn find_vote_count_address(program_id: &Pubkey, poll_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[VOTE_COUNT_SEED, &poll_id.to_le_bytes()], program_id)
+}
+
+/// Address of page `page` of the voter registry for `poll_id`
+pub fn find_voter_registry_address(program_id: &Pubkey, poll_id: u64, page: u32) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[VOTER_REGISTRY_SEED, &poll_id.to_le_bytes(), &page.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Address of `voter`'s record for `poll_id`
+pub fn find_voter_record_address(program_id: &Pubkey, poll_id: u64, voter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[VOTER_RECORD_SEED, &poll_id.to_le_bytes(), &voter.serialize()],
+        program_id,
+    )
+}
+
+/// Address of `voter`'s vote account for `poll_id`
+pub fn find_vote_address(program_id: &Pubkey, poll_id: u64, voter: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[VOTE_SEED, &poll_id.to_le_bytes(), &voter.serialize()],
+        program_id,
+    )
+}
+
+/// Address of the delegation account for `delegation_id`
+pub fn find_delegation_address(program_id: &Pubkey, delegation_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DELEGATION_SEED, &delegation_id.to_le_bytes()], program_id)
+}
+
+/// Address of the recount tally account for `poll_id`
+pub fn find_recount_address(program_id: &Pubkey, poll_id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[RECOUNT_SEED, &poll_id.to_le_bytes()], program_id)
+}
+
+/// Address of the account claiming `outpoint` for a voter's weight in `poll_id`
+pub fn find_utxo_claim_address(program_id: &Pubkey, poll_id: u64, outpoint: &UtxoOutpoint) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[UTXO_CLAIM_SEED, &poll_id.to_le_bytes(), &outpoint.txid, &outpoint.vout.to_le_bytes()],
+        program_id,
+    )
+}
+
+/// Address of `owner`'s proven UTXO weight for `poll_id`
+pub fn find_utxo_weight_address(program_id: &Pubkey, poll_id: u64, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[UTXO_WEIGHT_SEED, &poll_id.to_le_bytes(), &owner.serialize()],
+        program_id,
+    )
+}
+
+/// Address of `owner`'s proven holdings of `asset` for `poll_id`
+pub fn find_asset_holdings_address(
+    program_id: &Pubkey,
+    poll_id: u64,
+    owner: &Pubkey,
+    asset: &BitcoinAsset,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ASSET_HOLDINGS_SEED, &poll_id.to_le_bytes(), &owner.serialize(), &asset_seed(asset)],
+        program_id,
+    )
+}
+
+/// Address of the account claiming `outpoint`'s holdings of `asset` in `poll_id`
+pub fn find_asset_claim_address(
+    program_id: &Pubkey,
+    poll_id: u64,
+    asset: &BitcoinAsset,
+    outpoint: &UtxoOutpoint,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[ASSET_CLAIM_SEED, &poll_id.to_le_bytes(), &asset_seed(asset), &outpoint.txid, &outpoint.vout.to_le_bytes()],
+        program_id,
+    )
+}
+
+// Inscription collections serialize to more than a seed can hold, so assets are hashed
+fn asset_seed(asset: &BitcoinAsset) -> [u8; 32] {
+    sha256::Hash::hash(&borsh::to_vec(asset).unwrap_or_default()).to_byte_array()
+}
+
+/// Address of `owner`'s attested balance of `token`
+pub fn find_token_balance_address(program_id: &Pubkey, owner: &Pubkey, token: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[TOKEN_BALANCE_SEED, &owner.serialize(), &token.serialize()],
+        program_id,
+    )
+}
+
+/// Address of `owner`'s escrow position in `token`
+pub fn find_escrow_address(program_id: &Pubkey, owner: &Pubkey, token: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[ESCROW_SEED, &owner.serialize(), &token.serialize()], program_id)
+}
+
+/// Address of `delegate`'s profile
+pub fn find_delegate_profile_address(program_id: &Pubkey, delegate: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[DELEGATE_PROFILE_SEED, &delegate.serialize()], program_id)
+}
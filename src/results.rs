@@ -0,0 +1,221 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::weight::BPS_DENOMINATOR;
+use crate::{Poll, VoteCount};
+
+// Structured poll results.
+// `GetResults` returns a Borsh-encoded `PollResults` as the instruction's return data,
+// so indexers and clients can decode the tally instead of scraping program logs.
+
+/// Layout version of `PollResults`; bumped whenever the encoding changes shape
+pub const RESULTS_VERSION: u8 = 1;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollStatus {
+    /// Voting has not started yet
+    Upcoming,
+    /// Voting is open
+    Active,
+    /// End time has passed but the poll has not been closed
+    Ended,
+    /// Poll was closed or cancelled
+    Closed,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PollOutcome {
+    /// Counts of an encrypted poll are hidden until its results are decrypted
+    Hidden,
+    /// No weight has been cast
+    NoVotes,
+    /// A single option has the highest count
+    Winner { option: u8 },
+    /// Several options share the highest count
+    Tie { options: Vec<u8> },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OptionResult {
+    /// Effective weight counted for the option
+    pub count: u64,
+    /// Weight counted before caps and dampening
+    pub raw_count: u64,
+    /// Portion of `count` carried by proxy through delegation
+    pub proxy_count: u64,
+    /// Share of all effective weight cast, in basis points
+    pub share_bps: u16,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PollResults {
+    /// Always `RESULTS_VERSION`
+    pub version: u8,
+    /// Poll the results belong to
+    pub poll_id: u64,
+    /// Voting status at the block the results were read
+    pub status: PollStatus,
+    /// Whether the counts are final
+    pub is_finalized: bool,
+    /// Leading option(s); final once `is_finalized` is set
+    pub outcome: PollOutcome,
+    /// Number of distinct voters
+    pub total_voters: u64,
+    /// Effective weight cast across all options
+    pub total_weight: u64,
+    /// Raw weight cast across all options
+    pub total_raw_weight: u64,
+    /// Raw weight cast as a share of the poll's total supply, in basis points
+    /// (only for polls that declare a total supply)
+    pub turnout_bps: Option<u16>,
+    /// Per-option results, in option order (empty while the outcome is hidden)
+    pub options: Vec<OptionResult>,
+}
+
+impl PollResults {
+    /// Decode results returned by `GetResults`
+    pub fn decode(data: &[u8]) -> Result<Self, std::io::Error> {
+        let results = Self::try_from_slice(data)?;
+
+        if results.version != RESULTS_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "unsupported results version",
+            ));
+        }
+
+        Ok(results)
+    }
+}
+
+/// Results of `poll` as of `current_time`
+pub fn poll_results(poll: &Poll, vote_count: &VoteCount, current_time: u64) -> PollResults {
+    let status = if !poll.is_active {
+        PollStatus::Closed
+    } else if current_time < poll.start_time {
+        PollStatus::Upcoming
+    } else if current_time <= poll.end_time {
+        PollStatus::Active
+    } else {
+        PollStatus::Ended
+    };
+
+    let count_at = |counts: &[u64], option: usize| counts.get(option).copied().unwrap_or(0);
+    let total_weight = (0..poll.options.len()).fold(0u64, |total, option| {
+        total.saturating_add(count_at(&vote_count.counts, option))
+    });
+    let total_raw_weight = (0..poll.options.len()).fold(0u64, |total, option| {
+        total.saturating_add(count_at(&vote_count.raw_counts, option))
+    });
+
+    let turnout_bps = (poll.weight_transform.total_supply > 0)
+        .then(|| share_bps(total_raw_weight, poll.weight_transform.total_supply));
+
+    // Encrypted polls only reveal their counts once decrypted
+    if poll.is_encrypted && !vote_count.is_finalized {
+        return PollResults {
+            version: RESULTS_VERSION,
+            poll_id: poll.id,
+            status,
+            is_finalized: false,
+            outcome: PollOutcome::Hidden,
+            total_voters: vote_count.total_voters,
+            total_weight: 0,
+            total_raw_weight: 0,
+            turnout_bps: None,
+            options: Vec::new(),
+        };
+    }
+
+    let options: Vec<OptionResult> = (0..poll.options.len())
+        .map(|option| {
+            let count = count_at(&vote_count.counts, option);
+            OptionResult {
+                count,
+                raw_count: count_at(&vote_count.raw_counts, option),
+                proxy_count: count_at(&vote_count.proxy_counts, option),
+                share_bps: share_bps(count, total_weight),
+            }
+        })
+        .collect();
+
+    let top_count = options.iter().map(|option| option.count).max().unwrap_or(0);
+    let leaders: Vec<u8> = options
+        .iter()
+        .enumerate()
+        .filter(|(_, option)| option.count == top_count)
+        .map(|(index, _)| index as u8)
+        .collect();
+
+    let outcome = match leaders.as_slice() {
+        _ if top_count == 0 => PollOutcome::NoVotes,
+        [option] => PollOutcome::Winner { option: *option },
+        _ => PollOutcome::Tie { options: leaders },
+    };
+
+    PollResults {
+        version: RESULTS_VERSION,
+        poll_id: poll.id,
+        status,
+        is_finalized: vote_count.is_finalized,
+        outcome,
+        total_voters: vote_count.total_voters,
+        total_weight,
+        total_raw_weight,
+        turnout_bps,
+        options,
+    }
+}
+
+/// Most raw weight a poll that allows early close can count: its declared `total_supply` with
+/// the full early voter bonus. `CastVote` refuses votes that would count more.
+pub fn max_raw_weight(poll: &Poll) -> u128 {
+    let max_bonus_bps = poll.early_voter_bonus as u128 * BPS_DENOMINATOR as u128 / 100;
+    poll.weight_transform.total_supply as u128 * (BPS_DENOMINATOR as u128 + max_bonus_bps) / BPS_DENOMINATOR as u128
+}
+
+/// Whether the leading option of `poll` can no longer be overtaken before the poll ends.
+///
+/// Assumes the worst case for the leader: all the raw weight the poll can still count (see
+/// `max_raw_weight`) goes to a single other option, and every vote carried by proxy is displaced
+/// by its principal onto that option too. Caps and dampening never raise a weight above its raw
+/// weight, so this bounds the weight any option can still gain. Only polls that allow early close
+/// (weighted, with a declared supply, no revotes, not encrypted) can be decided.
+/// Weight attested beyond the declared supply is refused when cast, so it cannot decide a poll.
+pub fn is_decided(poll: &Poll, vote_count: &VoteCount) -> bool {
+    if !poll.allow_early_close {
+        return false;
+    }
+
+    let count_at = |counts: &[u64], option: usize| counts.get(option).copied().unwrap_or(0) as u128;
+    let options = poll.options.len();
+
+    let total_raw_weight: u128 = (0..options).map(|option| count_at(&vote_count.raw_counts, option)).sum();
+    let total_proxy_weight: u128 = (0..options).map(|option| count_at(&vote_count.proxy_counts, option)).sum();
+
+    let remaining_weight = max_raw_weight(poll).saturating_sub(total_raw_weight);
+
+    let Some(leader) = (0..options).max_by_key(|option| count_at(&vote_count.counts, *option)) else {
+        return false;
+    };
+    let secured = count_at(&vote_count.counts, leader).saturating_sub(count_at(&vote_count.proxy_counts, leader));
+    if secured == 0 {
+        return false;
+    }
+
+    (0..options).filter(|option| *option != leader).all(|option| {
+        let reachable = count_at(&vote_count.counts, option)
+            + remaining_weight
+            + (total_proxy_weight - count_at(&vote_count.proxy_counts, option));
+        secured > reachable
+    })
+}
+
+/// `part` as a share of `total`, in basis points (rounded down, at most 100%)
+fn share_bps(part: u64, total: u64) -> u16 {
+    if total == 0 {
+        return 0;
+    }
+
+    let bps = part as u128 * BPS_DENOMINATOR as u128 / total as u128;
+    bps.min(BPS_DENOMINATOR as u128) as u16
+}
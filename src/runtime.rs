@@ -0,0 +1,109 @@
+use arch_program::{
+    account::AccountInfo,
+    bitcoin::Transaction,
+    helper,
+    input_to_sign::InputToSign,
+    program::{
+        get_account_script_pubkey, get_bitcoin_block_height, set_return_data as set_runtime_return_data,
+        set_transaction_to_sign as set_runtime_transaction_to_sign,
+    },
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+#[cfg(not(target_os = "solana"))]
+use crate::simulator;
+
+// Runtime services the program relies on. On-chain these are the Arch syscalls; on the host,
+// an active `simulator::Simulator` answers them instead and records what the program did.
+
+/// Current Bitcoin block height
+pub(crate) fn block_height() -> u64 {
+    #[cfg(not(target_os = "solana"))]
+    {
+        if let Some(height) = simulator::with_runtime(|runtime| runtime.block_height) {
+            return height;
+        }
+    }
+
+    get_bitcoin_block_height()
+}
+
+/// Append `message` to the program logs
+pub(crate) fn log(message: &str) {
+    #[cfg(not(target_os = "solana"))]
+    {
+        let logged = simulator::with_runtime(|runtime| runtime.logs.push(format!("Program log: {}", message)));
+        if logged.is_some() {
+            return;
+        }
+    }
+
+    arch_program::msg!("{}", message);
+}
+
+/// Set the data returned to the caller of the instruction
+pub(crate) fn set_return_data(data: &[u8]) {
+    #[cfg(not(target_os = "solana"))]
+    {
+        if simulator::with_runtime(|runtime| runtime.return_data = Some(data.to_vec())).is_some() {
+            return;
+        }
+    }
+
+    set_runtime_return_data(data);
+}
+
+/// Script pubkey of the Bitcoin address controlled by `account`
+pub(crate) fn account_script_pubkey(account: &Pubkey) -> Vec<u8> {
+    #[cfg(not(target_os = "solana"))]
+    {
+        if let Some(script_pubkey) = simulator::with_runtime(|runtime| runtime.script_pubkey(account)) {
+            return script_pubkey;
+        }
+    }
+
+    get_account_script_pubkey(account).to_vec()
+}
+
+/// Resize the data of `account` to `new_len` bytes; new bytes are zeroed
+pub(crate) fn realloc(account: &AccountInfo, new_len: usize) -> Result<(), ProgramError> {
+    #[cfg(not(target_os = "solana"))]
+    {
+        if let Some(result) = simulator::with_runtime(|runtime| runtime.realloc(account, new_len)) {
+            return result;
+        }
+    }
+
+    account.realloc(new_len, true)
+}
+
+/// Spend the UTXO of `account` in `transaction` and recreate it with the account's new state
+pub(crate) fn add_state_transition(transaction: &mut Transaction, account: &AccountInfo) -> Result<(), ProgramError> {
+    #[cfg(not(target_os = "solana"))]
+    {
+        if let Some(()) = simulator::with_runtime(|runtime| runtime.add_state_transition(transaction, account)) {
+            return Ok(());
+        }
+    }
+
+    // Program accounts are anchored when they are created, so their `utxo` is always set
+    #[allow(deprecated)]
+    helper::add_state_transition(transaction, account).map(|_| ())
+}
+
+/// Hand the state transition transaction to the runtime, with the inputs it has to sign
+pub(crate) fn set_transaction_to_sign(
+    accounts: &[AccountInfo],
+    transaction: &Transaction,
+    inputs_to_sign: &[InputToSign],
+) -> Result<(), ProgramError> {
+    #[cfg(not(target_os = "solana"))]
+    {
+        if let Some(()) = simulator::with_runtime(|runtime| runtime.sign(transaction, inputs_to_sign)) {
+            return Ok(());
+        }
+    }
+
+    set_runtime_transaction_to_sign(accounts, transaction, inputs_to_sign)
+}
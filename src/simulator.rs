@@ -0,0 +1,398 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+
+use arch_program::{
+    account::{AccountInfo, AccountMeta},
+    bitcoin::{Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness},
+    entrypoint::MAX_PERMITTED_DATA_INCREASE,
+    input_to_sign::InputToSign,
+    instruction::Instruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    utxo::UtxoMeta,
+};
+
+use crate::indexer::AccountSource;
+use crate::{process_instruction, ProgramAccount, ACCOUNT_VERSION};
+
+// In-process program simulator.
+// Holds the accounts and the Bitcoin block height, runs instructions through
+// `process_instruction` the way the runtime does (accounts shared between duplicate metas,
+// writes committed only when the instruction succeeds, read-only accounts left untouched) and
+// records the logs, return data and state transition transaction of every instruction.
+
+/// Value of the output recreating an account's UTXO in a simulated state transition
+pub const ACCOUNT_UTXO_VALUE: u64 = 546;
+
+/// Account held by the simulator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedAccount {
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    /// UTXO anchoring the account on Bitcoin
+    pub utxo: UtxoMeta,
+}
+
+impl SimulatedAccount {
+    /// Account at `address` that has not been written yet: empty data and a placeholder UTXO
+    pub fn new(address: &Pubkey, owner: Pubkey) -> Self {
+        SimulatedAccount { owner, lamports: 0, data: Vec::new(), utxo: UtxoMeta::from(address.serialize(), 0) }
+    }
+}
+
+/// Everything the runtime reported for one processed instruction
+#[derive(Debug, Clone)]
+pub struct ProcessedInstruction {
+    pub instruction: Instruction,
+    /// Block height the instruction ran at
+    pub block_height: u64,
+    pub result: Result<(), ProgramError>,
+    /// Program logs, as the runtime reports them (`Program log: ...`)
+    pub logs: Vec<String>,
+    /// Data set with `set_return_data`, if any
+    pub return_data: Option<Vec<u8>>,
+    /// State transition transaction handed over for signing, spending the fee input
+    pub state_transaction: Option<Transaction>,
+    /// Inputs of `state_transaction` the runtime was asked to sign
+    pub inputs_to_sign: Vec<InputToSign>,
+}
+
+/// Runtime state answering the program's syscalls while an instruction runs
+pub(crate) struct Runtime {
+    pub(crate) block_height: u64,
+    pub(crate) logs: Vec<String>,
+    pub(crate) return_data: Option<Vec<u8>>,
+    state_transaction: Option<Transaction>,
+    inputs_to_sign: Vec<InputToSign>,
+    script_pubkeys: HashMap<Pubkey, Vec<u8>>,
+    // Buffers backing the account data, by start address: base pointer and allocated size
+    buffers: HashMap<usize, (*mut u8, usize)>,
+}
+
+impl Runtime {
+    pub(crate) fn new(block_height: u64) -> Self {
+        Runtime {
+            block_height,
+            logs: Vec::new(),
+            return_data: None,
+            state_transaction: None,
+            inputs_to_sign: Vec::new(),
+            script_pubkeys: HashMap::new(),
+            buffers: HashMap::new(),
+        }
+    }
+
+    /// Let account data starting at `base` grow to `size` bytes
+    pub(crate) fn add_buffer(&mut self, base: *mut u8, size: usize) {
+        self.buffers.insert(base as usize, (base, size));
+    }
+
+    /// Run `f` with this runtime answering the program's syscalls, then hand the runtime back
+    pub(crate) fn run<R>(self, f: impl FnOnce() -> R) -> (R, Runtime) {
+        ACTIVE_RUNTIME.with(|active| *active.borrow_mut() = Some(self));
+        let result = f();
+        let runtime = ACTIVE_RUNTIME
+            .with(|active| active.borrow_mut().take())
+            .expect("runtime stays installed while the instruction runs");
+        (result, runtime)
+    }
+
+    pub(crate) fn script_pubkey(&self, account: &Pubkey) -> Vec<u8> {
+        self.script_pubkeys
+            .get(account)
+            .cloned()
+            .unwrap_or_else(|| default_script_pubkey(account))
+    }
+
+    pub(crate) fn sign(&mut self, transaction: &Transaction, inputs_to_sign: &[InputToSign]) {
+        self.state_transaction = Some(transaction.clone());
+        self.inputs_to_sign = inputs_to_sign.to_vec();
+    }
+
+    pub(crate) fn add_state_transition(&mut self, transaction: &mut Transaction, account: &AccountInfo) {
+        transaction.input.push(TxIn {
+            previous_output: OutPoint { txid: account.utxo.to_txid(), vout: account.utxo.vout() },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        });
+        transaction.output.push(TxOut {
+            value: Amount::from_sat(ACCOUNT_UTXO_VALUE),
+            script_pubkey: ScriptBuf::from_bytes(self.script_pubkey(account.key)),
+        });
+    }
+
+    // Like the runtime, accounts can grow by up to `MAX_PERMITTED_DATA_INCREASE` bytes per
+    // instruction; the simulator allocates that headroom up front and reslices within it
+    pub(crate) fn realloc(&self, account: &AccountInfo, new_len: usize) -> Result<(), ProgramError> {
+        let mut data = account.try_borrow_mut_data()?;
+        let &(base, size) = self.buffers.get(&(data.as_ptr() as usize)).ok_or(ProgramError::InvalidRealloc)?;
+        if new_len > size {
+            return Err(ProgramError::InvalidRealloc);
+        }
+
+        let old_len = data.len();
+        // SAFETY: `base` points to `size` bytes the simulator owns and leaves untouched until the
+        // instruction returns, and every slice handed to the program is derived from it
+        *data = unsafe { std::slice::from_raw_parts_mut(base, new_len) };
+        if new_len > old_len {
+            data[old_len..].fill(0);
+        }
+
+        Ok(())
+    }
+}
+
+thread_local! {
+    static ACTIVE_RUNTIME: RefCell<Option<Runtime>> = const { RefCell::new(None) };
+}
+
+/// Run `f` against the runtime of the instruction being simulated on this thread, if any
+pub(crate) fn with_runtime<R>(f: impl FnOnce(&mut Runtime) -> R) -> Option<R> {
+    ACTIVE_RUNTIME.with(|active| active.borrow_mut().as_mut().map(f))
+}
+
+/// Key-path taproot script (`OP_1 <32-byte key>`) of an account without an explicit script pubkey
+fn default_script_pubkey(account: &Pubkey) -> Vec<u8> {
+    let mut script = vec![0x51, 0x20];
+    script.extend_from_slice(&account.serialize());
+    script
+}
+
+pub struct Simulator {
+    program_id: Pubkey,
+    block_height: u64,
+    accounts: BTreeMap<Pubkey, SimulatedAccount>,
+    script_pubkeys: HashMap<Pubkey, Vec<u8>>,
+    processed: Vec<ProcessedInstruction>,
+}
+
+impl Simulator {
+    pub fn new(program_id: Pubkey) -> Self {
+        Simulator {
+            program_id,
+            block_height: 0,
+            accounts: BTreeMap::new(),
+            script_pubkeys: HashMap::new(),
+            processed: Vec::new(),
+        }
+    }
+
+    pub fn program_id(&self) -> Pubkey {
+        self.program_id
+    }
+
+    pub fn block_height(&self) -> u64 {
+        self.block_height
+    }
+
+    pub fn set_block_height(&mut self, block_height: u64) {
+        self.block_height = block_height;
+    }
+
+    pub fn advance_blocks(&mut self, blocks: u64) {
+        self.block_height += blocks;
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Option<&SimulatedAccount> {
+        self.accounts.get(address)
+    }
+
+    pub fn accounts(&self) -> impl Iterator<Item = (&Pubkey, &SimulatedAccount)> {
+        self.accounts.iter()
+    }
+
+    pub fn set_account(&mut self, address: Pubkey, account: SimulatedAccount) {
+        self.accounts.insert(address, account);
+    }
+
+    /// Register an empty wallet at `address`, for keys such as tokens or delegates that
+    /// instructions name without signing
+    pub fn set_wallet(&mut self, address: Pubkey) {
+        self.set_account(address, SimulatedAccount::new(&address, Pubkey::default()));
+    }
+
+    /// Store `data` in a program-owned account at `address`
+    pub fn set_program_account(&mut self, address: Pubkey, data: Vec<u8>) {
+        let account = SimulatedAccount { data, ..SimulatedAccount::new(&address, self.program_id) };
+        self.set_account(address, account);
+    }
+
+    /// Program account at `address` decoded as `T`, or `None` if it is missing, not yet
+    /// written, or of another type
+    pub fn load<T: ProgramAccount>(&self, address: &Pubkey) -> Option<T> {
+        let account = self.accounts.get(address).filter(|account| account.owner == self.program_id)?;
+        if account.data.len() < 2 || account.data[0] != T::ACCOUNT_TYPE as u8 || account.data[1] != ACCOUNT_VERSION {
+            return None;
+        }
+
+        // Accounts are not shrunk when rewritten, so stored data may be followed by stale bytes
+        T::deserialize(&mut &account.data[..]).ok()
+    }
+
+    /// Script pubkey `get_account_script_pubkey` returns for `account`; defaults to the
+    /// key-path taproot script of the account key
+    pub fn set_script_pubkey(&mut self, account: Pubkey, script_pubkey: Vec<u8>) {
+        self.script_pubkeys.insert(account, script_pubkey);
+    }
+
+    /// Every instruction processed so far, in order
+    pub fn processed(&self) -> &[ProcessedInstruction] {
+        &self.processed
+    }
+
+    pub fn last_processed(&self) -> Option<&ProcessedInstruction> {
+        self.processed.last()
+    }
+
+    /// Run `instruction` at the current block height.
+    ///
+    /// Signers seen for the first time are created as empty wallets. Any other account must have
+    /// been registered with `set_account` or `set_program_account`, unless it is writable and the
+    /// instruction creates it by writing its first data; otherwise the instruction fails with
+    /// `UninitializedAccount`. Signer flags are taken from the instruction; signatures are not
+    /// checked.
+    pub fn process(&mut self, instruction: &Instruction) -> Result<(), ProgramError> {
+        if instruction.program_id != self.program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        let is_missing_readonly = |meta: &AccountMeta| {
+            !self.accounts.contains_key(&meta.pubkey)
+                && !instruction.accounts.iter().any(|other| other.pubkey == meta.pubkey && (other.is_signer || other.is_writable))
+        };
+        if instruction.accounts.iter().any(is_missing_readonly) {
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        // One buffer per account; metas naming the same account share it, as they do on-chain
+        let mut keys: Vec<Pubkey> = Vec::new();
+        for meta in &instruction.accounts {
+            if !keys.contains(&meta.pubkey) {
+                keys.push(meta.pubkey);
+            }
+        }
+        let mut slots: Vec<Slot> = keys
+            .iter()
+            .map(|key| {
+                let is_signer = instruction.accounts.iter().any(|meta| meta.pubkey == *key && meta.is_signer);
+                let account = self.accounts.get(key).cloned().unwrap_or_else(|| {
+                    SimulatedAccount::new(key, if is_signer { Pubkey::default() } else { self.program_id })
+                });
+                let len = account.data.len();
+                let mut buffer = account.data;
+                buffer.resize(len + MAX_PERMITTED_DATA_INCREASE, 0);
+                Slot { key: *key, owner: account.owner, lamports: account.lamports, utxo: account.utxo, buffer, len }
+            })
+            .collect();
+
+        let mut runtime = Runtime::new(self.block_height);
+        runtime.script_pubkeys = std::mem::take(&mut self.script_pubkeys);
+        let shared: Vec<AccountInfo> = slots
+            .iter_mut()
+            .map(|Slot { key, owner, lamports, utxo, buffer, len }| {
+                let base = buffer.as_mut_ptr();
+                runtime.add_buffer(base, buffer.len());
+                // SAFETY: `len` is within the buffer, which outlives the account infos
+                let data = unsafe { std::slice::from_raw_parts_mut(base, *len) };
+                AccountInfo::new(key, lamports, data, owner, utxo, false, false, false)
+            })
+            .collect();
+        let infos: Vec<AccountInfo> = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let index = keys.iter().position(|key| *key == meta.pubkey).expect("every meta has a slot");
+                let mut info = shared[index].clone();
+                info.is_signer = meta.is_signer;
+                info.is_writable = meta.is_writable;
+                info
+            })
+            .collect();
+
+        let (mut result, runtime) = runtime.run(|| process_instruction(&self.program_id, &infos, &instruction.data));
+        self.script_pubkeys = runtime.script_pubkeys;
+
+        let written: Vec<SimulatedAccount> = shared
+            .iter()
+            .map(|info| SimulatedAccount {
+                owner: *info.owner,
+                lamports: **info.lamports.borrow(),
+                data: info.data.borrow().to_vec(),
+                utxo: *info.utxo,
+            })
+            .collect();
+        drop(infos);
+        drop(shared);
+        drop(slots);
+
+        if result.is_ok() {
+            result = self.commit(&instruction.accounts, &keys, written);
+        }
+
+        self.processed.push(ProcessedInstruction {
+            instruction: instruction.clone(),
+            block_height: self.block_height,
+            result: result.clone(),
+            logs: runtime.logs,
+            return_data: runtime.return_data,
+            state_transaction: runtime.state_transaction,
+            inputs_to_sign: runtime.inputs_to_sign,
+        });
+
+        result
+    }
+
+    // Writes back the accounts of a successful instruction. Like the runtime, it rejects the
+    // whole instruction if a read-only account or an account the program does not own changed,
+    // and it rejects accounts that were neither registered nor created by the instruction.
+    fn commit(&mut self, metas: &[AccountMeta], keys: &[Pubkey], written: Vec<SimulatedAccount>) -> Result<(), ProgramError> {
+        let mut updates = BTreeMap::new();
+
+        for (key, account) in keys.iter().zip(&written) {
+            let changed = match self.accounts.get(key) {
+                Some(previous) => previous.data != account.data || previous.lamports != account.lamports,
+                None => !account.data.is_empty() || account.lamports != 0,
+            };
+            if !changed {
+                let is_signer = metas.iter().any(|meta| meta.pubkey == *key && meta.is_signer);
+                if !self.accounts.contains_key(key) && !is_signer {
+                    return Err(ProgramError::UninitializedAccount);
+                }
+                continue;
+            }
+
+            let is_writable = metas.iter().any(|meta| meta.pubkey == *key && meta.is_writable);
+            if !is_writable || account.owner != self.program_id {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            updates.insert(*key, account.clone());
+        }
+
+        // Signers seen for the first time exist from now on, even if left empty
+        for (key, account) in keys.iter().zip(written) {
+            self.accounts.entry(*key).or_insert(account);
+        }
+        self.accounts.extend(updates);
+
+        Ok(())
+    }
+}
+
+// Storage behind the `AccountInfo` of one account while an instruction runs
+struct Slot {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    utxo: UtxoMeta,
+    buffer: Vec<u8>,
+    len: usize,
+}
+
+impl AccountSource for Simulator {
+    fn account_data(&self, address: &Pubkey) -> Option<Vec<u8>> {
+        self.accounts.get(address).map(|account| account.data.clone())
+    }
+}
@@ -1,662 +1,4236 @@
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use arch_program::{
-        account::AccountInfo,
-        program_error::ProgramError,
-        pubkey::Pubkey,
-    };
-    use borsh::{BorshDeserialize, BorshSerialize};
-    use std::cell::{RefCell, RefMut};
-    use std::collections::HashMap;
-    use std::rc::Rc;
-
-    // Mock Bitcoin block height for testing
-    const MOCK_BLOCK_HEIGHT: u64 = 1000000;
-
-    // Mock implementation of get_bitcoin_block_height
-    fn mock_get_bitcoin_block_height() -> u32 {
-        MOCK_BLOCK_HEIGHT as u32
-    }
+use super::*;
+use arch_program::{
+    account::AccountInfo,
+    entrypoint::MAX_PERMITTED_DATA_INCREASE,
+    instruction::Instruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    utxo::UtxoMeta,
+};
+use borsh::BorshDeserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use super::assets::{AssetIndexer, BitcoinAsset};
+use super::client::{self, CastVoteBuilder, CreatePollBuilder, DelegateVoteBuilder, ProxyGroup, UpdatePollBuilder};
+use super::content::{self, ContentError, ContentRef, DirectoryStore, MemoryStore};
+use super::events::{self, EventParseError, VotingEvent};
+use super::hex;
+use super::indexer::{Indexer, IndexerError, MockRpc, Snapshot};
+use super::pda;
+use super::results::{self, PollOutcome, PollResults, PollStatus, RESULTS_VERSION};
+use super::simulator::{Runtime, Simulator};
+use super::weight::{self, BonusSchedule, WeightCurve, WeightTransform, BPS_DENOMINATOR};
+
+// Mock Bitcoin block height for testing
+const MOCK_BLOCK_HEIGHT: u64 = 1000000;
+
+thread_local! {
+    // Allocations behind the data of the mock accounts created on this thread
+    static ACCOUNT_BUFFERS: RefCell<Vec<(*mut u8, usize)>> = const { RefCell::new(Vec::new()) };
+}
 
-    // Helper function to create a mock account
-    fn create_account(key: Pubkey, is_signer: bool, is_writable: bool, lamports: u64, data_size: usize) -> AccountInfo {
-        let data = vec![0; data_size];
-        AccountInfo {
-            key: Rc::new(key),
-            is_signer,
-            is_writable,
-            lamports: Rc::new(RefCell::new(lamports)),
-            data: Rc::new(RefCell::new(data)),
-            owner: Rc::new(Pubkey::default()),
-            executable: false,
-            rent_epoch: 0,
+// Runs the program at `MOCK_BLOCK_HEIGHT`, with the mock accounts able to grow like on-chain accounts
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> Result<(), ProgramError> {
+    let mut runtime = Runtime::new(MOCK_BLOCK_HEIGHT);
+    ACCOUNT_BUFFERS.with(|buffers| {
+        for &(base, size) in buffers.borrow().iter() {
+            runtime.add_buffer(base, size);
         }
+    });
+
+    runtime.run(|| super::process_instruction(program_id, accounts, instruction_data)).0
+}
+
+// Helper function to create a mock account; its storage lives for the rest of the test run
+fn create_account(key: Pubkey, is_signer: bool, is_writable: bool, lamports: u64, data_size: usize) -> AccountInfo<'static> {
+    let buffer = Box::leak(vec![0; data_size + MAX_PERMITTED_DATA_INCREASE].into_boxed_slice());
+    let base = buffer.as_mut_ptr();
+    ACCOUNT_BUFFERS.with(|buffers| buffers.borrow_mut().push((base, buffer.len())));
+    // SAFETY: the buffer is leaked, and the data slice and later reallocations are all derived from `base`
+    let data = unsafe { std::slice::from_raw_parts_mut(base, data_size) };
+
+    AccountInfo::new(
+        Box::leak(Box::new(key)),
+        Box::leak(Box::new(lamports)),
+        data,
+        Box::leak(Box::new(Pubkey::default())),
+        Box::leak(Box::new(UtxoMeta::from(key.serialize(), 0))),
+        is_signer,
+        is_writable,
+        false,
+    )
+}
+
+// Helper function to create a mock account owned by the program
+fn create_program_account(program_id: &Pubkey, key: Pubkey, is_writable: bool, data_size: usize) -> AccountInfo<'static> {
+    let account = create_account(key, false, is_writable, 100, data_size);
+    AccountInfo {
+        owner: Box::leak(Box::new(*program_id)),
+        ..account
     }
+}
 
-    // Helper function to create a mock transaction
-    fn create_mock_transaction() -> Vec<u8> {
-        // Create a simple Bitcoin transaction with one input
-        let tx = Transaction {
-            version: Version::TWO,
-            lock_time: LockTime::ZERO,
-            input: vec![bitcoin::transaction::TxIn {
-                previous_output: bitcoin::transaction::OutPoint {
-                    txid: bitcoin::hash_types::Txid::default(),
-                    vout: 0,
-                },
-                script_sig: bitcoin::script::Script::new(),
-                sequence: 0xFFFFFFFF,
-                witness: bitcoin::transaction::Witness::default(),
-            }],
-            output: vec![],
-        };
-        
-        bitcoin::consensus::serialize(&tx)
+// Decodes a program account, ignoring the unused space after the value
+fn decode<T: BorshDeserialize>(account: &AccountInfo) -> T {
+    T::deserialize(&mut &account.data.borrow()[..]).unwrap()
+}
+
+// Helper function to create a program account holding only the header of another account type
+fn create_typed_account(program_id: &Pubkey, key: Pubkey, account_type: AccountType) -> AccountInfo<'static> {
+    let account = create_program_account(program_id, key, true, 1000);
+    account.data.borrow_mut()[..2].copy_from_slice(&[account_type as u8, ACCOUNT_VERSION]);
+    account
+}
+
+// Helper function to create a mock transaction
+fn create_mock_transaction() -> Vec<u8> {
+    // Create a simple Bitcoin transaction with one input
+    let tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![bitcoin::transaction::TxIn {
+            previous_output: bitcoin::transaction::OutPoint {
+                txid: bitcoin::Txid::all_zeros(),
+                vout: 0,
+            },
+            script_sig: bitcoin::ScriptBuf::new(),
+            sequence: bitcoin::Sequence::MAX,
+            witness: bitcoin::Witness::default(),
+        }],
+        output: vec![],
+    };
+    
+    bitcoin::consensus::serialize(&tx)
+}
+
+// Helper function to create an empty vote account at the address derived for poll 12345
+fn create_vote_account(program_id: &Pubkey, voter: Pubkey) -> AccountInfo<'static> {
+    create_program_account(program_id, pda::find_vote_address(program_id, 12345, &voter).0, true, 1000)
+}
+
+// Helper function to create an empty voter record account at the address derived for poll 12345
+fn create_voter_record_account(program_id: &Pubkey, voter: Pubkey) -> AccountInfo<'static> {
+    create_program_account(program_id, pda::find_voter_record_address(program_id, 12345, &voter).0, true, 1000)
+}
+
+// Helper function to create an active delegation account giving everything to one delegate
+fn create_delegation_account(
+    program_id: &Pubkey,
+    delegator: Pubkey,
+    delegate: Pubkey,
+    poll_id: Option<u64>,
+    topic: Option<&str>,
+) -> AccountInfo<'static> {
+    let shares = vec![DelegationShare { delegate, bps: 10_000 }];
+    create_split_delegation_account(program_id, delegator, shares, poll_id, topic)
+}
+
+// Helper function to create an active delegation account split across several delegates
+fn create_split_delegation_account(
+    program_id: &Pubkey,
+    delegator: Pubkey,
+    shares: Vec<DelegationShare>,
+    poll_id: Option<u64>,
+    topic: Option<&str>,
+) -> AccountInfo<'static> {
+    let delegation_account = create_program_account(program_id, Pubkey::new_unique(), false, 1000);
+    let delegation = Delegation {
+        account_type: AccountType::Delegation,
+        version: ACCOUNT_VERSION,
+        id: 1,
+        delegator,
+        shares,
+        poll_id,
+        topic: topic.map(|topic| topic.to_string()),
+        expiration: None,
+        is_active: true,
+        registered: false,
+    };
+    
+    let delegation_data = borsh::to_vec(&delegation).unwrap();
+    delegation_account.data.borrow_mut()[..delegation_data.len()].copy_from_slice(&delegation_data);
+    delegation_account
+}
+
+// Helper function to create the base accounts for an unweighted poll that allows delegation
+fn create_delegation_poll_accounts(program_id: &Pubkey, voter_key: Pubkey, max_delegation_depth: u8) -> Vec<AccountInfo<'static>> {
+    let voter_account = create_account(voter_key, true, true, 100, 0);
+    let vote_account = create_vote_account(program_id, voter_key);
+    let poll_account = create_program_account(program_id, pda::find_poll_address(program_id, 12345).0, true, 1000);
+    let vote_count_account = create_program_account(program_id, pda::find_vote_count_address(program_id, 12345).0, true, 1000);
+    let voter_registry_account = create_program_account(program_id, pda::find_voter_registry_address(program_id, 12345, 0).0, true, 1000);
+    let voter_record_account = create_voter_record_account(program_id, voter_key);
+    
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: 12345,
+        creator: Pubkey::new_unique(),
+        title: "Delegation Poll".to_string(),
+        description: "This is a poll with liquid delegation".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT - 50,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: false,
+        is_active: true,
+        is_weighted: false,
+        allow_delegation: true, // Delegation enabled
+        is_encrypted: false,
+        decryption_key: None,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: None,
+        max_balance_age: 0,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth,
+        topic: Some("treasury".to_string()),
+        require_registered_delegates: false,
+        max_extensions: 0,
+        extension_count: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+    };
+    
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    let vote_count = VoteCount {
+        account_type: AccountType::VoteCount,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        counts: vec![0, 0],
+        raw_counts: vec![0, 0],
+        proxy_counts: vec![0, 0],
+        total_voters: 0,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        revision: 0,
+        is_finalized: false,
+    };
+    
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    let voter_registry = VoterRegistry {
+        account_type: AccountType::VoterRegistry,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        page: 0,
+        voters: Vec::new(),
+    };
+    
+    let voter_registry_data = borsh::to_vec(&voter_registry).unwrap();
+    voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
+    
+    vec![
+        voter_account,
+        vote_account,
+        poll_account,
+        vote_count_account,
+        voter_registry_account,
+        voter_record_account,
+    ]
+}
+
+// Helper function to resolve an instruction's account metas against a store of mock accounts,
+// creating program accounts the first time a key is seen
+fn accounts_for(program_id: &Pubkey, store: &mut HashMap<Pubkey, AccountInfo<'static>>, instruction: &Instruction) -> Vec<AccountInfo<'static>> {
+    instruction
+        .accounts
+        .iter()
+        .map(|meta| {
+            let account = store
+                .entry(meta.pubkey)
+                .or_insert_with(|| create_program_account(program_id, meta.pubkey, true, 1000));
+            AccountInfo {
+                is_signer: meta.is_signer,
+                is_writable: meta.is_writable,
+                ..account.clone()
+            }
+        })
+        .collect()
+}
+
+// Fixture-based stand-in for an off-chain Runes / ordinals indexer
+struct FixtureAssetIndexer {
+    holdings: HashMap<(u8, [u8; 32], u32), u128>,
+}
+
+impl FixtureAssetIndexer {
+    fn new() -> Self {
+        Self { holdings: HashMap::new() }
     }
 
-    // Test creating a poll
-    #[test]
-    fn test_create_poll() {
-        // Create mock accounts
-        let program_id = Pubkey::new_unique();
-        let creator_key = Pubkey::new_unique();
-        let poll_key = Pubkey::new_unique();
-        let vote_count_key = Pubkey::new_unique();
-        let voter_registry_key = Pubkey::new_unique();
-        
-        let creator_account = create_account(creator_key, true, true, 100, 0);
-        let poll_account = create_account(poll_key, false, true, 100, 1000);
-        let vote_count_account = create_account(vote_count_key, false, true, 100, 1000);
-        let voter_registry_account = create_account(voter_registry_key, false, true, 100, 1000);
-        
-        let accounts = vec![
-            creator_account,
-            poll_account,
-            vote_count_account,
-            voter_registry_account,
-        ];
-        
-        // Create poll instruction data
-        let create_poll_instruction = PollInstruction::CreatePoll {
-            title: "Test Poll".to_string(),
-            description: "This is a test poll".to_string(),
-            options: vec!["Option 1".to_string(), "Option 2".to_string(), "Option 3".to_string()],
-            start_time: MOCK_BLOCK_HEIGHT + 100,
-            end_time: MOCK_BLOCK_HEIGHT + 1000,
-            is_private: false,
-            allow_revote: true,
-            is_weighted: false,
-            allow_delegation: false,
-            is_encrypted: false,
-            weight_token: None,
-            early_voter_bonus: 0,
-            tx_hex: create_mock_transaction(),
-        };
-        
-        let instruction_data = create_poll_instruction.try_to_vec().unwrap();
-        
-        // Process the instruction
-        let result = process_instruction(&program_id, &accounts, &instruction_data);
-        
-        // Verify the result
-        assert!(result.is_ok(), "Failed to create poll: {:?}", result);
-        
-        // Verify the poll was created correctly
-        let poll_data = Poll::try_from_slice(&accounts[1].data.borrow()).unwrap();
-        assert_eq!(poll_data.title, "Test Poll");
-        assert_eq!(poll_data.options.len(), 3);
-        assert_eq!(poll_data.is_active, true);
-        
-        // Verify the vote count was created correctly
-        let vote_count_data = VoteCount::try_from_slice(&accounts[2].data.borrow()).unwrap();
-        assert_eq!(vote_count_data.counts.len(), 3);
-        assert_eq!(vote_count_data.total_voters, 0);
+    fn with_holding(mut self, asset: &BitcoinAsset, outpoint: &UtxoOutpoint, amount: u128) -> Self {
+        self.holdings.insert(Self::key(asset, outpoint), amount);
+        self
     }
 
-    // Test casting a vote
-    #[test]
-    fn test_cast_vote() {
-        // Create mock accounts
-        let program_id = Pubkey::new_unique();
-        let voter_key = Pubkey::new_unique();
-        let vote_key = Pubkey::new_unique();
-        let poll_key = Pubkey::new_unique();
-        let vote_count_key = Pubkey::new_unique();
-        let voter_registry_key = Pubkey::new_unique();
-        
-        let voter_account = create_account(voter_key, true, true, 100, 0);
-        let vote_account = create_account(vote_key, false, true, 100, 1000);
-        let vote_count_account = create_account(vote_count_key, false, true, 100, 1000);
-        let voter_registry_account = create_account(voter_registry_key, false, true, 100, 1000);
-        
-        // Create and initialize poll account
-        let poll_account = create_account(poll_key, false, true, 100, 1000);
-        let poll = Poll {
-            id: 12345,
-            creator: Pubkey::new_unique(),
-            title: "Test Poll".to_string(),
-            description: "This is a test poll".to_string(),
-            options: vec!["Option 1".to_string(), "Option 2".to_string(), "Option 3".to_string()],
-            start_time: MOCK_BLOCK_HEIGHT - 50, // Poll has already started
-            end_time: MOCK_BLOCK_HEIGHT + 1000,
-            is_private: false,
-            allow_revote: true,
-            is_active: true,
-            is_weighted: false,
-            allow_delegation: false,
-            is_encrypted: false,
-            decryption_key: None,
-            weight_token: None,
-            early_voter_bonus: 0,
+    fn key(asset: &BitcoinAsset, outpoint: &UtxoOutpoint) -> (u8, [u8; 32], u32) {
+        let tag = match asset {
+            BitcoinAsset::Rune { .. } => 0,
+            BitcoinAsset::InscriptionCollection { .. } => 1,
         };
-        
-        let poll_data = poll.try_to_vec().unwrap();
-        poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
-        
-        // Initialize vote count account
-        let vote_count = VoteCount {
-            poll_id: 12345,
-            counts: vec![0, 0, 0],
-            total_voters: 0,
-            last_updated: MOCK_BLOCK_HEIGHT,
-            is_finalized: false,
-        };
-        
-        let vote_count_data = vote_count.try_to_vec().unwrap();
-        vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
-        
-        // Initialize voter registry account
-        let voter_registry = VoterRegistry {
-            poll_id: 12345,
-            voter_bitmap: vec![0; 1024],
-            voters: Vec::new(),
+        (tag, outpoint.txid, outpoint.vout)
+    }
+}
+
+impl AssetIndexer for FixtureAssetIndexer {
+    fn asset_amount(&self, asset: &BitcoinAsset, outpoint: &UtxoOutpoint) -> u128 {
+        self.holdings.get(&Self::key(asset, outpoint)).copied().unwrap_or(0)
+    }
+}
+
+// Test creating a poll
+#[test]
+fn test_create_poll() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let poll_id = pda::poll_id(&creator_key, 7);
+    let poll_key = pda::find_poll_address(&program_id, poll_id).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, poll_id).0;
+    let voter_registry_key = pda::find_voter_registry_address(&program_id, poll_id, 0).0;
+    
+    let creator_account = create_account(creator_key, true, true, 100, 0);
+    let poll_account = create_program_account(&program_id, poll_key, true, 1000);
+    let vote_count_account = create_program_account(&program_id, vote_count_key, true, 1000);
+    let voter_registry_account = create_program_account(&program_id, voter_registry_key, true, 1000);
+    
+    let accounts = vec![
+        creator_account,
+        poll_account,
+        vote_count_account,
+        voter_registry_account,
+    ];
+    
+    // Create poll instruction data
+    let create_poll_instruction = PollInstruction::CreatePoll {
+        title: "Test Poll".to_string(),
+        description: "This is a test poll".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string(), "Option 3".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT + 100,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: true,
+        is_weighted: false,
+        allow_delegation: false,
+        is_encrypted: false,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: None,
+        max_balance_age: 0,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+        id_nonce: 7,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&create_poll_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the result
+    assert!(result.is_ok(), "Failed to create poll: {:?}", result);
+    
+    // Verify the poll was created correctly
+    let poll_data = decode::<Poll>(&accounts[1]);
+    assert_eq!(poll_data.id, poll_id);
+    assert_eq!(poll_data.title, "Test Poll");
+    assert_eq!(poll_data.options.len(), 3);
+    assert!(poll_data.is_active);
+    
+    // Verify the vote count was created correctly
+    let vote_count_data = decode::<VoteCount>(&accounts[2]);
+    assert_eq!(vote_count_data.counts.len(), 3);
+    assert_eq!(vote_count_data.total_voters, 0);
+}
+
+// Test casting a vote
+#[test]
+fn test_cast_vote() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let vote_key = pda::find_vote_address(&program_id, 12345, &voter_key).0;
+    let poll_key = pda::find_poll_address(&program_id, 12345).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, 12345).0;
+    let voter_registry_key = pda::find_voter_registry_address(&program_id, 12345, 0).0;
+    
+    let voter_account = create_account(voter_key, true, true, 100, 0);
+    let vote_account = create_program_account(&program_id, vote_key, true, 1000);
+    let vote_count_account = create_program_account(&program_id, vote_count_key, true, 1000);
+    let voter_registry_account = create_program_account(&program_id, voter_registry_key, true, 1000);
+    let voter_record_account = create_voter_record_account(&program_id, voter_key);
+    
+    // Create and initialize poll account
+    let poll_account = create_program_account(&program_id, poll_key, true, 1000);
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: 12345,
+        creator: Pubkey::new_unique(),
+        title: "Test Poll".to_string(),
+        description: "This is a test poll".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string(), "Option 3".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT - 50, // Poll has already started
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: true,
+        is_active: true,
+        is_weighted: false,
+        allow_delegation: false,
+        is_encrypted: false,
+        decryption_key: None,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: None,
+        max_balance_age: 0,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        extension_count: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+    };
+    
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    // Initialize vote count account
+    let vote_count = VoteCount {
+        account_type: AccountType::VoteCount,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        counts: vec![0, 0, 0],
+        raw_counts: vec![0, 0, 0],
+        proxy_counts: vec![0, 0, 0],
+        total_voters: 0,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        revision: 0,
+        is_finalized: false,
+    };
+    
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    // Initialize voter registry account
+    let voter_registry = VoterRegistry {
+        account_type: AccountType::VoterRegistry,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        page: 0,
+        voters: Vec::new(),
+    };
+    
+    let voter_registry_data = borsh::to_vec(&voter_registry).unwrap();
+    voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
+    
+    let accounts = vec![
+        voter_account,
+        vote_account,
+        poll_account,
+        vote_count_account,
+        voter_registry_account,
+        voter_record_account,
+    ];
+    
+    // Create cast vote instruction data
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 1, // Vote for Option 2
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&cast_vote_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the result
+    assert!(result.is_ok(), "Failed to cast vote: {:?}", result);
+    
+    // Verify the vote was recorded correctly
+    let vote_data = decode::<Vote>(&accounts[1]);
+    assert_eq!(vote_data.poll_id, 12345);
+    assert_eq!(vote_data.option_index, 1);
+    
+    // Verify the vote count was updated correctly
+    let vote_count_data = decode::<VoteCount>(&accounts[3]);
+    assert_eq!(vote_count_data.counts, vec![0, 1, 0]);
+    assert_eq!(vote_count_data.total_voters, 1);
+    
+    // Verify the voter registry was updated correctly
+    let voter_registry_data = decode::<VoterRegistry>(&accounts[4]);
+    assert_eq!(voter_registry_data.voters.len(), 1);
+    assert_eq!(voter_registry_data.voters[0], voter_key);
+}
+
+// Test changing a vote
+#[test]
+fn test_change_vote() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let vote_key = pda::find_vote_address(&program_id, 12345, &voter_key).0;
+    let poll_key = pda::find_poll_address(&program_id, 12345).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, 12345).0;
+    
+    let voter_account = create_account(voter_key, true, true, 100, 0);
+    let vote_account = create_program_account(&program_id, vote_key, true, 1000);
+    let vote_count_account = create_program_account(&program_id, vote_count_key, true, 1000);
+    
+    // Create and initialize poll account
+    let poll_account = create_program_account(&program_id, poll_key, true, 1000);
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: 12345,
+        creator: Pubkey::new_unique(),
+        title: "Test Poll".to_string(),
+        description: "This is a test poll".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string(), "Option 3".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT - 50, // Poll has already started
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: true, // Allow revoting
+        is_active: true,
+        is_weighted: false,
+        allow_delegation: false,
+        is_encrypted: false,
+        decryption_key: None,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: None,
+        max_balance_age: 0,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        extension_count: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+    };
+    
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    // Initialize vote account with an existing vote
+    let vote = Vote {
+        account_type: AccountType::Vote,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        voter: voter_key,
+        option_index: 1, // Currently voted for Option 2
+        timestamp: MOCK_BLOCK_HEIGHT,
+        weight: 1,
+        raw_weight: 1,
+        delegated_to: None,
+        proxies: Vec::new(),
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+    };
+    
+    let vote_data = borsh::to_vec(&vote).unwrap();
+    vote_account.data.borrow_mut()[..vote_data.len()].copy_from_slice(&vote_data);
+    
+    // Initialize vote count account
+    let vote_count = VoteCount {
+        account_type: AccountType::VoteCount,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        counts: vec![0, 1, 0], // One vote for Option 2
+        raw_counts: vec![0, 1, 0],
+        proxy_counts: vec![0, 0, 0],
+        total_voters: 1,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        revision: 0,
+        is_finalized: false,
+    };
+    
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    let accounts = vec![
+        voter_account,
+        vote_account,
+        poll_account,
+        vote_count_account,
+    ];
+    
+    // Create change vote instruction data
+    let change_vote_instruction = PollInstruction::ChangeVote {
+        poll_id: 12345,
+        new_option_index: 2, // Change vote to Option 3
+        new_encrypted_data: None,
+        new_zk_proof: None,
+        new_nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&change_vote_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the result
+    assert!(result.is_ok(), "Failed to change vote: {:?}", result);
+    
+    // Verify the vote was updated correctly
+    let vote_data = decode::<Vote>(&accounts[1]);
+    assert_eq!(vote_data.option_index, 2);
+    
+    // Verify the vote count was updated correctly
+    let vote_count_data = decode::<VoteCount>(&accounts[3]);
+    assert_eq!(vote_count_data.counts, vec![0, 0, 1]);
+    assert_eq!(vote_count_data.total_voters, 1);
+}
+
+// Test closing a poll
+#[test]
+fn test_close_poll() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let caller_key = Pubkey::new_unique();
+    let poll_key = pda::find_poll_address(&program_id, 12345).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, 12345).0;
+    
+    let caller_account = create_account(caller_key, true, true, 100, 0);
+    let vote_count_account = create_program_account(&program_id, vote_count_key, true, 1000);
+    
+    // Create and initialize poll account
+    let poll_account = create_program_account(&program_id, poll_key, true, 1000);
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: 12345,
+        creator: caller_key, // Caller is the creator
+        title: "Test Poll".to_string(),
+        description: "This is a test poll".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string(), "Option 3".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT - 50,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: true,
+        is_active: true,
+        is_weighted: false,
+        allow_delegation: false,
+        is_encrypted: false,
+        decryption_key: None,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: None,
+        max_balance_age: 0,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        extension_count: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+    };
+    
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    // Initialize vote count account
+    let vote_count = VoteCount {
+        account_type: AccountType::VoteCount,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        counts: vec![5, 10, 3], // Some votes already cast
+        raw_counts: vec![5, 10, 3],
+        proxy_counts: vec![0, 0, 0],
+        total_voters: 18,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        revision: 0,
+        is_finalized: false,
+    };
+    
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    let accounts = vec![
+        caller_account,
+        poll_account,
+        vote_count_account,
+    ];
+    
+    // Create close poll instruction data
+    let close_poll_instruction = PollInstruction::ClosePoll {
+        poll_id: 12345,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&close_poll_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the result
+    assert!(result.is_ok(), "Failed to close poll: {:?}", result);
+    
+    // Verify the poll was closed correctly
+    let poll_data = decode::<Poll>(&accounts[1]);
+    assert!(!poll_data.is_active);
+    
+    // Verify the vote count was finalized
+    let vote_count_data = decode::<VoteCount>(&accounts[2]);
+    assert!(vote_count_data.is_finalized);
+}
+
+// Test delegating a vote
+#[test]
+fn test_delegate_vote() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let delegator_key = Pubkey::new_unique();
+    let delegation_key = pda::find_delegation_address(&program_id, pda::delegation_id(&delegator_key, 0)).0;
+    let delegate_key = Pubkey::new_unique();
+    
+    let delegator_account = create_account(delegator_key, true, true, 100, 0);
+    let delegation_account = create_program_account(&program_id, delegation_key, true, 1000);
+    let delegate_account = create_account(delegate_key, false, false, 100, 0);
+    let profile_account = create_program_account(&program_id, pda::find_delegate_profile_address(&program_id, &delegate_key).0, true, 1000);
+    let poll_account = create_delegation_poll_accounts(&program_id, Pubkey::new_unique(), 1)[2].clone();
+    
+    let accounts = vec![
+        delegator_account,
+        delegation_account,
+        delegate_account,
+        profile_account,
+        poll_account,
+    ];
+    
+    // Create delegate vote instruction data
+    let delegate_vote_instruction = PollInstruction::DelegateVote {
+        poll_id: Some(12345),
+        topic: None,
+        shares: vec![], // Everything to the single delegate
+        expiration: Some(MOCK_BLOCK_HEIGHT + 2000),
+        id_nonce: 0,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&delegate_vote_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the result
+    assert!(result.is_ok(), "Failed to delegate vote: {:?}", result);
+    
+    // Verify the delegation was created correctly
+    let delegation_data = decode::<Delegation>(&accounts[1]);
+    assert_eq!(delegation_data.delegator, delegator_key);
+    assert_eq!(delegation_data.shares, vec![DelegationShare { delegate: delegate_key, bps: 10_000 }]);
+    assert_eq!(delegation_data.poll_id, Some(12345));
+    assert!(delegation_data.is_active);
+    assert!(!delegation_data.registered);
+}
+
+// Test weighted voting
+#[test]
+fn test_weighted_voting() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let vote_key = pda::find_vote_address(&program_id, 12345, &voter_key).0;
+    let poll_key = pda::find_poll_address(&program_id, 12345).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, 12345).0;
+    let voter_registry_key = pda::find_voter_registry_address(&program_id, 12345, 0).0;
+    let token_key = Pubkey::new_unique();
+    let token_balance_key = pda::find_token_balance_address(&program_id, &voter_key, &token_key).0;
+    let oracle_key = Pubkey::new_unique();
+    
+    let voter_account = create_account(voter_key, true, true, 100, 0);
+    let vote_account = create_program_account(&program_id, vote_key, true, 1000);
+    let vote_count_account = create_program_account(&program_id, vote_count_key, true, 1000);
+    let voter_registry_account = create_program_account(&program_id, voter_registry_key, true, 1000);
+    let voter_record_account = create_voter_record_account(&program_id, voter_key);
+    let token_balance_account = create_program_account(&program_id, token_balance_key, true, 1000);
+    let token_account = create_account(token_key, false, false, 100, 0);
+    
+    // Create and initialize poll account with weighted voting
+    let poll_account = create_program_account(&program_id, poll_key, true, 1000);
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: 12345,
+        creator: Pubkey::new_unique(),
+        title: "Weighted Poll".to_string(),
+        description: "This is a weighted poll".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT - 50,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: false,
+        is_active: true,
+        is_weighted: true, // Weighted voting enabled
+        allow_delegation: false,
+        is_encrypted: false,
+        decryption_key: None,
+        weight_token: Some(token_key), // Token for weighting
+        early_voter_bonus: 10, // 10% early voter bonus
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: Some(oracle_key), // Oracle attesting balances
+        max_balance_age: 100,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        extension_count: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+    };
+    
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    // Initialize vote count account
+    let vote_count = VoteCount {
+        account_type: AccountType::VoteCount,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        counts: vec![0, 0],
+        raw_counts: vec![0, 0],
+        proxy_counts: vec![0, 0],
+        total_voters: 0,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        revision: 0,
+        is_finalized: false,
+    };
+    
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    // Initialize voter registry account
+    let voter_registry = VoterRegistry {
+        account_type: AccountType::VoterRegistry,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        page: 0,
+        voters: Vec::new(),
+    };
+    
+    let voter_registry_data = borsh::to_vec(&voter_registry).unwrap();
+    voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
+    
+    // Initialize token balance account
+    let token_balance = TokenBalance {
+        account_type: AccountType::TokenBalance,
+        version: ACCOUNT_VERSION,
+        owner: voter_key,
+        token: token_key,
+        amount: 100, // Voter has 100 tokens
+        last_updated: MOCK_BLOCK_HEIGHT,
+        attested_by: oracle_key,
+        locked_amount: 0,
+    };
+    
+    let token_balance_data = borsh::to_vec(&token_balance).unwrap();
+    token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
+    
+    let accounts = vec![
+        voter_account,
+        vote_account,
+        poll_account,
+        vote_count_account,
+        voter_registry_account,
+        voter_record_account,
+        token_balance_account,
+        token_account,
+    ];
+    
+    // Create cast vote instruction data
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 0, // Vote for Option 1
+        weight: None, // Use token balance for weight
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&cast_vote_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the result
+    assert!(result.is_ok(), "Failed to cast weighted vote: {:?}", result);
+    
+    // Verify the vote was recorded with correct weight (including early voter bonus)
+    let vote_data = decode::<Vote>(&accounts[1]);
+    assert!(vote_data.weight > 100, "Weight should be greater than token balance due to early voter bonus");
+    
+    // Verify the vote count was updated correctly
+    let vote_count_data = decode::<VoteCount>(&accounts[3]);
+    assert_eq!(vote_count_data.counts[0], vote_data.weight);
+    assert_eq!(vote_count_data.raw_counts[0], vote_data.raw_weight);
+    assert_eq!(vote_count_data.total_voters, 1);
+}
+
+// Test that token balances must be attested by an oracle
+#[test]
+fn test_update_token_balance_requires_oracle() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let owner_key = Pubkey::new_unique();
+    let token_key = Pubkey::new_unique();
+    let token_balance_key = pda::find_token_balance_address(&program_id, &owner_key, &token_key).0;
+    let oracle_key = Pubkey::new_unique();
+    
+    let owner_account = create_account(owner_key, true, true, 100, 0);
+    let token_balance_account = create_program_account(&program_id, token_balance_key, true, 1000);
+    let token_account = create_account(token_key, false, false, 100, 0);
+    let oracle_account = create_account(oracle_key, false, false, 100, 0); // Oracle did not sign
+    
+    let accounts = vec![
+        owner_account,
+        token_balance_account,
+        token_account,
+        oracle_account,
+    ];
+    
+    let update_instruction = PollInstruction::UpdateTokenBalance {
+        token: token_key,
+        amount: 1_000_000,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&update_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the self-reported balance was rejected
+    assert_eq!(result, Err(VotingError::UnverifiedTokenBalance.into()));
+}
+
+// Test that stale token balances are rejected when casting a weighted vote
+#[test]
+fn test_weighted_voting_rejects_stale_balance() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let token_key = Pubkey::new_unique();
+    let oracle_key = Pubkey::new_unique();
+    
+    let voter_account = create_account(voter_key, true, true, 100, 0);
+    let vote_account = create_vote_account(&program_id, voter_key);
+    let poll_account = create_program_account(&program_id, pda::find_poll_address(&program_id, 12345).0, true, 1000);
+    let vote_count_account = create_program_account(&program_id, pda::find_vote_count_address(&program_id, 12345).0, true, 1000);
+    let voter_registry_account = create_program_account(&program_id, pda::find_voter_registry_address(&program_id, 12345, 0).0, true, 1000);
+    let voter_record_account = create_voter_record_account(&program_id, voter_key);
+    let token_balance_account = create_program_account(
+        &program_id,
+        pda::find_token_balance_address(&program_id, &voter_key, &token_key).0,
+        false,
+        1000,
+    );
+    
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: 12345,
+        creator: Pubkey::new_unique(),
+        title: "Weighted Poll".to_string(),
+        description: "This is a weighted poll".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT - 50,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: false,
+        is_active: true,
+        is_weighted: true,
+        allow_delegation: false,
+        is_encrypted: false,
+        decryption_key: None,
+        weight_token: Some(token_key),
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: Some(oracle_key),
+        max_balance_age: 10, // Balances older than 10 blocks are stale
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        extension_count: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+    };
+    
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    let vote_count = VoteCount {
+        account_type: AccountType::VoteCount,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        counts: vec![0, 0],
+        raw_counts: vec![0, 0],
+        proxy_counts: vec![0, 0],
+        total_voters: 0,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        revision: 0,
+        is_finalized: false,
+    };
+    
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    let voter_registry = VoterRegistry {
+        account_type: AccountType::VoterRegistry,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        page: 0,
+        voters: Vec::new(),
+    };
+    
+    let voter_registry_data = borsh::to_vec(&voter_registry).unwrap();
+    voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
+    
+    // Balance was attested 20 blocks ago
+    let token_balance = TokenBalance {
+        account_type: AccountType::TokenBalance,
+        version: ACCOUNT_VERSION,
+        owner: voter_key,
+        token: token_key,
+        amount: 100,
+        last_updated: MOCK_BLOCK_HEIGHT - 20,
+        attested_by: oracle_key,
+        locked_amount: 0,
+    };
+    
+    let token_balance_data = borsh::to_vec(&token_balance).unwrap();
+    token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
+    
+    let accounts = vec![
+        voter_account,
+        vote_account,
+        poll_account,
+        vote_count_account,
+        voter_registry_account,
+        voter_record_account,
+        token_balance_account,
+    ];
+    
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 0,
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&cast_vote_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the stale balance was rejected
+    assert_eq!(result, Err(VotingError::StaleTokenBalance.into()));
+}
+
+// Test that a UTXO cannot be counted twice for the same poll
+#[test]
+fn test_prove_utxo_weight_rejects_reused_outpoint() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    
+    let voter_account = create_account(voter_key, true, true, 100, 0);
+    let utxo_weight_account =
+        create_program_account(&program_id, pda::find_utxo_weight_address(&program_id, 12345, &voter_key).0, true, 1000);
+    let poll_account = create_program_account(&program_id, pda::find_poll_address(&program_id, 12345).0, false, 1000);
+    
+    // Create and initialize poll account weighted by Bitcoin holdings
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: 12345,
+        creator: Pubkey::new_unique(),
+        title: "BTC Weighted Poll".to_string(),
+        description: "Weight comes from BTC held".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT - 50,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: false,
+        is_active: true,
+        is_weighted: true,
+        allow_delegation: false,
+        is_encrypted: false,
+        decryption_key: None,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: None,
+        max_balance_age: 0,
+        weight_source: WeightSource::BitcoinUtxo,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        extension_count: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+    };
+    
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    // Fee transaction: input 0 pays the fee, input 1 is the UTXO being proven
+    let mut fee_tx: Transaction = bitcoin::consensus::deserialize(&create_mock_transaction()).unwrap();
+    let mut weight_input = fee_tx.input[0].clone();
+    weight_input.previous_output.vout = 1;
+    fee_tx.input.push(weight_input);
+    
+    // The UTXO was already claimed by another voter
+    let outpoint = UtxoOutpoint {
+        txid: fee_tx.input[1].previous_output.txid.to_byte_array(),
+        vout: 1,
+    };
+    let claim_account = create_program_account(&program_id, pda::find_utxo_claim_address(&program_id, 12345, &outpoint).0, true, 1000);
+    let claim = UtxoClaim {
+        account_type: AccountType::UtxoClaim,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        outpoint,
+        owner: Pubkey::new_unique(),
+    };
+    
+    let claim_data = borsh::to_vec(&claim).unwrap();
+    claim_account.data.borrow_mut()[..claim_data.len()].copy_from_slice(&claim_data);
+    
+    let accounts = vec![
+        voter_account,
+        utxo_weight_account,
+        poll_account,
+        claim_account,
+    ];
+    
+    let prove_instruction = PollInstruction::ProveUtxoWeight {
+        poll_id: 12345,
+        prev_txs: vec![],
+        tx_hex: bitcoin::consensus::serialize(&fee_tx),
+    };
+    
+    let instruction_data = borsh::to_vec(&prove_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the reused outpoint was rejected
+    assert_eq!(result, Err(VotingError::UtxoAlreadyCounted.into()));
+}
+
+// Test that proving UTXO weight spends the proven UTXOs, signed by the voter, pays their value
+// back to the voter and claims them
+#[test]
+fn test_prove_utxo_weight_refunds_proven_inputs() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    let builder = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        1,
+        "BTC Weighted Poll".to_string(),
+        vec!["Yes".to_string(), "No".to_string()],
+        MOCK_BLOCK_HEIGHT + 10,
+        MOCK_BLOCK_HEIGHT + 100,
+    )
+    .weighted(WeightSource::BitcoinUtxo, None);
+    let poll_id = builder.poll_id();
+    let result = simulator.process(&builder.build(simulator.block_height(), create_mock_transaction()).unwrap());
+    assert!(result.is_ok(), "Failed to create poll: {:?}", result);
+    
+    // Two outputs paying to the voter, proven as inputs 1 and 2 of the fee transaction
+    let voter_script = bitcoin::ScriptBuf::from_bytes(vec![0x51; 34]);
+    simulator.set_script_pubkey(voter_key, voter_script.to_bytes());
+    let funding_tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![],
+        output: [40_000, 60_000]
+            .into_iter()
+            .map(|sats| bitcoin::TxOut { value: bitcoin::Amount::from_sat(sats), script_pubkey: voter_script.clone() })
+            .collect(),
+    };
+    let mut fee_tx: Transaction = bitcoin::consensus::deserialize(&create_mock_transaction()).unwrap();
+    for vout in 0..2 {
+        let mut input = fee_tx.input[0].clone();
+        input.previous_output = bitcoin::OutPoint { txid: funding_tx.compute_txid(), vout };
+        fee_tx.input.push(input);
+    }
+    
+    let weight_account = pda::find_utxo_weight_address(&program_id, poll_id, &voter_key).0;
+    let prove = |voter: &Pubkey| {
+        client::prove_utxo_weight(
+            &program_id,
+            voter,
+            poll_id,
+            vec![bitcoin::consensus::serialize(&funding_tx)],
+            bitcoin::consensus::serialize(&fee_tx),
+        )
+        .unwrap()
+    };
+    let result = simulator.process(&prove(&voter_key));
+    assert!(result.is_ok(), "Failed to prove UTXO weight: {:?}", result);
+    assert_eq!(simulator.load::<UtxoWeight>(&weight_account).unwrap().total_sats, 100_000);
+    
+    // The state transition ends with the fee input and both proven inputs, all signed by the voter
+    let processed = simulator.last_processed().unwrap().clone();
+    let state_transaction = processed.state_transaction.as_ref().unwrap();
+    let first_spent = state_transaction.input.len() - fee_tx.input.len();
+    assert_eq!(state_transaction.input[first_spent..], fee_tx.input[..]);
+    let signed: Vec<(usize, Pubkey)> =
+        processed.inputs_to_sign.iter().map(|input| (input.index as usize, input.signer)).collect();
+    assert_eq!(signed, (first_spent..first_spent + 3).map(|index| (index, voter_key)).collect::<Vec<_>>());
+    
+    // The last output returns the full proven value to the voter; only the fee input is spent
+    let refund = state_transaction.output.last().unwrap();
+    assert_eq!(refund.value.to_sat(), 100_000);
+    assert_eq!(refund.script_pubkey, voter_script);
+    let refund_outpoint = bitcoin::OutPoint {
+        txid: state_transaction.compute_txid(),
+        vout: state_transaction.output.len() as u32 - 1,
+    };
+    
+    // Each outpoint is claimed at its derived address, so no voter can prove it again
+    let claim_address = pda::find_utxo_claim_address(&program_id, poll_id, &UtxoOutpoint { txid: funding_tx.compute_txid().to_byte_array(), vout: 1 }).0;
+    assert_eq!(simulator.load::<UtxoClaim>(&claim_address).unwrap().owner, voter_key);
+    
+    let result = simulator.process(&prove(&Pubkey::new_unique()));
+    assert_eq!(result, Err(VotingError::UtxoAlreadyCounted.into()));
+    
+    // Nor can the voter prove the refunded value a second time
+    let mut refund_fee_tx: Transaction = bitcoin::consensus::deserialize(&create_mock_transaction()).unwrap();
+    let mut refund_input = refund_fee_tx.input[0].clone();
+    refund_input.previous_output = refund_outpoint;
+    refund_fee_tx.input.push(refund_input);
+    let prove_refund = client::prove_utxo_weight(
+        &program_id,
+        &voter_key,
+        poll_id,
+        vec![bitcoin::consensus::serialize(state_transaction)],
+        bitcoin::consensus::serialize(&refund_fee_tx),
+    )
+    .unwrap();
+    let result = simulator.process(&prove_refund);
+    assert_eq!(result, Err(VotingError::UtxoAlreadyCounted.into()));
+    assert_eq!(simulator.load::<UtxoWeight>(&weight_account).unwrap().total_sats, 100_000);
+}
+
+// Test totalling Rune and inscription holdings against the fixture indexer
+#[test]
+fn test_asset_holdings_with_fixture_indexer() {
+    let rune = BitcoinAsset::Rune { block: 840000, tx: 1 };
+    let collection = BitcoinAsset::InscriptionCollection { parent_txid: [7; 32], parent_index: 0 };
+    let outpoint_a = UtxoOutpoint { txid: [1; 32], vout: 0 };
+    let outpoint_b = UtxoOutpoint { txid: [2; 32], vout: 3 };
+    
+    let indexer = FixtureAssetIndexer::new()
+        .with_holding(&rune, &outpoint_a, 1_500)
+        .with_holding(&rune, &outpoint_b, 500)
+        .with_holding(&collection, &outpoint_b, 2);
+    
+    // Rune holdings are summed across outpoints
+    let total = assets::total_holdings(&indexer, &rune, &[outpoint_a.clone(), outpoint_b.clone()]);
+    assert_eq!(total, Ok(2_000));
+    
+    // Inscriptions count one per inscription held
+    let total = assets::total_holdings(&indexer, &collection, std::slice::from_ref(&outpoint_b));
+    assert_eq!(total, Ok(2));
+    
+    // An outpoint that holds none of the asset invalidates the proof
+    let total = assets::total_holdings(&indexer, &collection, &[outpoint_a, outpoint_b]);
+    assert_eq!(total, Err(VotingError::InvalidAssetProof));
+    
+    // Rune amounts that do not fit a vote weight are rejected
+    let huge = UtxoOutpoint { txid: [3; 32], vout: 0 };
+    let indexer = FixtureAssetIndexer::new().with_holding(&rune, &huge, u128::from(u64::MAX) + 1);
+    assert_eq!(assets::total_holdings(&indexer, &rune, &[huge]), Err(VotingError::InvalidVoteWeight));
+}
+
+// Test that each outpoint's asset holdings are claimed at their own address and counted once
+#[test]
+fn test_asset_claims_count_each_outpoint_once() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let oracle_key = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let rune = BitcoinAsset::Rune { block: 840_000, tx: 1 };
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    let builder = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        1,
+        "Rune Weighted Poll".to_string(),
+        vec!["Yes".to_string(), "No".to_string()],
+        MOCK_BLOCK_HEIGHT + 10,
+        MOCK_BLOCK_HEIGHT + 100,
+    )
+    .weighted(WeightSource::BitcoinAsset(rune), None)
+    .balance_oracle(oracle_key, 100);
+    let poll_id = builder.poll_id();
+    let result = simulator.process(&builder.build(simulator.block_height(), create_mock_transaction()).unwrap());
+    assert!(result.is_ok(), "Failed to create poll: {:?}", result);
+    let poll = simulator.load::<Poll>(&pda::find_poll_address(&program_id, poll_id).0).unwrap();
+    
+    // One output paying to the voter holds the Rune
+    let voter_script = bitcoin::ScriptBuf::from_bytes(vec![0x51; 34]);
+    simulator.set_script_pubkey(voter_key, voter_script.to_bytes());
+    let funding_tx = Transaction {
+        version: Version::TWO,
+        lock_time: LockTime::ZERO,
+        input: vec![],
+        output: vec![bitcoin::TxOut { value: bitcoin::Amount::from_sat(546), script_pubkey: voter_script }],
+    };
+    let outpoint = UtxoOutpoint { txid: funding_tx.compute_txid().to_byte_array(), vout: 0 };
+    let prove = |voter: &Pubkey| {
+        let proof = client::AssetProof {
+            asset: rune,
+            holdings: vec![AssetHolding { outpoint: outpoint.clone(), amount: 1_500 }],
+            prev_txs: vec![bitcoin::consensus::serialize(&funding_tx)],
         };
-        
-        let voter_registry_data = voter_registry.try_to_vec().unwrap();
-        voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
-        
+        client::prove_asset_holdings(&program_id, voter, &poll, proof, create_mock_transaction()).unwrap()
+    };
+    
+    let result = simulator.process(&prove(&voter_key));
+    assert!(result.is_ok(), "Failed to prove holdings: {:?}", result);
+    let holdings_address = pda::find_asset_holdings_address(&program_id, poll_id, &voter_key, &rune).0;
+    assert_eq!(simulator.load::<AssetHoldings>(&holdings_address).unwrap().amount, 1_500);
+    let claim_address = pda::find_asset_claim_address(&program_id, poll_id, &rune, &outpoint).0;
+    assert_eq!(simulator.load::<AssetClaim>(&claim_address).unwrap().owner, voter_key);
+    
+    // Neither the voter nor anyone else can count the outpoint again
+    for voter in [voter_key, Pubkey::new_unique()] {
+        let result = simulator.process(&prove(&voter));
+        assert_eq!(result, Err(VotingError::UtxoAlreadyCounted.into()));
+    }
+    assert_eq!(simulator.load::<AssetHoldings>(&holdings_address).unwrap().amount, 1_500);
+}
+
+// Test that voters without the eligibility asset cannot vote
+#[test]
+fn test_cast_vote_requires_eligibility_asset() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let oracle_key = Pubkey::new_unique();
+    let rune = BitcoinAsset::Rune { block: 840000, tx: 1 };
+    
+    let voter_account = create_account(voter_key, true, true, 100, 0);
+    let vote_account = create_vote_account(&program_id, voter_key);
+    let poll_account = create_program_account(&program_id, pda::find_poll_address(&program_id, 12345).0, true, 1000);
+    let vote_count_account = create_program_account(&program_id, pda::find_vote_count_address(&program_id, 12345).0, true, 1000);
+    let voter_registry_account = create_program_account(&program_id, pda::find_voter_registry_address(&program_id, 12345, 0).0, true, 1000);
+    let voter_record_account = create_voter_record_account(&program_id, voter_key);
+    let holdings_account = create_program_account(
+        &program_id,
+        pda::find_asset_holdings_address(&program_id, 12345, &voter_key, &rune).0,
+        false,
+        1000,
+    );
+    
+    // Create and initialize a poll restricted to Rune holders
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: 12345,
+        creator: Pubkey::new_unique(),
+        title: "Rune Holder Poll".to_string(),
+        description: "Only holders of the community Rune may vote".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT - 50,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: false,
+        is_active: true,
+        is_weighted: false,
+        allow_delegation: false,
+        is_encrypted: false,
+        decryption_key: None,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: Some(oracle_key),
+        max_balance_age: 100,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: Some(rune),
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        extension_count: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+    };
+    
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    let vote_count = VoteCount {
+        account_type: AccountType::VoteCount,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        counts: vec![0, 0],
+        raw_counts: vec![0, 0],
+        proxy_counts: vec![0, 0],
+        total_voters: 0,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        revision: 0,
+        is_finalized: false,
+    };
+    
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    let voter_registry = VoterRegistry {
+        account_type: AccountType::VoterRegistry,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        page: 0,
+        voters: Vec::new(),
+    };
+    
+    let voter_registry_data = borsh::to_vec(&voter_registry).unwrap();
+    voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
+    
+    // Holdings were proven for a different asset
+    let holdings = AssetHoldings {
+        account_type: AccountType::AssetHoldings,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        owner: voter_key,
+        asset: BitcoinAsset::Rune { block: 840000, tx: 2 },
+        outpoints: vec![UtxoOutpoint { txid: [1; 32], vout: 0 }],
+        amount: 1_000,
+        last_updated: MOCK_BLOCK_HEIGHT,
+    };
+    
+    let holdings_data = borsh::to_vec(&holdings).unwrap();
+    holdings_account.data.borrow_mut()[..holdings_data.len()].copy_from_slice(&holdings_data);
+    
+    let accounts = vec![
+        voter_account,
+        vote_account,
+        poll_account,
+        vote_count_account,
+        voter_registry_account,
+        voter_record_account,
+        holdings_account,
+    ];
+    
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 0,
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&cast_vote_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the voter was rejected
+    assert_eq!(result, Err(VotingError::NotEligible.into()));
+}
+
+// Test vote-escrowed weight decay over block height
+#[test]
+fn test_escrow_weight_decays() {
+    let mut position = EscrowPosition {
+        account_type: AccountType::EscrowPosition,
+        version: ACCOUNT_VERSION,
+        owner: Pubkey::new_unique(),
+        token: Pubkey::new_unique(),
+        amount: 1_000,
+        unlock_height: MOCK_BLOCK_HEIGHT + MAX_ESCROW_LOCK_BLOCKS,
+        attested_by: Pubkey::new_unique(),
+        last_updated: MOCK_BLOCK_HEIGHT,
+    };
+    
+    // A maximum lock carries full weight
+    assert_eq!(weight::escrow_weight(&position, MOCK_BLOCK_HEIGHT), 1_000);
+    
+    // Halfway to unlock carries half the weight
+    assert_eq!(weight::escrow_weight(&position, MOCK_BLOCK_HEIGHT + MAX_ESCROW_LOCK_BLOCKS / 2), 500);
+    
+    // An expired lock carries no weight
+    assert_eq!(weight::escrow_weight(&position, MOCK_BLOCK_HEIGHT + MAX_ESCROW_LOCK_BLOCKS), 0);
+    
+    // Large balances do not overflow
+    position.amount = u64::MAX;
+    assert_eq!(weight::escrow_weight(&position, MOCK_BLOCK_HEIGHT), u64::MAX);
+}
+
+// Test that escrowed tokens cannot be withdrawn before the unlock height
+#[test]
+fn test_withdraw_escrow_before_unlock() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let owner_key = Pubkey::new_unique();
+    let token_key = Pubkey::new_unique();
+    let oracle_key = Pubkey::new_unique();
+    
+    let owner_account = create_account(owner_key, true, true, 100, 0);
+    let escrow_account =
+        create_program_account(&program_id, pda::find_escrow_address(&program_id, &owner_key, &token_key).0, true, 1000);
+    let token_balance_account = create_program_account(
+        &program_id,
+        pda::find_token_balance_address(&program_id, &owner_key, &token_key).0,
+        true,
+        1000,
+    );
+    
+    // Initialize escrow position locked for another 100 blocks
+    let position = EscrowPosition {
+        account_type: AccountType::EscrowPosition,
+        version: ACCOUNT_VERSION,
+        owner: owner_key,
+        token: token_key,
+        amount: 100,
+        unlock_height: MOCK_BLOCK_HEIGHT + 100,
+        attested_by: oracle_key,
+        last_updated: MOCK_BLOCK_HEIGHT,
+    };
+    
+    let position_data = borsh::to_vec(&position).unwrap();
+    escrow_account.data.borrow_mut()[..position_data.len()].copy_from_slice(&position_data);
+    
+    // Initialize token balance with the escrowed amount locked
+    let token_balance = TokenBalance {
+        account_type: AccountType::TokenBalance,
+        version: ACCOUNT_VERSION,
+        owner: owner_key,
+        token: token_key,
+        amount: 100,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        attested_by: oracle_key,
+        locked_amount: 100,
+    };
+    
+    let token_balance_data = borsh::to_vec(&token_balance).unwrap();
+    token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
+    
+    let accounts = vec![
+        owner_account,
+        escrow_account,
+        token_balance_account,
+    ];
+    
+    let withdraw_instruction = PollInstruction::WithdrawEscrow {
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&withdraw_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the withdrawal was rejected and the tokens stay locked
+    assert_eq!(result, Err(VotingError::EscrowLocked.into()));
+    
+    let token_balance_data = decode::<TokenBalance>(&accounts[2]);
+    assert_eq!(token_balance_data.locked_amount, 100);
+}
+
+// Test that escrow locks need a recent attestation, and that later attestations are recorded
+// even below the locked amount
+#[test]
+fn test_lock_escrow_requires_fresh_balance() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let owner_key = Pubkey::new_unique();
+    let token_key = Pubkey::new_unique();
+    let oracle_key = Pubkey::new_unique();
+    
+    let owner_account = create_account(owner_key, true, true, 100, 0);
+    let escrow_account =
+        create_program_account(&program_id, pda::find_escrow_address(&program_id, &owner_key, &token_key).0, true, 1000);
+    let token_balance_account = create_program_account(
+        &program_id,
+        pda::find_token_balance_address(&program_id, &owner_key, &token_key).0,
+        true,
+        1000,
+    );
+    
+    // Initialize a balance attested too long ago
+    let mut token_balance = TokenBalance {
+        account_type: AccountType::TokenBalance,
+        version: ACCOUNT_VERSION,
+        owner: owner_key,
+        token: token_key,
+        amount: 100,
+        last_updated: MOCK_BLOCK_HEIGHT - MAX_ESCROW_BALANCE_AGE - 1,
+        attested_by: oracle_key,
+        locked_amount: 0,
+    };
+    let token_balance_data = borsh::to_vec(&token_balance).unwrap();
+    token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
+    
+    let accounts = vec![owner_account.clone(), escrow_account, token_balance_account.clone()];
+    let lock_instruction = PollInstruction::LockEscrow {
+        token: token_key,
+        amount: 100,
+        unlock_height: MOCK_BLOCK_HEIGHT + 100,
+        tx_hex: create_mock_transaction(),
+    };
+    let instruction_data = borsh::to_vec(&lock_instruction).unwrap();
+    
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert_eq!(result, Err(VotingError::StaleTokenBalance.into()));
+    
+    // A recent attestation can be locked
+    token_balance.last_updated = MOCK_BLOCK_HEIGHT - MAX_ESCROW_BALANCE_AGE;
+    let token_balance_data = borsh::to_vec(&token_balance).unwrap();
+    token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
+    
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert!(result.is_ok(), "Failed to lock escrow: {:?}", result);
+    assert_eq!(decode::<TokenBalance>(&token_balance_account).locked_amount, 100);
+    
+    // Re-attesting below the locked amount records the sale; the lock stays in place
+    let update_accounts = |amount: u64| {
         let accounts = vec![
-            voter_account,
-            vote_account,
-            poll_account,
-            vote_count_account,
-            voter_registry_account,
+            owner_account.clone(),
+            token_balance_account.clone(),
+            create_account(token_key, false, false, 100, 0),
+            create_account(oracle_key, true, false, 100, 0),
         ];
-        
-        // Create cast vote instruction data
-        let cast_vote_instruction = PollInstruction::CastVote {
+        let update_instruction = PollInstruction::UpdateTokenBalance {
+            token: token_key,
+            amount,
+            tx_hex: create_mock_transaction(),
+        };
+        process_instruction(&program_id, &accounts, &borsh::to_vec(&update_instruction).unwrap())
+    };
+    let result = update_accounts(50);
+    assert!(result.is_ok(), "Failed to update token balance: {:?}", result);
+    assert_eq!(decode::<TokenBalance>(&token_balance_account).amount, 50);
+    
+    let result = update_accounts(150);
+    assert!(result.is_ok(), "Failed to update token balance: {:?}", result);
+    
+    let token_balance_data = decode::<TokenBalance>(&token_balance_account);
+    assert_eq!(token_balance_data.amount, 150);
+    assert_eq!(token_balance_data.locked_amount, 100);
+}
+
+// Test that escrow weight only counts while a recent attestation shows the tokens are still held
+#[test]
+fn test_escrow_weight_requires_backing_balance() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let token_key = Pubkey::new_unique();
+    let oracle_key = Pubkey::new_unique();
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    let builder = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        1,
+        "Escrow Weighted Poll".to_string(),
+        vec!["Yes".to_string(), "No".to_string()],
+        MOCK_BLOCK_HEIGHT,
+        MOCK_BLOCK_HEIGHT + 100,
+    )
+    .weighted(WeightSource::VoteEscrow, Some(token_key))
+    .balance_oracle(oracle_key, 10)
+    .allow_revote();
+    let poll_id = builder.poll_id();
+    let poll_key = pda::find_poll_address(&program_id, poll_id).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, poll_id).0;
+    simulator.set_wallet(token_key);
+    let result = simulator.process(&builder.build(simulator.block_height(), create_mock_transaction()).unwrap());
+    assert!(result.is_ok(), "Failed to create poll: {:?}", result);
+    
+    // Attest 100 tokens and lock them all
+    let attest = |amount: u64| {
+        client::update_token_balance(&program_id, &voter_key, &token_key, amount, &oracle_key, create_mock_transaction()).unwrap()
+    };
+    assert!(simulator.process(&attest(100)).is_ok());
+    let lock = client::lock_escrow(
+        &program_id,
+        &voter_key,
+        &token_key,
+        100,
+        MOCK_BLOCK_HEIGHT + MAX_ESCROW_LOCK_BLOCKS,
+        create_mock_transaction(),
+    )
+    .unwrap();
+    let result = simulator.process(&lock);
+    assert!(result.is_ok(), "Failed to lock escrow: {:?}", result);
+    
+    let cast_vote = |simulator: &Simulator| {
+        let poll: Poll = simulator.load(&poll_key).unwrap();
+        let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+        let mut builder = CastVoteBuilder::new(program_id, voter_key, &poll, &vote_count, 0);
+        let voter_record: Option<VoterRecord> =
+            simulator.load(&pda::find_voter_record_address(&program_id, poll_id, &voter_key).0);
+        if let Some(voter_record) = &voter_record {
+            builder = builder.voter_record(voter_record);
+        }
+        builder.build(simulator.block_height(), create_mock_transaction()).unwrap()
+    };
+    
+    // The full lock counts while the attested balance covers it
+    let result = simulator.process(&cast_vote(&simulator));
+    assert!(result.is_ok(), "Failed to vote with escrow: {:?}", result);
+    let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+    assert_eq!(vote_count.counts[0], 100);
+    
+    // Once the attestation is older than the poll allows, the escrow no longer counts
+    simulator.advance_blocks(11);
+    assert_eq!(simulator.process(&cast_vote(&simulator)), Err(VotingError::StaleTokenBalance.into()));
+    
+    // After the tokens are sold, the oracle's attestation is recorded and the escrow stops counting
+    assert!(simulator.process(&attest(40)).is_ok());
+    assert_eq!(simulator.process(&cast_vote(&simulator)), Err(VotingError::EscrowNotBacked.into()));
+    
+    // Buying them back restores the escrow weight
+    assert!(simulator.process(&attest(100)).is_ok());
+    let result = simulator.process(&cast_vote(&simulator));
+    assert!(result.is_ok(), "Failed to vote with escrow: {:?}", result);
+}
+
+// Test linear early voter bonus at the schedule boundaries
+#[test]
+fn test_linear_bonus_boundaries() {
+    let start = MOCK_BLOCK_HEIGHT;
+    let end = MOCK_BLOCK_HEIGHT + 1000;
+    let linear = BonusSchedule::Linear;
+    
+    // Full bonus at (and before) the start, none at (and after) the end
+    assert_eq!(weight::bonus_bps(10, &linear, start, end, start - 1), 1_000);
+    assert_eq!(weight::bonus_bps(10, &linear, start, end, start), 1_000);
+    assert_eq!(weight::bonus_bps(10, &linear, start, end, start + 500), 500);
+    assert_eq!(weight::bonus_bps(10, &linear, start, end, end - 1), 1);
+    assert_eq!(weight::bonus_bps(10, &linear, start, end, end), 0);
+    assert_eq!(weight::bonus_bps(10, &linear, start, end, end + 1), 0);
+    
+    // Maximum bonus doubles the weight at the start
+    assert_eq!(weight::bonus_bps(100, &linear, start, end, start), BPS_DENOMINATOR);
+    
+    // No bonus configured, or a zero-length poll, gives no bonus
+    assert_eq!(weight::bonus_bps(0, &linear, start, end, start), 0);
+    assert_eq!(weight::bonus_bps(10, &linear, start, start, start), 0);
+    
+    // Matches the documented examples: 110 at the start, 105 halfway, 100 at the end
+    assert_eq!(weight::apply_early_voter_bonus(100, 10, &linear, start, end, start), Ok(110));
+    assert_eq!(weight::apply_early_voter_bonus(100, 10, &linear, start, end, start + 500), Ok(105));
+    assert_eq!(weight::apply_early_voter_bonus(100, 10, &linear, start, end, end), Ok(100));
+}
+
+// Test step early voter bonus at the step boundaries
+#[test]
+fn test_step_bonus_boundaries() {
+    let start = MOCK_BLOCK_HEIGHT;
+    let end = MOCK_BLOCK_HEIGHT + 1000;
+    let step = BonusSchedule::Step { steps: 4 };
+    
+    // 20% bonus, dropping by 5% every 250 blocks
+    assert_eq!(weight::bonus_bps(20, &step, start, end, start), 2_000);
+    assert_eq!(weight::bonus_bps(20, &step, start, end, start + 249), 2_000);
+    assert_eq!(weight::bonus_bps(20, &step, start, end, start + 250), 1_500);
+    assert_eq!(weight::bonus_bps(20, &step, start, end, start + 500), 1_000);
+    assert_eq!(weight::bonus_bps(20, &step, start, end, start + 750), 500);
+    assert_eq!(weight::bonus_bps(20, &step, start, end, end - 1), 500);
+    assert_eq!(weight::bonus_bps(20, &step, start, end, end), 0);
+    
+    // A single step keeps the full bonus for the whole poll
+    let single = BonusSchedule::Step { steps: 1 };
+    assert_eq!(weight::bonus_bps(20, &single, start, end, end - 1), 2_000);
+    
+    // More steps than blocks still never exceeds the configured bonus
+    let fine = BonusSchedule::Step { steps: 255 };
+    assert_eq!(weight::bonus_bps(20, &fine, start, start + 10, start), 2_000);
+    assert_eq!(weight::bonus_bps(20, &fine, start, start + 10, start + 10), 0);
+}
+
+// Test exponential early voter bonus at the half-life boundaries
+#[test]
+fn test_exponential_bonus_boundaries() {
+    let start = MOCK_BLOCK_HEIGHT;
+    let end = MOCK_BLOCK_HEIGHT + 10_000;
+    let exponential = BonusSchedule::Exponential { half_life: 100 };
+    
+    // 40% bonus halving every 100 blocks
+    assert_eq!(weight::bonus_bps(40, &exponential, start, end, start), 4_000);
+    assert_eq!(weight::bonus_bps(40, &exponential, start, end, start + 50), 3_000);
+    assert_eq!(weight::bonus_bps(40, &exponential, start, end, start + 100), 2_000);
+    assert_eq!(weight::bonus_bps(40, &exponential, start, end, start + 200), 1_000);
+    
+    // The bonus eventually reaches zero and never underflows
+    assert_eq!(weight::bonus_bps(40, &exponential, start, end, start + 1_300), 0);
+    assert_eq!(weight::bonus_bps(40, &exponential, start, end, start + 9_999), 0);
+    assert_eq!(weight::bonus_bps(40, &exponential, start, u64::MAX, u64::MAX - 1), 0);
+    
+    // No bonus once the poll has ended
+    assert_eq!(weight::bonus_bps(40, &exponential, start, start + 50, start + 50), 0);
+}
+
+// Test bonus rounding and overflow handling
+#[test]
+fn test_bonus_rounding_and_overflow() {
+    let start = MOCK_BLOCK_HEIGHT;
+    let end = MOCK_BLOCK_HEIGHT + 1000;
+    let linear = BonusSchedule::Linear;
+    
+    // The bonus is rounded up so early voters always get some of it
+    assert_eq!(weight::apply_early_voter_bonus(1, 10, &linear, start, end, start), Ok(2));
+    assert_eq!(weight::apply_early_voter_bonus(1, 10, &linear, start, end, end - 1), Ok(2));
+    
+    // Zero weight stays zero
+    assert_eq!(weight::apply_early_voter_bonus(0, 100, &linear, start, end, start), Ok(0));
+    
+    // Large balances do not overflow silently
+    assert_eq!(weight::apply_early_voter_bonus(u64::MAX, 0, &linear, start, end, start), Ok(u64::MAX));
+    assert_eq!(weight::apply_early_voter_bonus(u64::MAX / 2, 100, &linear, start, end, start), Ok(u64::MAX - 1));
+    assert_eq!(
+        weight::apply_early_voter_bonus(u64::MAX, 1, &linear, start, end, start),
+        Err(VotingError::InvalidVoteWeight)
+    );
+}
+
+// Test bonus schedule validation
+#[test]
+fn test_bonus_schedule_validation() {
+    assert!(weight::is_valid_schedule(&BonusSchedule::Linear));
+    assert!(weight::is_valid_schedule(&BonusSchedule::Step { steps: 1 }));
+    assert!(!weight::is_valid_schedule(&BonusSchedule::Step { steps: 0 }));
+    assert!(weight::is_valid_schedule(&BonusSchedule::Exponential { half_life: 1 }));
+    assert!(!weight::is_valid_schedule(&BonusSchedule::Exponential { half_life: 0 }));
+}
+
+// Test per-voter weight caps and dampening curves
+#[test]
+fn test_weight_transforms() {
+    // No transform leaves the weight untouched
+    assert_eq!(weight::apply_transform(1_000_000, &WeightTransform::NONE), 1_000_000);
+    
+    // Hard cap per voter
+    let capped = WeightTransform { max_weight: Some(500), ..WeightTransform::NONE };
+    assert_eq!(weight::apply_transform(499, &capped), 499);
+    assert_eq!(weight::apply_transform(500, &capped), 500);
+    assert_eq!(weight::apply_transform(1_000_000, &capped), 500);
+    
+    // Percentage-of-supply cap: 5% of 1,000,000
+    let supply_capped = WeightTransform {
+        max_supply_bps: Some(500),
+        total_supply: 1_000_000,
+        ..WeightTransform::NONE
+    };
+    assert_eq!(weight::apply_transform(49_999, &supply_capped), 49_999);
+    assert_eq!(weight::apply_transform(u64::MAX, &supply_capped), 50_000);
+    
+    // Square-root weighting
+    let sqrt = WeightTransform { curve: WeightCurve::SquareRoot, ..WeightTransform::NONE };
+    assert_eq!(weight::apply_transform(0, &sqrt), 0);
+    assert_eq!(weight::apply_transform(1, &sqrt), 1);
+    assert_eq!(weight::apply_transform(99, &sqrt), 9);
+    assert_eq!(weight::apply_transform(100, &sqrt), 10);
+    assert_eq!(weight::apply_transform(u64::MAX, &sqrt), u32::MAX as u64);
+    
+    // Logarithmic weighting
+    let log = WeightTransform { curve: WeightCurve::Logarithmic, ..WeightTransform::NONE };
+    assert_eq!(weight::apply_transform(0, &log), 0);
+    assert_eq!(weight::apply_transform(1, &log), 1);
+    assert_eq!(weight::apply_transform(1_023, &log), 10);
+    assert_eq!(weight::apply_transform(1_024, &log), 11);
+    assert_eq!(weight::apply_transform(u64::MAX, &log), 64);
+    
+    // Caps apply before the curve
+    let capped_sqrt = WeightTransform { max_weight: Some(10_000), curve: WeightCurve::SquareRoot, ..WeightTransform::NONE };
+    assert_eq!(weight::apply_transform(1_000_000, &capped_sqrt), 100);
+    
+    // Invalid transforms are rejected
+    assert!(weight::is_valid_transform(&supply_capped));
+    assert!(!weight::is_valid_transform(&WeightTransform { max_weight: Some(0), ..WeightTransform::NONE }));
+    assert!(!weight::is_valid_transform(&WeightTransform { max_supply_bps: Some(500), ..WeightTransform::NONE }));
+    assert!(!weight::is_valid_transform(&WeightTransform {
+        max_supply_bps: Some(BPS_DENOMINATOR as u16 + 1),
+        total_supply: 1_000_000,
+        ..WeightTransform::NONE
+    }));
+}
+
+// Test that a delegate's vote carries weight delegated to it directly and through a chain
+#[test]
+fn test_cast_vote_carries_delegation_chain() {
+    let program_id = Pubkey::new_unique();
+    let principal_a = Pubkey::new_unique();
+    let principal_b = Pubkey::new_unique();
+    let principal_d = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    
+    // A -> B -> voter, and D -> voter
+    let mut accounts = create_delegation_poll_accounts(&program_id, voter_key, 2);
+    accounts.push(create_delegation_account(&program_id, principal_a, principal_b, Some(12345), None));
+    accounts.push(create_delegation_account(&program_id, principal_b, voter_key, None, None));
+    accounts.push(create_voter_record_account(&program_id, principal_a));
+    accounts.push(create_delegation_account(&program_id, principal_d, voter_key, Some(12345), None));
+    accounts.push(create_voter_record_account(&program_id, principal_d));
+    
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 1,
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&cast_vote_instruction).unwrap();
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert!(result.is_ok(), "Failed to cast delegated vote: {:?}", result);
+    
+    // The voter's own vote plus one for each principal
+    let vote_data = decode::<Vote>(&accounts[1]);
+    assert_eq!(vote_data.weight, 3);
+    assert_eq!(vote_data.proxies.len(), 2);
+    assert_eq!(vote_data.proxies[0].principal, principal_a);
+    assert_eq!(vote_data.proxies[0].depth, 2);
+    assert_eq!(vote_data.proxies[1].principal, principal_d);
+    assert_eq!(vote_data.proxies[1].depth, 1);
+    
+    let vote_count_data = decode::<VoteCount>(&accounts[3]);
+    assert_eq!(vote_count_data.counts, vec![0, 3]);
+    assert_eq!(vote_count_data.proxy_counts, vec![0, 2]);
+    assert_eq!(vote_count_data.total_voters, 1);
+    
+    // Each principal's record names the voter as its carrier
+    let principal_record = decode::<VoterRecord>(&accounts[8]);
+    assert!(!principal_record.has_voted);
+    assert_eq!(principal_record.carried.len(), 1);
+    assert_eq!(principal_record.carried[0].delegate, voter_key);
+}
+
+// Test that delegation chains which are too deep or cyclic are rejected
+#[test]
+fn test_delegation_chain_rejects_depth_and_cycles() {
+    let program_id = Pubkey::new_unique();
+    let principal_a = Pubkey::new_unique();
+    let principal_b = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 0,
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    let instruction_data = borsh::to_vec(&cast_vote_instruction).unwrap();
+    
+    // A -> B -> voter is two hops, but the poll only allows one
+    let mut accounts = create_delegation_poll_accounts(&program_id, voter_key, 1);
+    accounts.push(create_delegation_account(&program_id, principal_a, principal_b, None, None));
+    accounts.push(create_delegation_account(&program_id, principal_b, voter_key, None, None));
+    
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert_eq!(result, Err(VotingError::DelegationTooDeep.into()));
+    
+    // A -> B -> A never reaches the voter
+    let mut accounts = create_delegation_poll_accounts(&program_id, voter_key, 3);
+    accounts.push(create_delegation_account(&program_id, principal_a, principal_b, None, None));
+    accounts.push(create_delegation_account(&program_id, principal_b, principal_a, None, None));
+    accounts.push(create_delegation_account(&program_id, principal_a, voter_key, None, None));
+    
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert_eq!(result, Err(VotingError::DelegationCycle.into()));
+}
+
+// Test that a delegator's direct vote overrides the slice their delegate carried, in either order
+#[test]
+fn test_direct_vote_overrides_delegate_slice() {
+    let program_id = Pubkey::new_unique();
+    let delegator_key = Pubkey::new_unique();
+    let delegate_key = Pubkey::new_unique();
+    
+    let vote_instruction = |option_index: u8| {
+        let instruction = PollInstruction::CastVote {
             poll_id: 12345,
-            option_index: 1, // Vote for Option 2
+            option_index,
             weight: None,
             encrypted_data: None,
             zk_proof: None,
             nonce: None,
             tx_hex: create_mock_transaction(),
         };
-        
-        let instruction_data = cast_vote_instruction.try_to_vec().unwrap();
-        
-        // Process the instruction
-        let result = process_instruction(&program_id, &accounts, &instruction_data);
-        
-        // Verify the result
-        assert!(result.is_ok(), "Failed to cast vote: {:?}", result);
-        
-        // Verify the vote was recorded correctly
-        let vote_data = Vote::try_from_slice(&accounts[1].data.borrow()).unwrap();
-        assert_eq!(vote_data.poll_id, 12345);
-        assert_eq!(vote_data.option_index, 1);
-        
-        // Verify the vote count was updated correctly
-        let vote_count_data = VoteCount::try_from_slice(&accounts[3].data.borrow()).unwrap();
-        assert_eq!(vote_count_data.counts, vec![0, 1, 0]);
-        assert_eq!(vote_count_data.total_voters, 1);
-        
-        // Verify the voter registry was updated correctly
-        let voter_registry_data = VoterRegistry::try_from_slice(&accounts[4].data.borrow()).unwrap();
-        assert_eq!(voter_registry_data.voters.len(), 1);
-        assert_eq!(voter_registry_data.voters[0], voter_key);
-    }
+        borsh::to_vec(&instruction).unwrap()
+    };
+    
+    // Delegate votes first, carrying the delegator's weight
+    let delegator_record = create_voter_record_account(&program_id, delegator_key);
+    let mut delegate_accounts = create_delegation_poll_accounts(&program_id, delegate_key, 1);
+    delegate_accounts.push(create_delegation_account(&program_id, delegator_key, delegate_key, Some(12345), None));
+    delegate_accounts.push(delegator_record.clone());
+    
+    let result = process_instruction(&program_id, &delegate_accounts, &vote_instruction(1));
+    assert!(result.is_ok(), "Failed to cast delegate vote: {:?}", result);
+    
+    let vote_count_data = decode::<VoteCount>(&delegate_accounts[3]);
+    assert_eq!(vote_count_data.counts, vec![0, 2]);
+    assert_eq!(vote_count_data.proxy_counts, vec![0, 1]);
+    
+    // Delegator then votes directly, passing the delegate's vote account
+    let delegator_accounts = vec![
+        create_account(delegator_key, true, true, 100, 0),
+        create_vote_account(&program_id, delegator_key),
+        delegate_accounts[2].clone(),
+        delegate_accounts[3].clone(),
+        delegate_accounts[4].clone(),
+        delegator_record.clone(),
+        delegate_accounts[1].clone(),
+    ];
+    
+    let result = process_instruction(&program_id, &delegator_accounts, &vote_instruction(0));
+    assert!(result.is_ok(), "Failed to cast overriding vote: {:?}", result);
+    
+    let delegate_vote = decode::<Vote>(&delegate_accounts[1]);
+    assert_eq!(delegate_vote.weight, 1);
+    assert!(delegate_vote.proxies.is_empty());
+    
+    let vote_count_data = decode::<VoteCount>(&delegate_accounts[3]);
+    assert_eq!(vote_count_data.counts, vec![1, 1]);
+    assert_eq!(vote_count_data.proxy_counts, vec![0, 0]);
+    assert_eq!(vote_count_data.total_voters, 2);
+    
+    let delegator_record_data = decode::<VoterRecord>(&delegator_record);
+    assert!(delegator_record_data.has_voted);
+    assert!(delegator_record_data.carried.is_empty());
+    
+    // When the delegator votes first, the delegate's vote skips their slice
+    let mut accounts = create_delegation_poll_accounts(&program_id, delegator_key, 1);
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(0));
+    assert!(result.is_ok(), "Failed to cast direct vote: {:?}", result);
+    
+    let delegator_record = accounts[5].clone();
+    accounts[0] = create_account(delegate_key, true, true, 100, 0);
+    accounts[1] = create_vote_account(&program_id, delegate_key);
+    accounts[5] = create_voter_record_account(&program_id, delegate_key);
+    accounts.push(create_delegation_account(&program_id, delegator_key, delegate_key, None, None));
+    accounts.push(delegator_record);
+    
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(1));
+    assert!(result.is_ok(), "Failed to cast delegate vote: {:?}", result);
+    
+    let delegate_vote = decode::<Vote>(&accounts[1]);
+    assert_eq!(delegate_vote.weight, 1);
+    assert!(delegate_vote.proxies.is_empty());
+    
+    let vote_count_data = decode::<VoteCount>(&accounts[3]);
+    assert_eq!(vote_count_data.counts, vec![1, 1]);
+}
 
-    // Test changing a vote
-    #[test]
-    fn test_change_vote() {
-        // Create mock accounts
-        let program_id = Pubkey::new_unique();
-        let voter_key = Pubkey::new_unique();
-        let vote_key = Pubkey::new_unique();
-        let poll_key = Pubkey::new_unique();
-        let vote_count_key = Pubkey::new_unique();
-        
-        let voter_account = create_account(voter_key, true, true, 100, 0);
-        let vote_account = create_account(vote_key, false, true, 100, 1000);
-        let vote_count_account = create_account(vote_count_key, false, true, 100, 1000);
-        
-        // Create and initialize poll account
-        let poll_account = create_account(poll_key, false, true, 100, 1000);
-        let poll = Poll {
-            id: 12345,
-            creator: Pubkey::new_unique(),
-            title: "Test Poll".to_string(),
-            description: "This is a test poll".to_string(),
-            options: vec!["Option 1".to_string(), "Option 2".to_string(), "Option 3".to_string()],
-            start_time: MOCK_BLOCK_HEIGHT - 50, // Poll has already started
-            end_time: MOCK_BLOCK_HEIGHT + 1000,
-            is_private: false,
-            allow_revote: true, // Allow revoting
-            is_active: true,
-            is_weighted: false,
-            allow_delegation: false,
-            is_encrypted: false,
-            decryption_key: None,
-            weight_token: None,
-            early_voter_bonus: 0,
-        };
-        
-        let poll_data = poll.try_to_vec().unwrap();
-        poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
-        
-        // Initialize vote account with an existing vote
-        let vote = Vote {
+// Test that poll-specific delegations beat topic delegations, which beat global ones
+#[test]
+fn test_topic_delegation_precedence() {
+    let program_id = Pubkey::new_unique();
+    let principal_key = Pubkey::new_unique();
+    let global_delegate = Pubkey::new_unique();
+    let topic_delegate = Pubkey::new_unique();
+    let poll_delegate = Pubkey::new_unique();
+    
+    let vote_instruction = |option_index: u8| {
+        let instruction = PollInstruction::CastVote {
             poll_id: 12345,
-            voter: voter_key,
-            option_index: 1, // Currently voted for Option 2
-            timestamp: MOCK_BLOCK_HEIGHT,
-            weight: 1,
-            delegated_to: None,
+            option_index,
+            weight: None,
             encrypted_data: None,
             zk_proof: None,
             nonce: None,
-        };
-        
-        let vote_data = vote.try_to_vec().unwrap();
-        vote_account.data.borrow_mut()[..vote_data.len()].copy_from_slice(&vote_data);
-        
-        // Initialize vote count account
-        let vote_count = VoteCount {
-            poll_id: 12345,
-            counts: vec![0, 1, 0], // One vote for Option 2
-            total_voters: 1,
-            last_updated: MOCK_BLOCK_HEIGHT,
-            is_finalized: false,
-        };
-        
-        let vote_count_data = vote_count.try_to_vec().unwrap();
-        vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
-        
-        let accounts = vec![
-            voter_account,
-            vote_account,
-            poll_account,
-            vote_count_account,
-        ];
-        
-        // Create change vote instruction data
-        let change_vote_instruction = PollInstruction::ChangeVote {
-            poll_id: 12345,
-            new_option_index: 2, // Change vote to Option 3
-            new_encrypted_data: None,
-            new_zk_proof: None,
-            new_nonce: None,
             tx_hex: create_mock_transaction(),
         };
-        
-        let instruction_data = change_vote_instruction.try_to_vec().unwrap();
-        
-        // Process the instruction
-        let result = process_instruction(&program_id, &accounts, &instruction_data);
-        
-        // Verify the result
-        assert!(result.is_ok(), "Failed to change vote: {:?}", result);
-        
-        // Verify the vote was updated correctly
-        let vote_data = Vote::try_from_slice(&accounts[1].data.borrow()).unwrap();
-        assert_eq!(vote_data.option_index, 2);
-        
-        // Verify the vote count was updated correctly
-        let vote_count_data = VoteCount::try_from_slice(&accounts[3].data.borrow()).unwrap();
-        assert_eq!(vote_count_data.counts, vec![0, 0, 1]);
-        assert_eq!(vote_count_data.total_voters, 1);
-    }
+        borsh::to_vec(&instruction).unwrap()
+    };
+    
+    // Poll, vote count, registry and the principal's record are shared by every vote below
+    let shared = create_delegation_poll_accounts(&program_id, global_delegate, 1);
+    let principal_record = create_voter_record_account(&program_id, principal_key);
+    let voter_accounts = |voter_key: Pubkey, vote_account: &AccountInfo<'static>| {
+        vec![
+            create_account(voter_key, true, true, 100, 0),
+            vote_account.clone(),
+            shared[2].clone(),
+            shared[3].clone(),
+            shared[4].clone(),
+            create_voter_record_account(&program_id, voter_key),
+        ]
+    };
+    
+    // Global delegate carries the principal first
+    let global_vote = shared[1].clone();
+    let mut accounts = voter_accounts(global_delegate, &global_vote);
+    accounts.push(create_delegation_account(&program_id, principal_key, global_delegate, None, None));
+    accounts.push(principal_record.clone());
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(0));
+    assert!(result.is_ok(), "Failed to cast global delegate vote: {:?}", result);
+    
+    // A delegation for another topic does not apply to this poll
+    let topic_vote = create_vote_account(&program_id, topic_delegate);
+    let mut accounts = voter_accounts(topic_delegate, &topic_vote);
+    accounts.push(create_delegation_account(&program_id, principal_key, topic_delegate, None, Some("technical")));
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(1));
+    assert_eq!(result, Err(VotingError::InvalidDelegation.into()));
+    
+    // The topic delegate displaces the global delegate
+    let mut accounts = voter_accounts(topic_delegate, &topic_vote);
+    accounts.push(create_delegation_account(&program_id, principal_key, topic_delegate, None, Some("treasury")));
+    accounts.push(principal_record.clone());
+    accounts.push(global_vote.clone());
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(1));
+    assert!(result.is_ok(), "Failed to cast topic delegate vote: {:?}", result);
+    
+    let vote_count_data = decode::<VoteCount>(&shared[3]);
+    assert_eq!(vote_count_data.counts, vec![1, 2]);
+    
+    // The poll-specific delegate displaces the topic delegate
+    let poll_vote = create_vote_account(&program_id, poll_delegate);
+    let mut accounts = voter_accounts(poll_delegate, &poll_vote);
+    accounts.push(create_delegation_account(&program_id, principal_key, poll_delegate, Some(12345), None));
+    accounts.push(principal_record.clone());
+    accounts.push(topic_vote.clone());
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(0));
+    assert!(result.is_ok(), "Failed to cast poll delegate vote: {:?}", result);
+    
+    let vote_count_data = decode::<VoteCount>(&shared[3]);
+    assert_eq!(vote_count_data.counts, vec![3, 1]);
+    assert_eq!(vote_count_data.proxy_counts, vec![1, 0]);
+    
+    let principal_record_data = decode::<VoterRecord>(&principal_record);
+    assert_eq!(principal_record_data.carried.len(), 1);
+    assert_eq!(principal_record_data.carried[0].delegate, poll_delegate);
+    assert_eq!(principal_record_data.carried[0].scope, DelegationScope::Poll);
+}
 
-    // Test closing a poll
-    #[test]
-    fn test_close_poll() {
-        // Create mock accounts
-        let program_id = Pubkey::new_unique();
-        let caller_key = Pubkey::new_unique();
-        let poll_key = Pubkey::new_unique();
-        let vote_count_key = Pubkey::new_unique();
-        
-        let caller_account = create_account(caller_key, true, true, 100, 0);
-        let vote_count_account = create_account(vote_count_key, false, true, 100, 1000);
-        
-        // Create and initialize poll account
-        let poll_account = create_account(poll_key, false, true, 100, 1000);
-        let poll = Poll {
-            id: 12345,
-            creator: caller_key, // Caller is the creator
-            title: "Test Poll".to_string(),
-            description: "This is a test poll".to_string(),
-            options: vec!["Option 1".to_string(), "Option 2".to_string(), "Option 3".to_string()],
-            start_time: MOCK_BLOCK_HEIGHT - 50,
-            end_time: MOCK_BLOCK_HEIGHT + 1000,
-            is_private: false,
-            allow_revote: true,
-            is_active: true,
-            is_weighted: false,
-            allow_delegation: false,
-            is_encrypted: false,
-            decryption_key: None,
-            weight_token: None,
-            early_voter_bonus: 0,
-        };
-        
-        let poll_data = poll.try_to_vec().unwrap();
-        poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
-        
-        // Initialize vote count account
-        let vote_count = VoteCount {
-            poll_id: 12345,
-            counts: vec![5, 10, 3], // Some votes already cast
-            total_voters: 18,
-            last_updated: MOCK_BLOCK_HEIGHT,
-            is_finalized: false,
-        };
-        
-        let vote_count_data = vote_count.try_to_vec().unwrap();
-        vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
-        
-        let accounts = vec![
-            caller_account,
-            poll_account,
-            vote_count_account,
-        ];
-        
-        // Create close poll instruction data
-        let close_poll_instruction = PollInstruction::ClosePoll {
+// Test that a principal's weight is counted once whichever order delegators and delegates vote in
+#[test]
+fn test_delegation_precedence_orderings() {
+    let program_id = Pubkey::new_unique();
+    let principal_a = Pubkey::new_unique();
+    let delegate_b = Pubkey::new_unique();
+    let delegate_c = Pubkey::new_unique();
+    
+    let vote_instruction = |option_index: u8| {
+        let instruction = PollInstruction::CastVote {
             poll_id: 12345,
+            option_index,
+            weight: None,
+            encrypted_data: None,
+            zk_proof: None,
+            nonce: None,
             tx_hex: create_mock_transaction(),
         };
-        
-        let instruction_data = close_poll_instruction.try_to_vec().unwrap();
-        
-        // Process the instruction
-        let result = process_instruction(&program_id, &accounts, &instruction_data);
-        
-        // Verify the result
-        assert!(result.is_ok(), "Failed to close poll: {:?}", result);
-        
-        // Verify the poll was closed correctly
-        let poll_data = Poll::try_from_slice(&accounts[1].data.borrow()).unwrap();
-        assert_eq!(poll_data.is_active, false);
-        
-        // Verify the vote count was finalized
-        let vote_count_data = VoteCount::try_from_slice(&accounts[2].data.borrow()).unwrap();
-        assert_eq!(vote_count_data.is_finalized, true);
-    }
-
-    // Test delegating a vote
-    #[test]
-    fn test_delegate_vote() {
-        // Create mock accounts
-        let program_id = Pubkey::new_unique();
-        let delegator_key = Pubkey::new_unique();
-        let delegation_key = Pubkey::new_unique();
-        let delegate_key = Pubkey::new_unique();
-        
-        let delegator_account = create_account(delegator_key, true, true, 100, 0);
-        let delegation_account = create_account(delegation_key, false, true, 100, 1000);
-        let delegate_account = create_account(delegate_key, false, false, 100, 0);
-        
-        let accounts = vec![
-            delegator_account,
-            delegation_account,
-            delegate_account,
+        borsh::to_vec(&instruction).unwrap()
+    };
+    
+    // A -> B -> C on a poll that allows revoting
+    let shared = create_delegation_poll_accounts(&program_id, delegate_c, 2);
+    let mut poll = decode::<Poll>(&shared[2]);
+    poll.allow_revote = true;
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    shared[2].data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    let a_to_b = create_delegation_account(&program_id, principal_a, delegate_b, None, None);
+    let b_to_c = create_delegation_account(&program_id, delegate_b, delegate_c, None, None);
+    let (a_vote, a_record) = (create_vote_account(&program_id, principal_a), create_voter_record_account(&program_id, principal_a));
+    let (b_vote, b_record) = (create_vote_account(&program_id, delegate_b), create_voter_record_account(&program_id, delegate_b));
+    let (c_vote, c_record) = (shared[1].clone(), shared[5].clone());
+    
+    let voter_accounts = |voter_key: Pubkey, vote_account: &AccountInfo<'static>, record_account: &AccountInfo<'static>, rest: Vec<&AccountInfo<'static>>| {
+        let mut accounts = vec![
+            create_account(voter_key, true, true, 100, 0),
+            vote_account.clone(),
+            shared[2].clone(),
+            shared[3].clone(),
+            shared[4].clone(),
+            record_account.clone(),
         ];
-        
-        // Create delegate vote instruction data
-        let delegate_vote_instruction = PollInstruction::DelegateVote {
-            poll_id: Some(12345),
-            expiration: Some(MOCK_BLOCK_HEIGHT + 2000),
-            tx_hex: create_mock_transaction(),
-        };
-        
-        let instruction_data = delegate_vote_instruction.try_to_vec().unwrap();
-        
-        // Process the instruction
-        let result = process_instruction(&program_id, &accounts, &instruction_data);
-        
-        // Verify the result
-        assert!(result.is_ok(), "Failed to delegate vote: {:?}", result);
-        
-        // Verify the delegation was created correctly
-        let delegation_data = Delegation::try_from_slice(&accounts[1].data.borrow()).unwrap();
-        assert_eq!(delegation_data.delegator, delegator_key);
-        assert_eq!(delegation_data.delegate, delegate_key);
-        assert_eq!(delegation_data.poll_id, Some(12345));
-        assert_eq!(delegation_data.is_active, true);
-    }
+        accounts.extend(rest.into_iter().cloned());
+        accounts
+    };
+    let counts = || {
+        let vote_count = decode::<VoteCount>(&shared[3]);
+        (vote_count.counts, vote_count.proxy_counts)
+    };
+    
+    // C votes first, carrying A through B and B directly
+    let accounts = voter_accounts(delegate_c, &c_vote, &c_record, vec![&a_to_b, &b_to_c, &a_record, &b_to_c, &b_record]);
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(1));
+    assert!(result.is_ok(), "Failed to cast C's vote: {:?}", result);
+    assert_eq!(counts(), (vec![0, 3], vec![0, 2]));
+    
+    // B votes directly: its own slice leaves C's vote, and as the delegate nearer A it takes A's slice too
+    let accounts = voter_accounts(delegate_b, &b_vote, &b_record, vec![&c_vote, &a_to_b, &a_record, &c_vote]);
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(0));
+    assert!(result.is_ok(), "Failed to cast B's vote: {:?}", result);
+    assert_eq!(counts(), (vec![2, 1], vec![1, 0]));
+    
+    let c_vote_data = decode::<Vote>(&c_vote);
+    assert_eq!(c_vote_data.weight, 1);
+    assert!(c_vote_data.proxies.is_empty());
+    
+    // A principal cannot be passed twice in one vote
+    let accounts = voter_accounts(delegate_c, &c_vote, &c_record, vec![&a_to_b, &b_to_c, &a_record, &a_to_b, &b_to_c, &a_record]);
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(1));
+    assert_eq!(result, Err(VotingError::InvalidDelegation.into()));
+    
+    // C revoting cannot take A back from B, who is nearer A
+    let accounts = voter_accounts(delegate_c, &c_vote, &c_record, vec![&a_to_b, &b_to_c, &a_record]);
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(1));
+    assert!(result.is_ok(), "Failed to revote as C: {:?}", result);
+    assert_eq!(counts(), (vec![2, 1], vec![1, 0]));
+    
+    // B revoting re-declares A, whose slice moves with B's vote instead of being added again
+    let accounts = voter_accounts(delegate_b, &b_vote, &b_record, vec![&a_record, &a_to_b, &a_record]);
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(1));
+    assert!(result.is_ok(), "Failed to revote as B: {:?}", result);
+    assert_eq!(counts(), (vec![0, 3], vec![0, 1]));
+    
+    let a_record_data = decode::<VoterRecord>(&a_record);
+    assert_eq!(a_record_data.carried.len(), 1);
+    assert_eq!(a_record_data.carried[0].delegate, delegate_b);
+    assert!(a_record_data.carried[0].via.is_empty());
+    
+    // A finally votes directly and overrides B's slice
+    let accounts = voter_accounts(principal_a, &a_vote, &a_record, vec![&b_vote]);
+    let result = process_instruction(&program_id, &accounts, &vote_instruction(0));
+    assert!(result.is_ok(), "Failed to cast A's vote: {:?}", result);
+    assert_eq!(counts(), (vec![1, 2], vec![0, 0]));
+    
+    let vote_count_data = decode::<VoteCount>(&shared[3]);
+    assert_eq!(vote_count_data.total_voters, 3);
+}
 
-    // Test weighted voting
-    #[test]
-    fn test_weighted_voting() {
-        // Create mock accounts
-        let program_id = Pubkey::new_unique();
-        let voter_key = Pubkey::new_unique();
-        let vote_key = Pubkey::new_unique();
-        let poll_key = Pubkey::new_unique();
-        let vote_count_key = Pubkey::new_unique();
-        let voter_registry_key = Pubkey::new_unique();
-        let token_balance_key = Pubkey::new_unique();
-        let token_key = Pubkey::new_unique();
-        
-        let voter_account = create_account(voter_key, true, true, 100, 0);
-        let vote_account = create_account(vote_key, false, true, 100, 1000);
-        let vote_count_account = create_account(vote_count_key, false, true, 100, 1000);
-        let voter_registry_account = create_account(voter_registry_key, false, true, 100, 1000);
-        let token_balance_account = create_account(token_balance_key, false, true, 100, 1000);
-        let token_account = create_account(token_key, false, false, 100, 0);
-        
-        // Create and initialize poll account with weighted voting
-        let poll_account = create_account(poll_key, false, true, 100, 1000);
-        let poll = Poll {
-            id: 12345,
-            creator: Pubkey::new_unique(),
-            title: "Weighted Poll".to_string(),
-            description: "This is a weighted poll".to_string(),
-            options: vec!["Option 1".to_string(), "Option 2".to_string()],
-            start_time: MOCK_BLOCK_HEIGHT - 50,
-            end_time: MOCK_BLOCK_HEIGHT + 1000,
-            is_private: false,
-            allow_revote: false,
-            is_active: true,
-            is_weighted: true, // Weighted voting enabled
-            allow_delegation: false,
-            is_encrypted: false,
-            decryption_key: None,
-            weight_token: Some(token_key), // Token for weighting
-            early_voter_bonus: 10, // 10% early voter bonus
-        };
-        
-        let poll_data = poll.try_to_vec().unwrap();
-        poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
-        
-        // Initialize vote count account
-        let vote_count = VoteCount {
-            poll_id: 12345,
-            counts: vec![0, 0],
-            total_voters: 0,
-            last_updated: MOCK_BLOCK_HEIGHT,
-            is_finalized: false,
-        };
-        
-        let vote_count_data = vote_count.try_to_vec().unwrap();
-        vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
-        
-        // Initialize voter registry account
-        let voter_registry = VoterRegistry {
-            poll_id: 12345,
-            voter_bitmap: vec![0; 1024],
-            voters: Vec::new(),
-        };
-        
-        let voter_registry_data = voter_registry.try_to_vec().unwrap();
-        voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
-        
-        // Initialize token balance account
+// Test that a split delegation apportions the delegator's token weight between delegates
+#[test]
+fn test_split_delegation_apportions_weight() {
+    let program_id = Pubkey::new_unique();
+    let principal_key = Pubkey::new_unique();
+    let alice_key = Pubkey::new_unique();
+    let bob_key = Pubkey::new_unique();
+    let token_key = Pubkey::new_unique();
+    let oracle_key = Pubkey::new_unique();
+    
+    // Token-weighted poll shared by both delegates
+    let shared = create_delegation_poll_accounts(&program_id, alice_key, 1);
+    let mut poll = decode::<Poll>(&shared[2]);
+    poll.is_weighted = true;
+    poll.weight_token = Some(token_key);
+    poll.balance_oracle = Some(oracle_key);
+    poll.max_balance_age = 100;
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    shared[2].data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    let token_balance_account = |owner: Pubkey, amount: u64| {
+        let account_key = pda::find_token_balance_address(&program_id, &owner, &token_key).0;
+        let account = create_program_account(&program_id, account_key, false, 1000);
         let token_balance = TokenBalance {
-            owner: voter_key,
+            account_type: AccountType::TokenBalance,
+            version: ACCOUNT_VERSION,
+            owner,
             token: token_key,
-            amount: 100, // Voter has 100 tokens
+            amount,
             last_updated: MOCK_BLOCK_HEIGHT,
+            attested_by: oracle_key,
+            locked_amount: 0,
         };
-        
-        let token_balance_data = token_balance.try_to_vec().unwrap();
-        token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
-        
+        let token_balance_data = borsh::to_vec(&token_balance).unwrap();
+        account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
+        account
+    };
+    
+    // 60% to Alice, 40% to Bob
+    let split = create_split_delegation_account(
+        &program_id,
+        principal_key,
+        vec![
+            DelegationShare { delegate: alice_key, bps: 6_000 },
+            DelegationShare { delegate: bob_key, bps: 4_000 },
+        ],
+        Some(12345),
+        None,
+    );
+    
+    let principal_record = create_voter_record_account(&program_id, principal_key);
+    
+    for (delegate_key, vote_account, option_index) in [
+        (alice_key, shared[1].clone(), 0u8),
+        (bob_key, create_vote_account(&program_id, bob_key), 1u8),
+    ] {
         let accounts = vec![
-            voter_account,
+            create_account(delegate_key, true, true, 100, 0),
             vote_account,
-            poll_account,
-            vote_count_account,
-            voter_registry_account,
-            token_balance_account,
-            token_account,
+            shared[2].clone(),
+            shared[3].clone(),
+            shared[4].clone(),
+            create_voter_record_account(&program_id, delegate_key),
+            token_balance_account(delegate_key, 100),
+            split.clone(),
+            token_balance_account(principal_key, 1000),
+            principal_record.clone(),
         ];
         
-        // Create cast vote instruction data
         let cast_vote_instruction = PollInstruction::CastVote {
             poll_id: 12345,
-            option_index: 0, // Vote for Option 1
-            weight: None, // Use token balance for weight
+            option_index,
+            weight: None,
             encrypted_data: None,
             zk_proof: None,
             nonce: None,
             tx_hex: create_mock_transaction(),
         };
         
-        let instruction_data = cast_vote_instruction.try_to_vec().unwrap();
-        
-        // Process the instruction
-        let result = process_instruction(&program_id, &accounts, &instruction_data);
-        
-        // Verify the result
-        assert!(result.is_ok(), "Failed to cast weighted vote: {:?}", result);
-        
-        // Verify the vote was recorded with correct weight (including early voter bonus)
-        let vote_data = Vote::try_from_slice(&accounts[1].data.borrow()).unwrap();
-        assert!(vote_data.weight > 100, "Weight should be greater than token balance due to early voter bonus");
-        
-        // Verify the vote count was updated correctly
-        let vote_count_data = VoteCount::try_from_slice(&accounts[3].data.borrow()).unwrap();
-        assert_eq!(vote_count_data.counts[0], vote_data.weight);
-        assert_eq!(vote_count_data.total_voters, 1);
+        let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&cast_vote_instruction).unwrap());
+        assert!(result.is_ok(), "Failed to cast split delegate vote: {:?}", result);
     }
+    
+    // Each delegate carries its own 100 tokens plus its share of the principal's 1000
+    let vote_count_data = decode::<VoteCount>(&shared[3]);
+    assert_eq!(vote_count_data.counts, vec![700, 500]);
+    assert_eq!(vote_count_data.proxy_counts, vec![600, 400]);
+    
+    let alice_vote = decode::<Vote>(&shared[1]);
+    assert_eq!(alice_vote.proxies[0].share_bps, 6_000);
+}
 
-    // Test encrypted voting
-    #[test]
-    fn test_encrypted_voting() {
-        // Create mock accounts
-        let program_id = Pubkey::new_unique();
-        let voter_key = Pubkey::new_unique();
-        let vote_key = Pubkey::new_unique();
-        let poll_key = Pubkey::new_unique();
-        let vote_count_key = Pubkey::new_unique();
-        let voter_registry_key = Pubkey::new_unique();
-        
-        let voter_account = create_account(voter_key, true, true, 100, 0);
-        let vote_account = create_account(vote_key, false, true, 100, 1000);
-        let vote_count_account = create_account(vote_count_key, false, true, 100, 1000);
-        let voter_registry_account = create_account(voter_registry_key, false, true, 100, 1000);
-        
-        // Create and initialize poll account with encryption
-        let poll_account = create_account(poll_key, false, true, 100, 1000);
-        let poll = Poll {
-            id: 12345,
-            creator: Pubkey::new_unique(),
-            title: "Encrypted Poll".to_string(),
-            description: "This is an encrypted poll".to_string(),
-            options: vec!["Option 1".to_string(), "Option 2".to_string()],
-            start_time: MOCK_BLOCK_HEIGHT - 50,
-            end_time: MOCK_BLOCK_HEIGHT + 1000,
-            is_private: false,
-            allow_revote: false,
-            is_active: true,
-            is_weighted: false,
-            allow_delegation: false,
-            is_encrypted: true, // Encryption enabled
-            decryption_key: None,
-            weight_token: None,
-            early_voter_bonus: 0,
-        };
-        
-        let poll_data = poll.try_to_vec().unwrap();
-        poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
-        
-        // Initialize vote count account
-        let vote_count = VoteCount {
-            poll_id: 12345,
-            counts: vec![0, 0],
-            total_voters: 0,
-            last_updated: MOCK_BLOCK_HEIGHT,
-            is_finalized: false,
-        };
-        
-        let vote_count_data = vote_count.try_to_vec().unwrap();
-        vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+// Test revoking one share of a split delegation, then the rest
+#[test]
+fn test_revoke_delegation_share() {
+    let program_id = Pubkey::new_unique();
+    let delegator_key = Pubkey::new_unique();
+    let alice_key = Pubkey::new_unique();
+    let bob_key = Pubkey::new_unique();
+    
+    let delegation_key = pda::find_delegation_address(&program_id, pda::delegation_id(&delegator_key, 0)).0;
+    let accounts = vec![
+        create_account(delegator_key, true, true, 100, 0),
+        create_program_account(&program_id, delegation_key, true, 1000),
+        create_account(alice_key, false, false, 100, 0),
+        create_account(bob_key, false, false, 100, 0),
+        create_program_account(&program_id, pda::find_delegate_profile_address(&program_id, &alice_key).0, true, 1000),
+        create_program_account(&program_id, pda::find_delegate_profile_address(&program_id, &bob_key).0, true, 1000),
+    ];
+    
+    // Shares that do not sum to 100% are rejected
+    let delegate_vote_instruction = PollInstruction::DelegateVote {
+        poll_id: None,
+        topic: None,
+        shares: vec![6_000, 3_000],
+        expiration: None,
+        id_nonce: 0,
+        tx_hex: create_mock_transaction(),
+    };
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&delegate_vote_instruction).unwrap());
+    assert_eq!(result, Err(VotingError::InvalidPollParameters.into()));
+    
+    let delegate_vote_instruction = PollInstruction::DelegateVote {
+        poll_id: None,
+        topic: None,
+        shares: vec![6_000, 4_000],
+        expiration: None,
+        id_nonce: 0,
+        tx_hex: create_mock_transaction(),
+    };
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&delegate_vote_instruction).unwrap());
+    assert!(result.is_ok(), "Failed to split delegation: {:?}", result);
+    
+    let delegation_id = decode::<Delegation>(&accounts[1]).id;
+    
+    // Revoke Bob's share; Alice keeps hers and the rest stays undelegated
+    let revoke_instruction = PollInstruction::RevokeDelegation {
+        delegation_id,
+        delegate: Some(bob_key),
+        tx_hex: create_mock_transaction(),
+    };
+    let result = process_instruction(&program_id, &accounts[..2], &borsh::to_vec(&revoke_instruction).unwrap());
+    assert!(result.is_ok(), "Failed to revoke share: {:?}", result);
+    
+    let delegation_data = decode::<Delegation>(&accounts[1]);
+    assert_eq!(delegation_data.shares, vec![DelegationShare { delegate: alice_key, bps: 6_000 }]);
+    assert!(delegation_data.is_active);
+    
+    // Revoke the whole delegation
+    let revoke_instruction = PollInstruction::RevokeDelegation {
+        delegation_id,
+        delegate: None,
+        tx_hex: create_mock_transaction(),
+    };
+    let result = process_instruction(&program_id, &accounts[..2], &borsh::to_vec(&revoke_instruction).unwrap());
+    assert!(result.is_ok(), "Failed to revoke delegation: {:?}", result);
+    
+    let delegation_data = decode::<Delegation>(&accounts[1]);
+    assert!(delegation_data.shares.is_empty());
+    assert!(!delegation_data.is_active);
+}
+
+// Test registering a delegate profile and enforcing its delegation cap
+#[test]
+fn test_register_delegate_and_cap() {
+    let program_id = Pubkey::new_unique();
+    let delegate_key = Pubkey::new_unique();
+    let profile_key = pda::find_delegate_profile_address(&program_id, &delegate_key).0;
+    let profile_account = create_program_account(&program_id, profile_key, true, 1000);
+    
+    let register_instruction = PollInstruction::RegisterDelegate {
+        statement_uri: "ipfs://delegate-statement".to_string(),
+        statement_hash: [7u8; 32],
+        voting_policy: "Votes for treasury transparency".to_string(),
+        accepting_delegations: true,
+        max_delegators: Some(1),
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let accounts = vec![create_account(delegate_key, true, true, 100, 0), profile_account.clone()];
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&register_instruction).unwrap());
+    assert!(result.is_ok(), "Failed to register delegate: {:?}", result);
+    
+    let delegate_vote_instruction = PollInstruction::DelegateVote {
+        poll_id: None,
+        topic: None,
+        shares: vec![],
+        expiration: None,
+        id_nonce: 0,
+        tx_hex: create_mock_transaction(),
+    };
+    let instruction_data = borsh::to_vec(&delegate_vote_instruction).unwrap();
+    
+    let delegator_accounts = || {
+        let delegator_key = Pubkey::new_unique();
+        let delegation_key = pda::find_delegation_address(&program_id, pda::delegation_id(&delegator_key, 0)).0;
+        (create_account(delegator_key, true, true, 100, 0), create_program_account(&program_id, delegation_key, true, 1000))
+    };
+    
+    // First delegator fills the only slot
+    let (delegator_account, registered_delegation) = delegator_accounts();
+    let accounts = vec![
+        delegator_account,
+        registered_delegation.clone(),
+        create_account(delegate_key, false, false, 100, 0),
+        profile_account.clone(),
+    ];
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert!(result.is_ok(), "Failed to delegate to registered delegate: {:?}", result);
+    
+    let delegation_data = decode::<Delegation>(&accounts[1]);
+    assert!(delegation_data.registered);
+    
+    let profile_data = decode::<DelegateProfile>(&profile_account);
+    assert_eq!(profile_data.delegator_count, 1);
+    
+    // Second delegator is refused
+    let (delegator_account, delegation_account) = delegator_accounts();
+    let accounts = vec![
+        delegator_account,
+        delegation_account,
+        create_account(delegate_key, false, false, 100, 0),
+        profile_account.clone(),
+    ];
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert_eq!(result, Err(VotingError::DelegateNotAccepting.into()));
+    
+    // Revoking through the client passes the derived profile of each revoked delegate, freeing the slot
+    let result = client::revoke_delegation(&program_id, &delegation_data, Some(Pubkey::new_unique()), create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::DelegationNotFound.into());
+    
+    let instruction = client::revoke_delegation(&program_id, &delegation_data, None, create_mock_transaction()).unwrap();
+    assert_eq!(instruction.accounts.len(), 3);
+    assert_eq!(instruction.accounts[2].pubkey, profile_key);
+    
+    let mut store = HashMap::from([(profile_key, profile_account.clone()), (*registered_delegation.key, registered_delegation)]);
+    let accounts = accounts_for(&program_id, &mut store, &instruction);
+    let result = process_instruction(&program_id, &accounts, &instruction.data);
+    assert!(result.is_ok(), "Failed to revoke registered delegation: {:?}", result);
+    assert_eq!(decode::<DelegateProfile>(&profile_account).delegator_count, 0);
+}
+
+// Test that delegating cannot skip the delegate registry
+#[test]
+fn test_delegate_vote_checks_every_profile() {
+    let program_id = Pubkey::new_unique();
+    let registered_key = Pubkey::new_unique();
+    let unregistered_key = Pubkey::new_unique();
+    
+    let profile_account = |delegate: Pubkey| {
+        create_program_account(&program_id, pda::find_delegate_profile_address(&program_id, &delegate).0, true, 1000)
+    };
+    
+    // A profile that refuses new delegations
+    let closed_profile = profile_account(registered_key);
+    let profile = DelegateProfile {
+        account_type: AccountType::DelegateProfile,
+        version: ACCOUNT_VERSION,
+        delegate: registered_key,
+        statement_uri: "ipfs://delegate-statement".to_string(),
+        statement_hash: [7u8; 32],
+        voting_policy: String::new(),
+        accepting_delegations: false,
+        max_delegators: None,
+        delegator_count: 0,
+        last_updated: MOCK_BLOCK_HEIGHT,
+    };
+    let profile_data = borsh::to_vec(&profile).unwrap();
+    closed_profile.data.borrow_mut()[..profile_data.len()].copy_from_slice(&profile_data);
+    
+    let delegate = |shares: Vec<u16>, poll_id: Option<u64>, mut tail: Vec<AccountInfo<'static>>| {
+        let delegator_key = Pubkey::new_unique();
+        let delegation_key = pda::find_delegation_address(&program_id, pda::delegation_id(&delegator_key, 0)).0;
+        let mut accounts = vec![
+            create_account(delegator_key, true, true, 100, 0),
+            create_program_account(&program_id, delegation_key, true, 1000),
+        ];
+        accounts.append(&mut tail);
         
-        // Initialize voter registry account
-        let voter_registry = VoterRegistry {
-            poll_id: 12345,
-            voter_bitmap: vec![0; 1024],
-            voters: Vec::new(),
+        let delegate_vote_instruction = PollInstruction::DelegateVote {
+            poll_id,
+            topic: None,
+            shares,
+            expiration: None,
+            id_nonce: 0,
+            tx_hex: create_mock_transaction(),
         };
-        
-        let voter_registry_data = voter_registry.try_to_vec().unwrap();
-        voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
-        
+        process_instruction(&program_id, &accounts, &borsh::to_vec(&delegate_vote_instruction).unwrap())
+    };
+    
+    // The profile is always passed, so a closed delegate cannot be delegated to as unregistered
+    let result = delegate(vec![], None, vec![create_account(registered_key, false, false, 100, 0), closed_profile.clone()]);
+    assert_eq!(result, Err(VotingError::DelegateNotAccepting.into()));
+    
+    // Omitting the profile account is refused
+    let result = delegate(vec![], None, vec![create_account(registered_key, false, false, 100, 0)]);
+    assert_eq!(result, Err(ProgramError::NotEnoughAccountKeys));
+    
+    // Another account cannot stand in for the delegate's profile
+    let result = delegate(
+        vec![],
+        None,
+        vec![create_account(registered_key, false, false, 100, 0), profile_account(unregistered_key)],
+    );
+    assert_eq!(result, Err(VotingError::InvalidAccountAddress.into()));
+    
+    // A split cannot mix registered and unregistered delegates
+    let result = delegate(
+        vec![5_000, 5_000],
+        None,
+        vec![
+            create_account(registered_key, false, false, 100, 0),
+            create_account(unregistered_key, false, false, 100, 0),
+            closed_profile.clone(),
+            profile_account(unregistered_key),
+        ],
+    );
+    assert_eq!(result, Err(VotingError::DelegateNotRegistered.into()));
+    
+    // Polls requiring registered delegates refuse unregistered delegations up front
+    let poll_account = create_delegation_poll_accounts(&program_id, Pubkey::new_unique(), 1)[2].clone();
+    let mut poll = decode::<Poll>(&poll_account);
+    poll.require_registered_delegates = true;
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    let result = delegate(
+        vec![],
+        Some(12345),
+        vec![create_account(unregistered_key, false, false, 100, 0), profile_account(unregistered_key), poll_account],
+    );
+    assert_eq!(result, Err(VotingError::DelegateNotRegistered.into()));
+}
+
+// Test that polls requiring registered delegates ignore unregistered delegations
+#[test]
+fn test_cast_vote_requires_registered_delegates() {
+    let program_id = Pubkey::new_unique();
+    let principal_key = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    
+    let mut accounts = create_delegation_poll_accounts(&program_id, voter_key, 1);
+    let mut poll = decode::<Poll>(&accounts[2]);
+    poll.require_registered_delegates = true;
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    accounts[2].data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    accounts.push(create_delegation_account(&program_id, principal_key, voter_key, None, None));
+    
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 0,
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&cast_vote_instruction).unwrap());
+    assert_eq!(result, Err(VotingError::DelegateNotRegistered.into()));
+}
+
+// Test that IDs and account addresses are deterministic and distinct per creator and nonce
+#[test]
+fn test_derived_ids_and_addresses() {
+    let program_id = Pubkey::new_unique();
+    let creator_a = Pubkey::new_unique();
+    let creator_b = Pubkey::new_unique();
+    
+    // Same inputs always give the same ID; any other creator or nonce gives another
+    assert_eq!(pda::poll_id(&creator_a, 1), pda::poll_id(&creator_a, 1));
+    assert_ne!(pda::poll_id(&creator_a, 1), pda::poll_id(&creator_a, 2));
+    assert_ne!(pda::poll_id(&creator_a, 1), pda::poll_id(&creator_b, 1));
+    assert_ne!(pda::poll_id(&creator_a, 1), pda::delegation_id(&creator_a, 1));
+    
+    let poll_id = pda::poll_id(&creator_a, 1);
+    assert_eq!(pda::find_poll_address(&program_id, poll_id), pda::find_poll_address(&program_id, poll_id));
+    assert_ne!(pda::find_poll_address(&program_id, poll_id).0, pda::find_vote_count_address(&program_id, poll_id).0);
+    assert_ne!(
+        pda::find_vote_address(&program_id, poll_id, &creator_a).0,
+        pda::find_vote_address(&program_id, poll_id, &creator_b).0
+    );
+}
+
+// Test that vote accounts at the wrong address and reused poll nonces are rejected
+#[test]
+fn test_rejects_wrong_address_and_reused_nonce() {
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    
+    // Another voter's vote account cannot be written through this voter
+    let mut accounts = create_delegation_poll_accounts(&program_id, voter_key, 1);
+    accounts[1] = create_vote_account(&program_id, Pubkey::new_unique());
+    
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 0,
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&cast_vote_instruction).unwrap());
+    assert_eq!(result, Err(VotingError::InvalidAccountAddress.into()));
+    
+    // Creating a second poll with the same nonce hits the existing poll account
+    let creator_key = Pubkey::new_unique();
+    let poll_id = pda::poll_id(&creator_key, 0);
+    let accounts = vec![
+        create_account(creator_key, true, true, 100, 0),
+        create_program_account(&program_id, pda::find_poll_address(&program_id, poll_id).0, true, 1000),
+        create_program_account(&program_id, pda::find_vote_count_address(&program_id, poll_id).0, true, 1000),
+        create_program_account(&program_id, pda::find_voter_registry_address(&program_id, poll_id, 0).0, true, 1000),
+    ];
+    
+    let create_poll_instruction = PollInstruction::CreatePoll {
+        title: "Nonce Poll".to_string(),
+        description: "This poll is created twice".to_string(),
+        options: vec!["Yes".to_string(), "No".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT + 100,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: false,
+        is_weighted: false,
+        allow_delegation: false,
+        is_encrypted: false,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: None,
+        max_balance_age: 0,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+        id_nonce: 0,
+        tx_hex: create_mock_transaction(),
+    };
+    let instruction_data = borsh::to_vec(&create_poll_instruction).unwrap();
+    
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert!(result.is_ok(), "Failed to create poll: {:?}", result);
+    
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert_eq!(result, Err(VotingError::PollAlreadyExists.into()));
+}
+
+// Test that every instruction rejects an account of the wrong type in a program account slot
+#[test]
+fn test_rejects_mismatched_account_types() {
+    let program_id = Pubkey::new_unique();
+    let user_key = Pubkey::new_unique();
+    let token_key = Pubkey::new_unique();
+    let poll_key = pda::find_poll_address(&program_id, 12345).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, 12345).0;
+    
+    let signer = || create_account(user_key, true, true, 100, 0);
+    let typed = |key: Pubkey, account_type: AccountType| create_typed_account(&program_id, key, account_type);
+    let empty = || create_program_account(&program_id, Pubkey::new_unique(), true, 1000);
+    
+    // A real token balance passed where a delegation is expected
+    let token_balance_account = create_program_account(&program_id, pda::find_delegation_address(&program_id, 1).0, true, 1000);
+    let token_balance = TokenBalance {
+        account_type: AccountType::TokenBalance,
+        version: ACCOUNT_VERSION,
+        owner: user_key,
+        token: token_key,
+        amount: 1000,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        attested_by: Pubkey::new_unique(),
+        locked_amount: 0,
+    };
+    let token_balance_data = borsh::to_vec(&token_balance).unwrap();
+    token_balance_account.data.borrow_mut()[..token_balance_data.len()].copy_from_slice(&token_balance_data);
+    
+    // Cast vote with a vote count where the poll belongs
+    let mut cast_vote_accounts = create_delegation_poll_accounts(&program_id, user_key, 1);
+    cast_vote_accounts[2] = typed(poll_key, AccountType::VoteCount);
+    
+    let delegation_key = pda::find_delegation_address(&program_id, pda::delegation_id(&user_key, 0)).0;
+    let delegate_key = Pubkey::new_unique();
+    let rune = BitcoinAsset::Rune { block: 840_000, tx: 1 };
+    let token_balance_key = pda::find_token_balance_address(&program_id, &user_key, &token_key).0;
+    let escrow_key = pda::find_escrow_address(&program_id, &user_key, &token_key).0;
+    let at = |key: Pubkey| create_program_account(&program_id, key, true, 1000);
+    
+    let cases: Vec<(Vec<AccountInfo>, PollInstruction)> = vec![
+        (
+            cast_vote_accounts,
+            PollInstruction::CastVote {
+                poll_id: 12345,
+                option_index: 0,
+                weight: None,
+                encrypted_data: None,
+                zk_proof: None,
+                nonce: None,
+                tx_hex: create_mock_transaction(),
+            },
+        ),
+        (
+            vec![signer(), create_vote_account(&program_id, user_key), typed(poll_key, AccountType::VoterRegistry), typed(vote_count_key, AccountType::VoteCount)],
+            PollInstruction::ChangeVote {
+                poll_id: 12345,
+                new_option_index: 1,
+                new_encrypted_data: None,
+                new_zk_proof: None,
+                new_nonce: None,
+                tx_hex: create_mock_transaction(),
+            },
+        ),
+        (
+            vec![signer(), typed(poll_key, AccountType::Vote)],
+            PollInstruction::CancelPoll { poll_id: 12345, tx_hex: create_mock_transaction() },
+        ),
+        (
+            vec![signer(), typed(poll_key, AccountType::TokenBalance), typed(vote_count_key, AccountType::VoteCount)],
+            PollInstruction::ClosePoll { poll_id: 12345, tx_hex: create_mock_transaction() },
+        ),
+        (
+            vec![typed(poll_key, AccountType::EscrowPosition), typed(vote_count_key, AccountType::VoteCount)],
+            PollInstruction::GetResults { poll_id: 12345 },
+        ),
+        (
+            vec![signer(), typed(poll_key, AccountType::AssetClaim), typed(vote_count_key, AccountType::VoteCount)],
+            PollInstruction::DecryptResults {
+                poll_id: 12345,
+                decryption_key: vec![1, 2, 3],
+                tx_hex: create_mock_transaction(),
+            },
+        ),
+        (
+            vec![
+                signer(),
+                create_program_account(&program_id, delegation_key, true, 1000),
+                create_account(delegate_key, false, false, 100, 0),
+                typed(pda::find_delegate_profile_address(&program_id, &delegate_key).0, AccountType::Delegation),
+            ],
+            PollInstruction::DelegateVote {
+                poll_id: None,
+                topic: None,
+                shares: vec![],
+                expiration: None,
+                id_nonce: 0,
+                tx_hex: create_mock_transaction(),
+            },
+        ),
+        (
+            vec![signer(), token_balance_account],
+            PollInstruction::RevokeDelegation { delegation_id: 1, delegate: None, tx_hex: create_mock_transaction() },
+        ),
+        (
+            vec![
+                signer(),
+                typed(token_balance_key, AccountType::EscrowPosition),
+                create_account(token_key, false, false, 100, 0),
+                create_account(Pubkey::new_unique(), true, false, 100, 0),
+            ],
+            PollInstruction::UpdateTokenBalance { token: token_key, amount: 1000, tx_hex: create_mock_transaction() },
+        ),
+        (
+            vec![
+                signer(),
+                at(pda::find_utxo_weight_address(&program_id, 12345, &user_key).0),
+                typed(poll_key, AccountType::AssetHoldings),
+                empty(),
+            ],
+            PollInstruction::ProveUtxoWeight { poll_id: 12345, prev_txs: vec![], tx_hex: create_mock_transaction() },
+        ),
+        (
+            vec![
+                signer(),
+                at(pda::find_asset_holdings_address(&program_id, 12345, &user_key, &rune).0),
+                typed(poll_key, AccountType::UtxoWeight),
+                create_account(Pubkey::new_unique(), true, false, 100, 0),
+            ],
+            PollInstruction::ProveAssetHoldings {
+                poll_id: 12345,
+                asset: rune,
+                holdings: vec![],
+                prev_txs: vec![],
+                tx_hex: create_mock_transaction(),
+            },
+        ),
+        (
+            vec![signer(), typed(escrow_key, AccountType::TokenBalance), at(token_balance_key)],
+            PollInstruction::LockEscrow {
+                token: token_key,
+                amount: 100,
+                unlock_height: MOCK_BLOCK_HEIGHT + 100,
+                tx_hex: create_mock_transaction(),
+            },
+        ),
+        (
+            vec![signer(), typed(escrow_key, AccountType::TokenBalance)],
+            PollInstruction::ExtendEscrow { new_unlock_height: MOCK_BLOCK_HEIGHT + 200, tx_hex: create_mock_transaction() },
+        ),
+        (
+            vec![signer(), typed(escrow_key, AccountType::Poll), at(token_balance_key)],
+            PollInstruction::WithdrawEscrow { tx_hex: create_mock_transaction() },
+        ),
+        (
+            vec![signer(), typed(pda::find_delegate_profile_address(&program_id, &user_key).0, AccountType::Vote)],
+            PollInstruction::RegisterDelegate {
+                statement_uri: "ipfs://delegate-statement".to_string(),
+                statement_hash: [7u8; 32],
+                voting_policy: String::new(),
+                accepting_delegations: true,
+                max_delegators: None,
+                tx_hex: create_mock_transaction(),
+            },
+        ),
+        (
+            vec![
+                signer(),
+                create_program_account(&program_id, pda::find_recount_address(&program_id, 12345).0, true, 1000),
+                typed(poll_key, AccountType::RecountTally),
+                typed(vote_count_key, AccountType::VoteCount),
+                empty(),
+            ],
+            PollInstruction::Recount { poll_id: 12345, restart: false, apply: false, tx_hex: create_mock_transaction() },
+        ),
+    ];
+    
+    for (accounts, instruction) in cases {
+        let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&instruction).unwrap());
+        assert_eq!(result, Err(VotingError::InvalidAccountType.into()), "{:?}", instruction);
+    }
+}
+
+// Test that accounts owned by another program are rejected
+#[test]
+fn test_rejects_accounts_not_owned_by_program() {
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    
+    // A well-formed poll at the right address, but owned by another program
+    let mut accounts = create_delegation_poll_accounts(&program_id, voter_key, 1);
+    let foreign_poll = create_account(*accounts[2].key, false, true, 100, 1000);
+    foreign_poll.data.borrow_mut().copy_from_slice(&accounts[2].data.borrow());
+    accounts[2] = foreign_poll;
+    
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 0,
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&cast_vote_instruction).unwrap());
+    assert_eq!(result, Err(VotingError::InvalidAccountOwner.into()));
+    
+    // New poll accounts must be assigned to the program before the poll is created
+    let creator_key = Pubkey::new_unique();
+    let poll_id = pda::poll_id(&creator_key, 0);
+    let accounts = vec![
+        create_account(creator_key, true, true, 100, 0),
+        create_account(pda::find_poll_address(&program_id, poll_id).0, false, true, 100, 1000),
+        create_account(pda::find_vote_count_address(&program_id, poll_id).0, false, true, 100, 1000),
+        create_account(pda::find_voter_registry_address(&program_id, poll_id, 0).0, false, true, 100, 1000),
+    ];
+    
+    let create_poll_instruction = PollInstruction::CreatePoll {
+        title: "Foreign Poll".to_string(),
+        description: "These accounts belong to another program".to_string(),
+        options: vec!["Yes".to_string(), "No".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT + 100,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: false,
+        is_weighted: false,
+        allow_delegation: false,
+        is_encrypted: false,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: None,
+        max_balance_age: 0,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+        id_nonce: 0,
+        tx_hex: create_mock_transaction(),
+    };
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&create_poll_instruction).unwrap());
+    assert_eq!(result, Err(VotingError::InvalidAccountOwner.into()));
+}
+
+// Test that first votes roll over onto the next registry page and voter records block double votes
+#[test]
+fn test_voter_registry_pages_and_records() {
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    
+    // Page 0 already holds VOTERS_PER_PAGE voters
+    let mut accounts = create_delegation_poll_accounts(&program_id, voter_key, 1);
+    let mut vote_count = decode::<VoteCount>(&accounts[3]);
+    vote_count.counts = vec![VOTERS_PER_PAGE as u64, 0];
+    vote_count.raw_counts = vec![VOTERS_PER_PAGE as u64, 0];
+    vote_count.total_voters = VOTERS_PER_PAGE as u64;
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    accounts[3].data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 1,
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    let instruction_data = borsh::to_vec(&cast_vote_instruction).unwrap();
+    
+    // The full page is refused; the voter goes on page 1
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert_eq!(result, Err(VotingError::InvalidAccountAddress.into()));
+    
+    accounts[4] = create_program_account(&program_id, pda::find_voter_registry_address(&program_id, 12345, 1).0, true, 1000);
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert!(result.is_ok(), "Failed to cast vote on page 1: {:?}", result);
+    
+    let voter_registry_data = decode::<VoterRegistry>(&accounts[4]);
+    assert_eq!(voter_registry_data.page, 1);
+    assert_eq!(voter_registry_data.voters, vec![voter_key]);
+    
+    let voter_record_data = decode::<VoterRecord>(&accounts[5]);
+    assert!(voter_record_data.has_voted);
+    
+    // The record alone stops a second vote, whichever registry page is passed
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert_eq!(result, Err(VotingError::AlreadyVoted.into()));
+    
+    // Another voter's record cannot stand in for the voter's own
+    accounts[5] = create_voter_record_account(&program_id, Pubkey::new_unique());
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert_eq!(result, Err(VotingError::InvalidAccountAddress.into()));
+}
+
+// Test that proofs only accept the accounts derived for their poll and voter
+#[test]
+fn test_proofs_require_derived_accounts() {
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let rune = BitcoinAsset::Rune { block: 840_000, tx: 1 };
+    let at = |key: Pubkey| create_program_account(&program_id, key, true, 1000);
+    let poll_key = pda::find_poll_address(&program_id, 12345).0;
+    
+    // A claim account at any other address would start out unclaimed
+    let outpoint = UtxoOutpoint { txid: [1; 32], vout: 0 };
+    let accounts = vec![
+        create_account(voter_key, true, true, 100, 0),
+        at(pda::find_asset_holdings_address(&program_id, 12345, &voter_key, &rune).0),
+        at(poll_key),
+        create_account(Pubkey::new_unique(), true, false, 100, 0),
+        at(pda::find_asset_claim_address(&program_id, 12345, &BitcoinAsset::Rune { block: 840_000, tx: 2 }, &outpoint).0),
+    ];
+    let prove_asset_instruction = PollInstruction::ProveAssetHoldings {
+        poll_id: 12345,
+        asset: rune,
+        holdings: vec![AssetHolding { outpoint: outpoint.clone(), amount: 10 }],
+        prev_txs: vec![],
+        tx_hex: create_mock_transaction(),
+    };
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&prove_asset_instruction).unwrap());
+    assert_eq!(result, Err(VotingError::InvalidAccountAddress.into()));
+    
+    // Holdings are kept per voter and asset
+    let accounts = vec![
+        create_account(voter_key, true, true, 100, 0),
+        at(pda::find_asset_holdings_address(&program_id, 12345, &Pubkey::new_unique(), &rune).0),
+        at(poll_key),
+        create_account(Pubkey::new_unique(), true, false, 100, 0),
+        at(pda::find_asset_claim_address(&program_id, 12345, &rune, &outpoint).0),
+    ];
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&prove_asset_instruction).unwrap());
+    assert_eq!(result, Err(VotingError::InvalidAccountAddress.into()));
+    
+    // UTXO weight must be proven against the poll's own account
+    let accounts = vec![
+        create_account(voter_key, true, true, 100, 0),
+        at(pda::find_utxo_weight_address(&program_id, 12345, &voter_key).0),
+        at(pda::find_poll_address(&program_id, 54321).0),
+    ];
+    let prove_utxo_instruction = PollInstruction::ProveUtxoWeight {
+        poll_id: 12345,
+        prev_txs: vec![],
+        tx_hex: create_mock_transaction(),
+    };
+    let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&prove_utxo_instruction).unwrap());
+    assert_eq!(result, Err(VotingError::InvalidAccountAddress.into()));
+}
+
+// Test that a recount walks the registry in batches, flags a drifted tally and repairs it once voting is over
+#[test]
+fn test_recount_flags_and_repairs_vote_count() {
+    let program_id = Pubkey::new_unique();
+    let alice_key = Pubkey::new_unique();
+    let bob_key = Pubkey::new_unique();
+    
+    let shared = create_delegation_poll_accounts(&program_id, alice_key, 1);
+    let bob_vote = create_vote_account(&program_id, bob_key);
+    
+    for (voter_key, vote_account, record_account, option_index) in [
+        (alice_key, shared[1].clone(), shared[5].clone(), 1u8),
+        (bob_key, bob_vote.clone(), create_voter_record_account(&program_id, bob_key), 0u8),
+    ] {
         let accounts = vec![
-            voter_account,
+            create_account(voter_key, true, true, 100, 0),
             vote_account,
-            poll_account,
-            vote_count_account,
-            voter_registry_account,
+            shared[2].clone(),
+            shared[3].clone(),
+            shared[4].clone(),
+            record_account,
         ];
-        
-        // Mock encrypted data and nonce
-        let encrypted_data = vec![1, 2, 3, 4, 5]; // Mock encrypted vote
-        let nonce = vec![6, 7, 8, 9, 10]; // Mock nonce
-        
-        // Create cast vote instruction data with encryption
         let cast_vote_instruction = PollInstruction::CastVote {
             poll_id: 12345,
-            option_index: 0, // This is still needed for the contract, but the real vote is encrypted
+            option_index,
             weight: None,
-            encrypted_data: Some(encrypted_data.clone()),
+            encrypted_data: None,
             zk_proof: None,
-            nonce: Some(nonce.clone()),
+            nonce: None,
             tx_hex: create_mock_transaction(),
         };
-        
-        let instruction_data = cast_vote_instruction.try_to_vec().unwrap();
-        
-        // Process the instruction
-        let result = process_instruction(&program_id, &accounts, &instruction_data);
-        
-        // Verify the result
-        assert!(result.is_ok(), "Failed to cast encrypted vote: {:?}", result);
-        
-        // Verify the encrypted vote was recorded correctly
-        let vote_data = Vote::try_from_slice(&accounts[1].data.borrow()).unwrap();
-        assert_eq!(vote_data.encrypted_data.unwrap(), encrypted_data);
-        assert_eq!(vote_data.nonce.unwrap(), nonce);
-        
-        // Verify the vote count was updated
-        let vote_count_data = VoteCount::try_from_slice(&accounts[3].data.borrow()).unwrap();
-        assert_eq!(vote_count_data.total_voters, 1);
+        let result = process_instruction(&program_id, &accounts, &borsh::to_vec(&cast_vote_instruction).unwrap());
+        assert!(result.is_ok(), "Failed to cast vote: {:?}", result);
+    }
+    
+    // The stored tally drifts away from the votes
+    let mut vote_count = decode::<VoteCount>(&shared[3]);
+    vote_count.counts = vec![5, 1];
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    shared[3].data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    let tally_account = create_program_account(&program_id, pda::find_recount_address(&program_id, 12345).0, true, 1000);
+    let recount_accounts = |votes: Vec<&AccountInfo<'static>>| {
+        let mut accounts = vec![
+            create_account(Pubkey::new_unique(), true, true, 100, 0), // Anyone can recount
+            tally_account.clone(),
+            shared[2].clone(),
+            shared[3].clone(),
+            shared[4].clone(),
+        ];
+        accounts.extend(votes.into_iter().cloned());
+        accounts
+    };
+    let recount_instruction = |restart: bool, apply: bool| {
+        let instruction = PollInstruction::Recount { poll_id: 12345, restart, apply, tx_hex: create_mock_transaction() };
+        borsh::to_vec(&instruction).unwrap()
+    };
+    
+    // Votes must follow registry order
+    let result = process_instruction(&program_id, &recount_accounts(vec![&bob_vote]), &recount_instruction(false, false));
+    assert_eq!(result, Err(VotingError::InvalidAccountAddress.into()));
+    
+    // Nothing can be repaired while voting is open
+    let result = process_instruction(&program_id, &recount_accounts(vec![&shared[1], &bob_vote]), &recount_instruction(false, true));
+    assert_eq!(result, Err(VotingError::PollStillActive.into()));
+    
+    // Recount one voter per batch and flag the drifted option
+    let result = process_instruction(&program_id, &recount_accounts(vec![&shared[1]]), &recount_instruction(false, false));
+    assert!(result.is_ok(), "Failed to recount first batch: {:?}", result);
+    assert!(!decode::<RecountTally>(&tally_account).is_complete);
+    
+    let result = process_instruction(&program_id, &recount_accounts(vec![&bob_vote]), &recount_instruction(false, false));
+    assert!(result.is_ok(), "Failed to recount second batch: {:?}", result);
+    
+    let tally = decode::<RecountTally>(&tally_account);
+    assert!(tally.is_complete);
+    assert_eq!(tally.counts, vec![1, 1]);
+    assert_eq!(tally.discrepancies, vec![0]);
+    assert!(!tally.applied);
+    assert_eq!(decode::<VoteCount>(&shared[3]).counts, vec![5, 1]);
+    
+    // After the poll closes, a fresh recount repairs the vote count
+    let mut poll = decode::<Poll>(&shared[2]);
+    poll.is_active = false;
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    shared[2].data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    let result = process_instruction(&program_id, &recount_accounts(vec![&shared[1], &bob_vote]), &recount_instruction(true, true));
+    assert!(result.is_ok(), "Failed to apply recount: {:?}", result);
+    
+    let tally = decode::<RecountTally>(&tally_account);
+    assert!(tally.applied);
+    
+    let vote_count_data = decode::<VoteCount>(&shared[3]);
+    assert_eq!(vote_count_data.counts, vec![1, 1]);
+    assert_eq!(vote_count_data.total_voters, 2);
+}
+
+// Test that a recount notices votes cast between its batches within the same block
+#[test]
+fn test_recount_detects_vote_in_same_block() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let alice_key = Pubkey::new_unique();
+    let bob_key = Pubkey::new_unique();
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    let builder = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        1,
+        "Recounted Poll".to_string(),
+        vec!["Yes".to_string(), "No".to_string()],
+        MOCK_BLOCK_HEIGHT,
+        MOCK_BLOCK_HEIGHT + 100,
+    )
+    .allow_revote();
+    let poll_id = builder.poll_id();
+    let poll_key = pda::find_poll_address(&program_id, poll_id).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, poll_id).0;
+    let result = simulator.process(&builder.build(simulator.block_height(), create_mock_transaction()).unwrap());
+    assert!(result.is_ok(), "Failed to create poll: {:?}", result);
+    
+    let cast_vote = |simulator: &Simulator, voter_key: Pubkey, option_index: u8| {
+        let poll: Poll = simulator.load(&poll_key).unwrap();
+        let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+        let voter_record: Option<VoterRecord> =
+            simulator.load(&pda::find_voter_record_address(&program_id, poll_id, &voter_key).0);
+        let previous_vote: Option<Vote> = simulator.load(&pda::find_vote_address(&program_id, poll_id, &voter_key).0);
+        let mut builder = CastVoteBuilder::new(program_id, voter_key, &poll, &vote_count, option_index);
+        if let (Some(voter_record), Some(previous_vote)) = (&voter_record, &previous_vote) {
+            builder = builder.voter_record(voter_record).previous_vote(previous_vote);
+        }
+        builder.build(simulator.block_height(), create_mock_transaction()).unwrap()
+    };
+    for (voter_key, option_index) in [(alice_key, 0), (bob_key, 1)] {
+        let result = simulator.process(&cast_vote(&simulator, voter_key, option_index));
+        assert!(result.is_ok(), "Failed to cast vote: {:?}", result);
+    }
+    
+    let registry: VoterRegistry = simulator.load(&pda::find_voter_registry_address(&program_id, poll_id, 0).0).unwrap();
+    let recount = |next_voter: u64, restart: bool| {
+        let mut instruction =
+            client::recount(&program_id, &Pubkey::new_unique(), &registry, next_voter, restart, false, create_mock_transaction())
+                .unwrap();
+        // One voter per batch
+        instruction.accounts.truncate(6);
+        instruction
+    };
+    
+    // Alice is counted in the first batch, then changes her vote in the same block
+    let result = simulator.process(&recount(0, false));
+    assert!(result.is_ok(), "Failed to recount first batch: {:?}", result);
+    let result = simulator.process(&cast_vote(&simulator, alice_key, 1));
+    assert!(result.is_ok(), "Failed to change vote: {:?}", result);
+    
+    // The block height did not move, so only the revision shows the tally changed
+    let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+    assert_eq!(vote_count.last_updated, MOCK_BLOCK_HEIGHT);
+    assert_eq!(simulator.process(&recount(1, false)), Err(VotingError::RecountOutdated.into()));
+    
+    // A fresh recount matches the changed tally
+    let result = simulator.process(&recount(0, true));
+    assert!(result.is_ok(), "Failed to restart recount: {:?}", result);
+    let result = simulator.process(&recount(1, false));
+    assert!(result.is_ok(), "Failed to recount second batch: {:?}", result);
+    let tally: RecountTally = simulator.load(&pda::find_recount_address(&program_id, poll_id).0).unwrap();
+    assert!(tally.is_complete);
+    assert_eq!(tally.counts, vec![0, 2]);
+    assert!(tally.discrepancies.is_empty());
+}
+
+// Test the structured results returned by GetResults
+#[test]
+fn test_poll_results_shares_and_outcome() {
+    let program_id = Pubkey::new_unique();
+    let accounts = create_delegation_poll_accounts(&program_id, Pubkey::new_unique(), 1);
+    let mut poll = decode::<Poll>(&accounts[2]);
+    let mut vote_count = decode::<VoteCount>(&accounts[3]);
+    
+    vote_count.counts = vec![3, 1];
+    vote_count.raw_counts = vec![30, 10];
+    vote_count.proxy_counts = vec![1, 0];
+    vote_count.total_voters = 3;
+    poll.weight_transform.total_supply = 400;
+    
+    let poll_results = results::poll_results(&poll, &vote_count, MOCK_BLOCK_HEIGHT);
+    assert_eq!(poll_results.status, PollStatus::Active);
+    assert_eq!(poll_results.outcome, PollOutcome::Winner { option: 0 });
+    assert_eq!(poll_results.total_weight, 4);
+    assert_eq!(poll_results.turnout_bps, Some(1_000));
+    assert_eq!(poll_results.options[0].share_bps, 7_500);
+    assert_eq!(poll_results.options[1].share_bps, 2_500);
+    assert_eq!(poll_results.options[0].proxy_count, 1);
+    
+    // The encoding round-trips through the exported decoder
+    let encoded = borsh::to_vec(&poll_results).unwrap();
+    assert_eq!(PollResults::decode(&encoded).unwrap(), poll_results);
+    
+    let mut unsupported = encoded.clone();
+    unsupported[0] = RESULTS_VERSION + 1;
+    assert!(PollResults::decode(&unsupported).is_err());
+    
+    // Equal leaders tie; an ended poll reports its status
+    vote_count.counts = vec![2, 2];
+    let poll_results = results::poll_results(&poll, &vote_count, poll.end_time + 1);
+    assert_eq!(poll_results.status, PollStatus::Ended);
+    assert_eq!(poll_results.outcome, PollOutcome::Tie { options: vec![0, 1] });
+    
+    // Encrypted polls hide their counts until decrypted
+    poll.is_encrypted = true;
+    let poll_results = results::poll_results(&poll, &vote_count, MOCK_BLOCK_HEIGHT);
+    assert_eq!(poll_results.outcome, PollOutcome::Hidden);
+    assert!(poll_results.options.is_empty());
+    
+    // GetResults succeeds on the stored poll
+    let get_results_instruction = PollInstruction::GetResults { poll_id: 12345 };
+    let result = process_instruction(&program_id, &accounts[2..4], &borsh::to_vec(&get_results_instruction).unwrap());
+    assert!(result.is_ok(), "Failed to get results: {:?}", result);
+}
+
+// Test that event log lines parse back into the events that were emitted
+#[test]
+fn test_event_logs_round_trip() {
+    let voter_key = Pubkey::new_unique();
+    let vote_cast = VotingEvent::VoteCast {
+        poll_id: 12345,
+        voter: voter_key,
+        option_index: 1,
+        weight: 3,
+        raw_weight: 3,
+        proxies: vec![Pubkey::new_unique()],
+        is_revote: false,
+    };
+    let delegation_revoked = VotingEvent::DelegationRevoked { delegation_id: 7, delegate: None };
+    
+    // Runtime prefixes and unrelated log lines are skipped
+    let logs = ["Program log: Vote cast successfully for poll 12345: option 1".to_string(),
+        format!("Program log: {}", events::encode_log(&vote_cast)),
+        events::encode_log(&delegation_revoked)];
+    let parsed = events::parse_logs(logs.iter().map(String::as_str)).unwrap();
+    assert_eq!(parsed, vec![vote_cast.clone(), delegation_revoked]);
+    
+    // Other encoding versions and corrupted payloads are reported, not misread
+    let line = events::encode_log(&vote_cast);
+    let other_version = line.replacen(&format!("v{}:", events::EVENT_VERSION), "v99:", 1);
+    assert_eq!(events::parse_log(&other_version), Some(Err(EventParseError::UnsupportedVersion)));
+    assert_eq!(events::parse_log(&format!("{}zz", line)), Some(Err(EventParseError::InvalidHex)));
+    assert_eq!(events::parse_log(&line[..line.len() - 2]), Some(Err(EventParseError::InvalidEvent)));
+    assert_eq!(events::parse_log("Program log: Poll closed successfully: 12345"), None);
+}
+
+// Test that the indexer rebuilds polls, votes, delegations and tallies from event logs
+#[test]
+fn test_indexer_follows_event_logs() {
+    let program_id = Pubkey::new_unique();
+    let principal_key = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    
+    // Principal delegates everything to the voter, who then votes
+    let mut accounts = create_delegation_poll_accounts(&program_id, voter_key, 1);
+    accounts.push(create_delegation_account(&program_id, principal_key, voter_key, Some(12345), None));
+    accounts.push(create_voter_record_account(&program_id, principal_key));
+    
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 1,
+        weight: None,
+        encrypted_data: None,
+        zk_proof: None,
+        nonce: None,
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&cast_vote_instruction).unwrap();
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    assert!(result.is_ok(), "Failed to cast delegated vote: {:?}", result);
+    
+    // Serve the resulting accounts as an RPC node would, with the delegation at its derived address
+    let mut rpc = MockRpc::new();
+    for account in &accounts[1..4] {
+        rpc.set_account(*account.key, account.data.borrow().to_vec());
+    }
+    rpc.set_account(
+        pda::find_delegation_address(&program_id, 1).0,
+        accounts[6].data.borrow().to_vec(),
+    );
+    
+    let poll_created = VotingEvent::PollCreated {
+        poll_id: 12345,
+        creator: Pubkey::new_unique(),
+        options: 2,
+        start_time: MOCK_BLOCK_HEIGHT - 50,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+    };
+    let delegation_created = VotingEvent::DelegationCreated {
+        delegation_id: 1,
+        delegator: principal_key,
+        delegates: vec![voter_key],
+        poll_id: Some(12345),
+        topic: None,
+    };
+    let vote_cast = VotingEvent::VoteCast {
+        poll_id: 12345,
+        voter: voter_key,
+        option_index: 1,
+        weight: 2,
+        raw_weight: 2,
+        proxies: vec![principal_key],
+        is_revote: false,
+    };
+    let logs = [format!("Program log: {}", events::encode_log(&poll_created)),
+        "Program log: Poll created successfully: 12345".to_string(),
+        format!("Program log: {}", events::encode_log(&delegation_created)),
+        format!("Program log: {}", events::encode_log(&vote_cast))];
+    
+    let mut index = Indexer::new(program_id);
+    assert_eq!(index.ingest_logs(logs.iter().map(String::as_str), &rpc), Ok(3));
+    
+    let active_polls = index.active_polls(MOCK_BLOCK_HEIGHT);
+    assert_eq!(active_polls.len(), 1);
+    assert_eq!(active_polls[0].id, 12345);
+    assert!(index.active_polls(MOCK_BLOCK_HEIGHT + 1001).is_empty());
+    
+    let votes = index.votes_by_voter(&voter_key);
+    assert_eq!(votes.len(), 1);
+    assert_eq!(votes[0].weight, 2);
+    assert_eq!(votes[0].proxies[0].principal, principal_key);
+    assert!(index.votes_by_voter(&principal_key).is_empty());
+    
+    let delegations = index.delegations_to(&voter_key);
+    assert_eq!(delegations.len(), 1);
+    assert_eq!(delegations[0].delegator, principal_key);
+    
+    assert_eq!(index.tally(12345).unwrap().counts, vec![0, 2]);
+    let poll_results = index.results(12345, MOCK_BLOCK_HEIGHT).unwrap();
+    assert_eq!(poll_results.outcome, PollOutcome::Winner { option: 1 });
+    
+    // A snapshot file carries the same accounts and logs
+    let mut snapshot_text = String::from("# accounts then logs\n");
+    for account in &accounts[1..4] {
+        snapshot_text.push_str(&format!(
+            "account {} {}\n",
+            hex::encode(&account.key.serialize()),
+            hex::encode(&account.data.borrow())
+        ));
+    }
+    snapshot_text.push_str(&format!("log {}\n", events::encode_log(&vote_cast)));
+    
+    let snapshot = Snapshot::parse(&snapshot_text).unwrap();
+    let mut snapshot_index = Indexer::new(program_id);
+    snapshot_index.ingest_snapshot(&snapshot).unwrap();
+    assert_eq!(snapshot_index.votes_by_voter(&voter_key).len(), 1);
+    assert_eq!(snapshot_index.tally(12345).unwrap().proxy_counts, vec![0, 1]);
+    
+    assert_eq!(
+        Snapshot::parse("account not-hex 00").unwrap_err(),
+        IndexerError::InvalidSnapshotLine(1)
+    );
+}
+
+// Test that the client builders validate like the program and produce instructions it accepts
+#[test]
+fn test_client_builders_match_program() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let principal_key = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let mut store: HashMap<Pubkey, AccountInfo> = HashMap::new();
+    
+    let create_poll = |end_time: u64| {
+        CreatePollBuilder::new(
+            program_id,
+            creator_key,
+            7,
+            "Client Poll".to_string(),
+            vec!["Yes".to_string(), "No".to_string()],
+            MOCK_BLOCK_HEIGHT - 10,
+            end_time,
+        )
+        .allow_delegation(1, false)
+    };
+    
+    // Invalid polls are rejected with the error the program would return
+    let result = create_poll(MOCK_BLOCK_HEIGHT).build(MOCK_BLOCK_HEIGHT, create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::InvalidPollParameters.into());
+    
+    let builder = create_poll(MOCK_BLOCK_HEIGHT + 1000);
+    let poll_id = builder.poll_id();
+    assert_eq!(poll_id, pda::poll_id(&creator_key, 7));
+    
+    let instruction = builder.build(MOCK_BLOCK_HEIGHT, create_mock_transaction()).unwrap();
+    assert_eq!(instruction.accounts.len(), 4);
+    assert_eq!(instruction.accounts[1].pubkey, pda::find_poll_address(&program_id, poll_id).0);
+    
+    let accounts = accounts_for(&program_id, &mut store, &instruction);
+    let result = process_instruction(&program_id, &accounts, &instruction.data);
+    assert!(result.is_ok(), "Failed to create poll from builder: {:?}", result);
+    
+    // The principal delegates to the voter for this poll
+    let delegate_vote = DelegateVoteBuilder::new(
+        program_id,
+        principal_key,
+        3,
+        vec![DelegationShare { delegate: voter_key, bps: 10_000 }],
+    )
+    .poll(poll_id);
+    let delegation_id = delegate_vote.delegation_id();
+    
+    let instruction = delegate_vote.build(MOCK_BLOCK_HEIGHT, create_mock_transaction()).unwrap();
+    let accounts = accounts_for(&program_id, &mut store, &instruction);
+    let result = process_instruction(&program_id, &accounts, &instruction.data);
+    assert!(result.is_ok(), "Failed to delegate from builder: {:?}", result);
+    
+    // The voter carries the principal's weight
+    let poll_key = pda::find_poll_address(&program_id, poll_id).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, poll_id).0;
+    let poll = Poll::deserialize(&mut &store[&poll_key].data.borrow()[..]).unwrap();
+    let vote_count = VoteCount::deserialize(&mut &store[&vote_count_key].data.borrow()[..]).unwrap();
+    
+    let result = CastVoteBuilder::new(program_id, voter_key, &poll, &vote_count, 2)
+        .build(MOCK_BLOCK_HEIGHT, create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::InvalidOptionIndex.into());
+    
+    let instruction = CastVoteBuilder::new(program_id, voter_key, &poll, &vote_count, 1)
+        .proxy_group(ProxyGroup {
+            principal: principal_key,
+            delegation_ids: vec![delegation_id],
+            displaced_delegates: Vec::new(),
+        })
+        .build(MOCK_BLOCK_HEIGHT, create_mock_transaction())
+        .unwrap();
+    assert_eq!(instruction.accounts.len(), 8);
+    
+    let accounts = accounts_for(&program_id, &mut store, &instruction);
+    let result = process_instruction(&program_id, &accounts, &instruction.data);
+    assert!(result.is_ok(), "Failed to cast vote from builder: {:?}", result);
+    
+    let vote_count = VoteCount::deserialize(&mut &store[&vote_count_key].data.borrow()[..]).unwrap();
+    assert_eq!(vote_count.counts, vec![0, 2]);
+    assert_eq!(vote_count.proxy_counts, vec![0, 1]);
+}
+
+// Test a whole election run through the simulator
+#[test]
+fn test_simulated_election() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let voter_keys = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    // Create a poll that opens in 10 blocks
+    let builder = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        1,
+        "Simulated Poll".to_string(),
+        vec!["Yes".to_string(), "No".to_string()],
+        MOCK_BLOCK_HEIGHT + 10,
+        MOCK_BLOCK_HEIGHT + 100,
+    );
+    let poll_id = builder.poll_id();
+    let instruction = builder.build(simulator.block_height(), create_mock_transaction()).unwrap();
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to create poll: {:?}", result);
+    
+    // The state transition spends the written accounts' UTXOs then the fee input, which the creator
+    // signs, and the creation is logged as an event
+    let processed = simulator.last_processed().unwrap();
+    let fee_tx: Transaction = bitcoin::consensus::deserialize(&create_mock_transaction()).unwrap();
+    let state_transaction = processed.state_transaction.as_ref().unwrap();
+    assert_eq!(state_transaction.input.last(), fee_tx.input.first());
+    let fee_input_index = state_transaction.input.len() as u32 - 1;
+    assert_eq!(processed.inputs_to_sign, vec![InputToSign { index: fee_input_index, signer: creator_key }]);
+    let logged = events::parse_logs(processed.logs.iter().map(String::as_str)).unwrap();
+    assert!(matches!(logged[..], [VotingEvent::PollCreated { poll_id: id, .. }] if id == poll_id));
+    
+    let poll_key = pda::find_poll_address(&program_id, poll_id).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, poll_id).0;
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    
+    // Voting before the poll opens fails and leaves no trace
+    let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+    let instruction = CastVoteBuilder::new(program_id, voter_keys[0], &poll, &vote_count, 0)
+        .build(poll.start_time, create_mock_transaction())
+        .unwrap();
+    assert_eq!(simulator.process(&instruction), Err(VotingError::PollNotStarted.into()));
+    assert!(simulator.account(&pda::find_vote_address(&program_id, poll_id, &voter_keys[0]).0).is_none());
+    assert!(simulator.last_processed().unwrap().state_transaction.is_none());
+    
+    // Once the poll opens, two voters pick "Yes" and one picks "No"
+    simulator.advance_blocks(20);
+    for (voter_key, option_index) in voter_keys.iter().zip([0, 1, 0]) {
+        let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+        let instruction = CastVoteBuilder::new(program_id, *voter_key, &poll, &vote_count, option_index)
+            .build(simulator.block_height(), create_mock_transaction())
+            .unwrap();
+        let result = simulator.process(&instruction);
+        assert!(result.is_ok(), "Failed to cast vote: {:?}", result);
+    }
+    
+    let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+    assert_eq!(vote_count.counts, vec![2, 1]);
+    assert_eq!(vote_count.last_updated, MOCK_BLOCK_HEIGHT + 20);
+    
+    // After the poll ends the creator closes it and the results are final
+    simulator.advance_blocks(100);
+    let instruction = client::close_poll(&program_id, &creator_key, poll_id, create_mock_transaction()).unwrap();
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to close poll: {:?}", result);
+    
+    let instruction = client::get_results(&program_id, poll_id).unwrap();
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to get results: {:?}", result);
+    
+    let return_data = simulator.last_processed().unwrap().return_data.clone().unwrap();
+    let poll_results = PollResults::decode(&return_data).unwrap();
+    assert!(poll_results.is_finalized);
+    assert_eq!(poll_results.outcome, PollOutcome::Winner { option: 0 });
+    assert_eq!(poll_results.total_voters, 3);
+    
+    // The simulator's logs and accounts feed the off-chain indexer
+    let mut index = Indexer::new(program_id);
+    for processed in simulator.processed().iter().filter(|processed| processed.result.is_ok()) {
+        index.ingest_logs(processed.logs.iter().map(String::as_str), &simulator).unwrap();
     }
+    assert_eq!(index.poll_votes(poll_id).count(), 3);
+    assert_eq!(index.results(poll_id, simulator.block_height()), Some(poll_results));
+}
+
+// Test that the simulator only runs instructions against registered or newly created accounts
+#[test]
+fn test_simulator_rejects_unknown_accounts() {
+    let program_id = Pubkey::new_unique();
+    let delegator_key = Pubkey::new_unique();
+    let delegate_key = Pubkey::new_unique();
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    // Reading a poll that was never created fails before the program runs
+    let instruction = client::get_results(&program_id, 99).unwrap();
+    assert_eq!(simulator.process(&instruction), Err(ProgramError::UninitializedAccount));
+    assert!(simulator.account(&pda::find_poll_address(&program_id, 99).0).is_none());
+    
+    // The delegate's key is read and must be registered
+    let delegate_vote = DelegateVoteBuilder::new(
+        program_id,
+        delegator_key,
+        0,
+        vec![DelegationShare { delegate: delegate_key, bps: 10_000 }],
+    );
+    let delegation_key = pda::find_delegation_address(&program_id, delegate_vote.delegation_id()).0;
+    let profile_key = pda::find_delegate_profile_address(&program_id, &delegate_key).0;
+    let instruction = delegate_vote.build(simulator.block_height(), create_mock_transaction()).unwrap();
+    assert_eq!(simulator.process(&instruction), Err(ProgramError::UninitializedAccount));
+    
+    // The delegate's profile is writable but left empty, so the instruction did not create it
+    simulator.set_wallet(delegate_key);
+    assert_eq!(simulator.process(&instruction), Err(ProgramError::UninitializedAccount));
+    assert!(simulator.account(&delegation_key).is_none());
+    assert!(simulator.account(&profile_key).is_none());
+    
+    // Once the empty profile is registered, the delegation account is created by the instruction
+    simulator.set_program_account(profile_key, Vec::new());
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to delegate: {:?}", result);
+    let delegation: Delegation = simulator.load(&delegation_key).unwrap();
+    assert_eq!(delegation.delegator, delegator_key);
+    assert!(simulator.account(&delegator_key).is_some());
+}
+
+// Test that releasing a delegate's carried slice is logged and followed by the indexer
+#[test]
+fn test_indexer_follows_released_proxy_slice() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let principal_key = Pubkey::new_unique();
+    let delegate_key = Pubkey::new_unique();
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    let builder = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        1,
+        "Released Slice Poll".to_string(),
+        vec!["Yes".to_string(), "No".to_string()],
+        MOCK_BLOCK_HEIGHT - 10,
+        MOCK_BLOCK_HEIGHT + 100,
+    )
+    .allow_delegation(1, false);
+    let poll_id = builder.poll_id();
+    let instruction = builder.build(simulator.block_height(), create_mock_transaction()).unwrap();
+    assert!(simulator.process(&instruction).is_ok());
+    
+    // The principal delegates to the unregistered delegate, who votes carrying the principal's weight
+    simulator.set_wallet(delegate_key);
+    simulator.set_program_account(pda::find_delegate_profile_address(&program_id, &delegate_key).0, Vec::new());
+    let delegate_vote = DelegateVoteBuilder::new(
+        program_id,
+        principal_key,
+        1,
+        vec![DelegationShare { delegate: delegate_key, bps: 10_000 }],
+    )
+    .poll(poll_id);
+    let delegation_id = delegate_vote.delegation_id();
+    let instruction = delegate_vote.build(simulator.block_height(), create_mock_transaction()).unwrap();
+    assert!(simulator.process(&instruction).is_ok());
+    
+    let poll_key = pda::find_poll_address(&program_id, poll_id).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, poll_id).0;
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+    let instruction = CastVoteBuilder::new(program_id, delegate_key, &poll, &vote_count, 1)
+        .proxy_group(ProxyGroup {
+            principal: principal_key,
+            delegation_ids: vec![delegation_id],
+            displaced_delegates: Vec::new(),
+        })
+        .build(simulator.block_height(), create_mock_transaction())
+        .unwrap();
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to cast delegated vote: {:?}", result);
+    
+    let mut index = Indexer::new(program_id);
+    for processed in simulator.processed().iter().filter(|processed| processed.result.is_ok()) {
+        index.ingest_logs(processed.logs.iter().map(String::as_str), &simulator).unwrap();
+    }
+    assert_eq!(index.vote(poll_id, &delegate_key).unwrap().proxies.len(), 1);
+    
+    // The principal votes directly, which releases the delegate's slice before the vote is cast
+    let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+    let principal_record: VoterRecord =
+        simulator.load(&pda::find_voter_record_address(&program_id, poll_id, &principal_key).0).unwrap();
+    let instruction = CastVoteBuilder::new(program_id, principal_key, &poll, &vote_count, 0)
+        .voter_record(&principal_record)
+        .build(simulator.block_height(), create_mock_transaction())
+        .unwrap();
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to cast direct vote: {:?}", result);
+    
+    let logs = &simulator.last_processed().unwrap().logs;
+    let logged = events::parse_logs(logs.iter().map(String::as_str)).unwrap();
+    assert!(matches!(
+        logged[..],
+        [
+            VotingEvent::ProxySliceReleased { poll_id: id, carrier, principal, weight: 1 },
+            VotingEvent::VoteCast { .. },
+        ] if id == poll_id && carrier == delegate_key && principal == principal_key
+    ));
+    
+    // Only the new logs are ingested, and the delegate's vote is refreshed from the release
+    index.ingest_logs(logs.iter().map(String::as_str), &simulator).unwrap();
+    let delegate_vote = index.vote(poll_id, &delegate_key).unwrap();
+    assert!(delegate_vote.proxies.is_empty());
+    assert_eq!(delegate_vote.weight, 1);
+    assert_eq!(index.tally(poll_id).unwrap().counts, vec![1, 1]);
+}
+
+// Test extending a poll's end time
+#[test]
+fn test_extend_poll() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    // Two extensions are allowed while fewer than 2 votes have been cast
+    let instruction = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        1,
+        "Extendable Poll".to_string(),
+        vec!["Yes".to_string(), "No".to_string()],
+        MOCK_BLOCK_HEIGHT,
+        MOCK_BLOCK_HEIGHT + 100,
+    )
+    .extensions(2, Some(2))
+    .build(simulator.block_height(), create_mock_transaction())
+    .unwrap();
+    let poll_id = pda::poll_id(&creator_key, 1);
+    let poll_key = pda::find_poll_address(&program_id, poll_id).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, poll_id).0;
+    assert!(simulator.process(&instruction).is_ok());
+    
+    // Only the creator can extend the poll
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    let mut instruction =
+        client::extend_poll(&program_id, &poll, MOCK_BLOCK_HEIGHT + 200, simulator.block_height(), create_mock_transaction())
+            .unwrap();
+    instruction.accounts[0].pubkey = Pubkey::new_unique();
+    assert_eq!(simulator.process(&instruction), Err(VotingError::NotPollCreator.into()));
+    
+    // The end time only moves forward
+    let result = client::extend_poll(&program_id, &poll, poll.end_time, simulator.block_height(), create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::InvalidPollParameters.into());
+    
+    let instruction =
+        client::extend_poll(&program_id, &poll, MOCK_BLOCK_HEIGHT + 200, simulator.block_height(), create_mock_transaction())
+            .unwrap();
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to extend poll: {:?}", result);
+    
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    assert_eq!(poll.end_time, MOCK_BLOCK_HEIGHT + 200);
+    assert_eq!(poll.extension_count, 1);
+    let logged = events::parse_logs(simulator.last_processed().unwrap().logs.iter().map(String::as_str)).unwrap();
+    assert_eq!(logged, vec![VotingEvent::PollExtended { poll_id, end_time: MOCK_BLOCK_HEIGHT + 200, extension_count: 1 }]);
+    
+    // Voting past the original end time is now allowed, and meets the quorum
+    simulator.advance_blocks(150);
+    for _ in 0..2 {
+        let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+        let instruction = CastVoteBuilder::new(program_id, Pubkey::new_unique(), &poll, &vote_count, 0)
+            .build(simulator.block_height(), create_mock_transaction())
+            .unwrap();
+        let result = simulator.process(&instruction);
+        assert!(result.is_ok(), "Failed to cast vote: {:?}", result);
+    }
+    
+    // With the quorum met the poll can no longer be extended
+    let instruction =
+        client::extend_poll(&program_id, &poll, MOCK_BLOCK_HEIGHT + 300, simulator.block_height(), create_mock_transaction())
+            .unwrap();
+    assert_eq!(simulator.process(&instruction), Err(VotingError::QuorumReached.into()));
+    
+    // Without a quorum, extensions stop once they are used up
+    let builder = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        2,
+        "Extend Once".to_string(),
+        vec!["Yes".to_string(), "No".to_string()],
+        simulator.block_height(),
+        simulator.block_height() + 100,
+    )
+    .extensions(1, None);
+    let poll_key = pda::find_poll_address(&program_id, builder.poll_id()).0;
+    let instruction = builder.build(simulator.block_height(), create_mock_transaction()).unwrap();
+    assert!(simulator.process(&instruction).is_ok());
+    
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    let extend = |new_end_time: u64| {
+        client::extend_poll(&program_id, &poll, new_end_time, MOCK_BLOCK_HEIGHT + 150, create_mock_transaction()).unwrap()
+    };
+    assert!(simulator.process(&extend(poll.end_time + 10)).is_ok());
+    assert_eq!(simulator.process(&extend(poll.end_time + 20)), Err(VotingError::ExtensionLimitReached.into()));
+    
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    let result = client::extend_poll(&program_id, &poll, poll.end_time + 10, simulator.block_height(), create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::ExtensionLimitReached.into());
+}
+
+// Test closing a poll early once its outcome is decided
+#[test]
+fn test_early_close_once_decided() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let caller_key = Pubkey::new_unique();
+    let token_key = Pubkey::new_unique();
+    let oracle_key = Pubkey::new_unique();
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    let create_poll = |nonce: u64| {
+        CreatePollBuilder::new(
+            program_id,
+            creator_key,
+            nonce,
+            "Decidable Poll".to_string(),
+            vec!["Yes".to_string(), "No".to_string()],
+            MOCK_BLOCK_HEIGHT,
+            MOCK_BLOCK_HEIGHT + 100,
+        )
+        .allow_early_close()
+    };
+    
+    // Early close needs a weighted poll with a declared total supply
+    let result = create_poll(1).build(simulator.block_height(), create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::InvalidPollParameters.into());
+    
+    simulator.set_wallet(token_key);
+    let builder = create_poll(2)
+        .weighted(WeightSource::TokenBalance, Some(token_key))
+        .balance_oracle(oracle_key, 10)
+        .weight_transform(WeightTransform { total_supply: 1_000, ..WeightTransform::NONE })
+        .early_voter_bonus(10, BonusSchedule::Linear);
+    let poll_id = builder.poll_id();
+    let poll_key = pda::find_poll_address(&program_id, poll_id).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, poll_id).0;
+    let instruction = builder.build(simulator.block_height(), create_mock_transaction()).unwrap();
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to create poll: {:?}", result);
+    
+    let set_counts = |simulator: &mut Simulator, counts: Vec<u64>, proxy_counts: Vec<u64>| {
+        let mut vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+        vote_count.raw_counts = counts.clone();
+        vote_count.counts = counts;
+        vote_count.proxy_counts = proxy_counts;
+        simulator.set_program_account(vote_count_key, borsh::to_vec(&vote_count).unwrap());
+    };
+    let close_poll = client::close_poll(&program_id, &caller_key, poll_id, create_mock_transaction()).unwrap();
+    
+    // The poll counts at most 1100 raw weight (1000 tokens with the 10% bonus), so 500 more could
+    // still overturn 500 to 100
+    set_counts(&mut simulator, vec![500, 100], vec![0, 0]);
+    assert!(!results::is_decided(&simulator.load(&poll_key).unwrap(), &simulator.load(&vote_count_key).unwrap()));
+    assert_eq!(simulator.process(&close_poll), Err(VotingError::NotPollCreator.into()));
+    
+    // Proxy votes may still be displaced by their principals
+    set_counts(&mut simulator, vec![700, 100], vec![300, 0]);
+    assert_eq!(simulator.process(&close_poll), Err(VotingError::NotPollCreator.into()));
+    
+    // 300 more cannot overturn 700 to 100
+    set_counts(&mut simulator, vec![700, 100], vec![0, 0]);
+    
+    // A balance attested beyond the declared supply cannot be counted
+    let voter_key = Pubkey::new_unique();
+    let token_balance = TokenBalance {
+        account_type: AccountType::TokenBalance,
+        version: ACCOUNT_VERSION,
+        owner: voter_key,
+        token: token_key,
+        amount: 400,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        attested_by: oracle_key,
+        locked_amount: 0,
+    };
+    simulator.set_program_account(
+        pda::find_token_balance_address(&program_id, &voter_key, &token_key).0,
+        borsh::to_vec(&token_balance).unwrap(),
+    );
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+    let cast_vote = CastVoteBuilder::new(program_id, voter_key, &poll, &vote_count, 1)
+        .build(simulator.block_height(), create_mock_transaction())
+        .unwrap();
+    assert_eq!(simulator.process(&cast_vote), Err(VotingError::InvalidVoteWeight.into()));
+    
+    let result = simulator.process(&close_poll);
+    assert!(result.is_ok(), "Failed to close decided poll: {:?}", result);
+    
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+    assert!(!poll.is_active);
+    assert!(vote_count.is_finalized);
+}
+
+// Test editing a poll before voting starts
+#[test]
+fn test_update_poll_before_start() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    let builder = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        1,
+        "Draft Poll".to_string(),
+        vec!["Yes".to_string(), "No".to_string()],
+        MOCK_BLOCK_HEIGHT + 10,
+        MOCK_BLOCK_HEIGHT + 100,
+    );
+    let poll_id = builder.poll_id();
+    let poll_key = pda::find_poll_address(&program_id, poll_id).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, poll_id).0;
+    let instruction = builder.build(simulator.block_height(), create_mock_transaction()).unwrap();
+    assert!(simulator.process(&instruction).is_ok());
+    
+    // Edits are checked like a new poll
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    let result = UpdatePollBuilder::new(program_id, &poll)
+        .title(String::new())
+        .build(simulator.block_height(), create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::InvalidPollParameters.into());
+    
+    // Only the creator can edit the poll
+    let mut instruction = UpdatePollBuilder::new(program_id, &poll)
+        .title("Final Poll".to_string())
+        .build(simulator.block_height(), create_mock_transaction())
+        .unwrap();
+    instruction.accounts[0].pubkey = Pubkey::new_unique();
+    assert_eq!(simulator.process(&instruction), Err(VotingError::NotPollCreator.into()));
+    
+    // A third option resizes the counts
+    let instruction = UpdatePollBuilder::new(program_id, &poll)
+        .title("Final Poll".to_string())
+        .options(vec!["Yes".to_string(), "No".to_string(), "Abstain".to_string()])
+        .times(MOCK_BLOCK_HEIGHT + 20, MOCK_BLOCK_HEIGHT + 200)
+        .build(simulator.block_height(), create_mock_transaction())
+        .unwrap();
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to update poll: {:?}", result);
+    
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    assert_eq!(poll.title, "Final Poll");
+    assert_eq!(poll.options.len(), 3);
+    assert_eq!((poll.start_time, poll.end_time), (MOCK_BLOCK_HEIGHT + 20, MOCK_BLOCK_HEIGHT + 200));
+    let vote_count: VoteCount = simulator.load(&vote_count_key).unwrap();
+    assert_eq!(vote_count.counts, vec![0, 0, 0]);
+    assert_eq!(vote_count.raw_counts, vec![0, 0, 0]);
+    assert_eq!(vote_count.proxy_counts, vec![0, 0, 0]);
+    let logged = events::parse_logs(simulator.last_processed().unwrap().logs.iter().map(String::as_str)).unwrap();
+    assert_eq!(
+        logged,
+        vec![VotingEvent::PollUpdated {
+            poll_id,
+            options: 3,
+            start_time: MOCK_BLOCK_HEIGHT + 20,
+            end_time: MOCK_BLOCK_HEIGHT + 200,
+        }]
+    );
+    
+    // A poll with votes can no longer be edited
+    let edit = UpdatePollBuilder::new(program_id, &poll)
+        .description("Last call".to_string())
+        .build(simulator.block_height(), create_mock_transaction())
+        .unwrap();
+    let mut voted = vote_count.clone();
+    voted.total_voters = 1;
+    simulator.set_program_account(vote_count_key, borsh::to_vec(&voted).unwrap());
+    assert_eq!(simulator.process(&edit), Err(VotingError::PollHasVotes.into()));
+    simulator.set_program_account(vote_count_key, borsh::to_vec(&vote_count).unwrap());
+    
+    // Nor once voting has started
+    simulator.advance_blocks(20);
+    assert_eq!(simulator.process(&edit), Err(VotingError::PollAlreadyStarted.into()));
+    let result = UpdatePollBuilder::new(program_id, &poll)
+        .description("Last call".to_string())
+        .build(simulator.block_height(), create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::PollAlreadyStarted.into());
+}
+
+// Test polls referencing off-chain documents
+#[test]
+fn test_poll_content_metadata() {
+    let program_id = Pubkey::new_unique();
+    let creator_key = Pubkey::new_unique();
+    let mut simulator = Simulator::new(program_id);
+    simulator.set_block_height(MOCK_BLOCK_HEIGHT);
+    
+    let proposal = "# Treasury upgrade\n\nMove the treasury to a 3-of-5 multisig.".repeat(100).into_bytes();
+    let rationale = b"Keep the current single-key treasury.".to_vec();
+    let mut store = MemoryStore::new();
+    store.insert("ipfs://proposal".to_string(), proposal.clone());
+    store.insert("ipfs://rationale".to_string(), rationale.clone());
+    
+    // Option documents must match the options in number
+    let builder = CreatePollBuilder::new(
+        program_id,
+        creator_key,
+        1,
+        "Treasury Upgrade".to_string(),
+        vec!["Approve".to_string(), "Reject".to_string()],
+        MOCK_BLOCK_HEIGHT,
+        MOCK_BLOCK_HEIGHT + 100,
+    )
+    .metadata(ContentRef::new("ipfs://proposal".to_string(), &proposal));
+    let result = builder
+        .clone()
+        .option_metadata(vec![None])
+        .build(simulator.block_height(), create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::InvalidPollParameters.into());
+    
+    let long_uri = format!("ipfs://{}", "a".repeat(MAX_CONTENT_URI_LEN));
+    let result = builder
+        .clone()
+        .option_metadata(vec![None, Some(ContentRef::new(long_uri, &rationale))])
+        .build(simulator.block_height(), create_mock_transaction());
+    assert_eq!(result.unwrap_err(), VotingError::InvalidPollParameters.into());
+    
+    // Only the URIs and hashes are stored on-chain
+    let poll_key = pda::find_poll_address(&program_id, builder.poll_id()).0;
+    let instruction = builder
+        .option_metadata(vec![None, Some(ContentRef::new("ipfs://rationale".to_string(), &rationale))])
+        .build(simulator.block_height(), create_mock_transaction())
+        .unwrap();
+    let result = simulator.process(&instruction);
+    assert!(result.is_ok(), "Failed to create poll: {:?}", result);
+    
+    let poll: Poll = simulator.load(&poll_key).unwrap();
+    assert!(borsh::to_vec(&poll).unwrap().len() < proposal.len());
+    assert_eq!(poll.metadata.as_ref().unwrap().hash, content::content_hash(&proposal));
+    
+    // The client fetches the documents and checks them against the poll
+    let fetched = content::fetch_poll_content(&store, &poll).unwrap();
+    assert_eq!(fetched.metadata, Some(proposal.clone()));
+    assert_eq!(fetched.options, vec![None, Some(rationale.clone())]);
+    
+    // Altered or missing documents are rejected
+    store.insert("ipfs://rationale".to_string(), b"Approve everything.".to_vec());
+    let result = content::fetch_poll_content(&store, &poll);
+    assert!(matches!(result, Err(ContentError::HashMismatch { ref uri, .. }) if uri == "ipfs://rationale"));
+    
+    let result = content::fetch_poll_content(&MemoryStore::new(), &poll);
+    assert_eq!(result, Err(ContentError::NotFound("ipfs://proposal".to_string())));
+    
+    // A local directory store never resolves outside its root
+    let directory = DirectoryStore::new("/var/lib/voting/content");
+    assert_eq!(
+        directory.path("ipfs://bafyproposal"),
+        Some(std::path::PathBuf::from("/var/lib/voting/content/bafyproposal"))
+    );
+    assert_eq!(directory.path("https://example.com/../../etc/passwd"), None);
+    assert_eq!(directory.path("file:///etc/passwd"), None);
+}
+
+// Test encrypted voting
+#[test]
+fn test_encrypted_voting() {
+    // Create mock accounts
+    let program_id = Pubkey::new_unique();
+    let voter_key = Pubkey::new_unique();
+    let vote_key = pda::find_vote_address(&program_id, 12345, &voter_key).0;
+    let poll_key = pda::find_poll_address(&program_id, 12345).0;
+    let vote_count_key = pda::find_vote_count_address(&program_id, 12345).0;
+    let voter_registry_key = pda::find_voter_registry_address(&program_id, 12345, 0).0;
+    
+    let voter_account = create_account(voter_key, true, true, 100, 0);
+    let vote_account = create_program_account(&program_id, vote_key, true, 1000);
+    let vote_count_account = create_program_account(&program_id, vote_count_key, true, 1000);
+    let voter_registry_account = create_program_account(&program_id, voter_registry_key, true, 1000);
+    let voter_record_account = create_voter_record_account(&program_id, voter_key);
+    
+    // Create and initialize poll account with encryption
+    let poll_account = create_program_account(&program_id, poll_key, true, 1000);
+    let poll = Poll {
+        account_type: AccountType::Poll,
+        version: ACCOUNT_VERSION,
+        id: 12345,
+        creator: Pubkey::new_unique(),
+        title: "Encrypted Poll".to_string(),
+        description: "This is an encrypted poll".to_string(),
+        options: vec!["Option 1".to_string(), "Option 2".to_string()],
+        start_time: MOCK_BLOCK_HEIGHT - 50,
+        end_time: MOCK_BLOCK_HEIGHT + 1000,
+        is_private: false,
+        allow_revote: false,
+        is_active: true,
+        is_weighted: false,
+        allow_delegation: false,
+        is_encrypted: true, // Encryption enabled
+        decryption_key: None,
+        weight_token: None,
+        early_voter_bonus: 0,
+        bonus_schedule: BonusSchedule::Linear,
+        balance_oracle: None,
+        max_balance_age: 0,
+        weight_source: WeightSource::TokenBalance,
+        weight_transform: WeightTransform::NONE,
+        eligibility_asset: None,
+        max_delegation_depth: 0,
+        topic: None,
+        require_registered_delegates: false,
+        max_extensions: 0,
+        extension_count: 0,
+        quorum: None,
+        allow_early_close: false,
+        metadata: None,
+        option_metadata: Vec::new(),
+    };
+    
+    let poll_data = borsh::to_vec(&poll).unwrap();
+    poll_account.data.borrow_mut()[..poll_data.len()].copy_from_slice(&poll_data);
+    
+    // Initialize vote count account
+    let vote_count = VoteCount {
+        account_type: AccountType::VoteCount,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        counts: vec![0, 0],
+        raw_counts: vec![0, 0],
+        proxy_counts: vec![0, 0],
+        total_voters: 0,
+        last_updated: MOCK_BLOCK_HEIGHT,
+        revision: 0,
+        is_finalized: false,
+    };
+    
+    let vote_count_data = borsh::to_vec(&vote_count).unwrap();
+    vote_count_account.data.borrow_mut()[..vote_count_data.len()].copy_from_slice(&vote_count_data);
+    
+    // Initialize voter registry account
+    let voter_registry = VoterRegistry {
+        account_type: AccountType::VoterRegistry,
+        version: ACCOUNT_VERSION,
+        poll_id: 12345,
+        page: 0,
+        voters: Vec::new(),
+    };
+    
+    let voter_registry_data = borsh::to_vec(&voter_registry).unwrap();
+    voter_registry_account.data.borrow_mut()[..voter_registry_data.len()].copy_from_slice(&voter_registry_data);
+    
+    let accounts = vec![
+        voter_account,
+        vote_account,
+        poll_account,
+        vote_count_account,
+        voter_registry_account,
+        voter_record_account,
+    ];
+    
+    // Mock encrypted data and nonce
+    let encrypted_data = vec![1, 2, 3, 4, 5]; // Mock encrypted vote
+    let nonce = vec![6, 7, 8, 9, 10]; // Mock nonce
+    
+    // Create cast vote instruction data with encryption
+    let cast_vote_instruction = PollInstruction::CastVote {
+        poll_id: 12345,
+        option_index: 0, // This is still needed for the contract, but the real vote is encrypted
+        weight: None,
+        encrypted_data: Some(encrypted_data.clone()),
+        zk_proof: None,
+        nonce: Some(nonce.clone()),
+        tx_hex: create_mock_transaction(),
+    };
+    
+    let instruction_data = borsh::to_vec(&cast_vote_instruction).unwrap();
+    
+    // Process the instruction
+    let result = process_instruction(&program_id, &accounts, &instruction_data);
+    
+    // Verify the result
+    assert!(result.is_ok(), "Failed to cast encrypted vote: {:?}", result);
+    
+    // Verify the encrypted vote was recorded correctly
+    let vote_data = decode::<Vote>(&accounts[1]);
+    assert_eq!(vote_data.encrypted_data.unwrap(), encrypted_data);
+    assert_eq!(vote_data.nonce.unwrap(), nonce);
+    
+    // Verify the vote count was updated
+    let vote_count_data = decode::<VoteCount>(&accounts[3]);
+    assert_eq!(vote_count_data.total_voters, 1);
 }
 
 // Gas optimization techniques
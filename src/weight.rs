@@ -0,0 +1,208 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{EscrowPosition, VotingError, MAX_ESCROW_LOCK_BLOCKS};
+
+// Deterministic vote weight computation.
+// All arithmetic is integer basis-point math with checked overflow, so every
+// validator computes the same weight regardless of target.
+
+/// Basis points in 100%
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BonusSchedule {
+    /// Bonus decays linearly from the full bonus at start time to zero at end time
+    Linear,
+    /// Poll is split into `steps` equal periods; the bonus drops by an equal share at each boundary
+    Step { steps: u8 },
+    /// Bonus halves every `half_life` blocks, decaying linearly within each half-life
+    Exponential { half_life: u64 },
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeightCurve {
+    /// Effective weight equals the (capped) raw weight
+    Linear,
+    /// Effective weight is the integer square root of the raw weight
+    SquareRoot,
+    /// Effective weight is `1 + floor(log2(raw weight))`, or zero for zero weight
+    Logarithmic,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WeightTransform {
+    /// Hard cap on any single voter's raw weight
+    pub max_weight: Option<u64>,
+    /// Cap on any single voter's raw weight, in basis points of `total_supply`
+    pub max_supply_bps: Option<u16>,
+    /// Total supply of the weighting asset (required for the supply cap)
+    pub total_supply: u64,
+    /// Dampening curve applied after the caps
+    pub curve: WeightCurve,
+}
+
+impl WeightTransform {
+    /// Transform that leaves weights untouched
+    pub const NONE: WeightTransform = WeightTransform {
+        max_weight: None,
+        max_supply_bps: None,
+        total_supply: 0,
+        curve: WeightCurve::Linear,
+    };
+}
+
+/// Whether a bonus schedule is well-formed
+pub fn is_valid_schedule(schedule: &BonusSchedule) -> bool {
+    match schedule {
+        BonusSchedule::Linear => true,
+        BonusSchedule::Step { steps } => *steps > 0,
+        BonusSchedule::Exponential { half_life } => *half_life > 0,
+    }
+}
+
+/// Whether a weight transform is well-formed
+pub fn is_valid_transform(transform: &WeightTransform) -> bool {
+    if transform.max_weight == Some(0) {
+        return false;
+    }
+
+    match transform.max_supply_bps {
+        Some(bps) => bps > 0 && bps as u64 <= BPS_DENOMINATOR && transform.total_supply > 0,
+        None => true,
+    }
+}
+
+/// Effective weight of a vote with `raw_weight`: caps are applied in raw units, then the curve
+pub fn apply_transform(raw_weight: u64, transform: &WeightTransform) -> u64 {
+    let mut weight = raw_weight;
+
+    if let Some(max_weight) = transform.max_weight {
+        weight = weight.min(max_weight);
+    }
+
+    if let Some(max_supply_bps) = transform.max_supply_bps {
+        let supply_cap = transform.total_supply as u128 * max_supply_bps as u128 / BPS_DENOMINATOR as u128;
+        weight = weight.min(supply_cap as u64);
+    }
+
+    match transform.curve {
+        WeightCurve::Linear => weight,
+        WeightCurve::SquareRoot => integer_sqrt(weight),
+        WeightCurve::Logarithmic => {
+            if weight == 0 {
+                0
+            } else {
+                1 + weight.ilog2() as u64
+            }
+        }
+    }
+}
+
+/// Portion of `weight` given by a delegation share of `share_bps`, rounded down
+pub fn apply_share(weight: u64, share_bps: u16) -> u64 {
+    (weight as u128 * share_bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+/// Floor of the square root of `value`
+pub fn integer_sqrt(value: u64) -> u64 {
+    if value < 2 {
+        return value;
+    }
+
+    // Newton's method, starting above the root so the sequence decreases monotonically
+    let mut x = 1u64 << (64 - value.leading_zeros()).div_ceil(2);
+    loop {
+        let next = (x + value / x) / 2;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
+}
+
+/// Early voter bonus in basis points for a vote cast at `current_time`.
+/// `max_bonus_percent` is the poll's `early_voter_bonus` (0-100).
+pub fn bonus_bps(
+    max_bonus_percent: u8,
+    schedule: &BonusSchedule,
+    start_time: u64,
+    end_time: u64,
+    current_time: u64,
+) -> u64 {
+    let max_bps = max_bonus_percent as u64 * BPS_DENOMINATOR / 100;
+    let duration = end_time.saturating_sub(start_time);
+
+    if max_bps == 0 || duration == 0 {
+        return 0;
+    }
+
+    // Votes before the start count as cast at the start, votes after the end get no bonus
+    let elapsed = current_time.saturating_sub(start_time).min(duration);
+
+    match *schedule {
+        BonusSchedule::Linear => {
+            let remaining = duration - elapsed;
+            (max_bps as u128 * remaining as u128 / duration as u128) as u64
+        }
+        BonusSchedule::Step { steps } => {
+            if steps == 0 {
+                return 0;
+            }
+
+            let steps = steps as u128;
+            let step_index = elapsed as u128 * steps / duration as u128;
+            (max_bps as u128 * (steps - step_index) / steps) as u64
+        }
+        BonusSchedule::Exponential { half_life } => {
+            if half_life == 0 || elapsed == duration {
+                return 0;
+            }
+
+            let halvings = elapsed / half_life;
+            if halvings >= 64 {
+                return 0;
+            }
+
+            // Interpolate between the bonus at this halving and half of it
+            let bonus = max_bps >> halvings;
+            let into_half_life = elapsed % half_life;
+            let decay = ((bonus / 2) as u128 * into_half_life as u128 / half_life as u128) as u64;
+            bonus - decay
+        }
+    }
+}
+
+/// Apply the early voter bonus to `base_weight`, rounding the bonus up so early
+/// voters always receive some of it.
+pub fn apply_early_voter_bonus(
+    base_weight: u64,
+    max_bonus_percent: u8,
+    schedule: &BonusSchedule,
+    start_time: u64,
+    end_time: u64,
+    current_time: u64,
+) -> Result<u64, VotingError> {
+    let bps = bonus_bps(max_bonus_percent, schedule, start_time, end_time, current_time);
+    if bps == 0 {
+        return Ok(base_weight);
+    }
+
+    let scaled = (base_weight as u128)
+        .checked_mul(bps as u128)
+        .ok_or(VotingError::InvalidVoteWeight)?;
+    let bonus = scaled.div_ceil(BPS_DENOMINATOR as u128);
+    let bonus = u64::try_from(bonus).map_err(|_| VotingError::InvalidVoteWeight)?;
+
+    base_weight.checked_add(bonus).ok_or(VotingError::InvalidVoteWeight)
+}
+
+/// Weight of an escrow position at `current_time`: the full amount for a maximum
+/// lock, decaying linearly to zero at the unlock height.
+pub fn escrow_weight(position: &EscrowPosition, current_time: u64) -> u64 {
+    let remaining = position
+        .unlock_height
+        .saturating_sub(current_time)
+        .min(MAX_ESCROW_LOCK_BLOCKS);
+
+    (position.amount as u128 * remaining as u128 / MAX_ESCROW_LOCK_BLOCKS as u128) as u64
+}